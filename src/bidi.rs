@@ -0,0 +1,30 @@
+use std::ops::Range;
+use unicode_bidi::{BidiInfo, Level};
+use unicode_segmentation::UnicodeSegmentation;
+
+// The graphemes of `text` as byte ranges, in the order they're shown from left to right.
+// Right-to-left runs are reordered by the Unicode BiDi algorithm, but the line itself is
+// taken as left-to-right so it keeps starting at the left edge.
+// Returns `None` if there's nothing right-to-left, so it's shown as is
+pub fn visual_order(text: &str) -> Option<Vec<Range<usize>>> {
+    let info = BidiInfo::new(text, Some(Level::ltr()));
+    if !info.has_rtl() {
+        return None;
+    }
+
+    let mut order = Vec::new();
+    for paragraph in &info.paragraphs {
+        let (levels, runs) = info.visual_runs(paragraph, paragraph.range.clone());
+        for run in runs {
+            let graphemes = text[run.clone()]
+                .grapheme_indices(true)
+                .map(|(i, g)| run.start + i..run.start + i + g.len());
+            if levels[run.start].is_rtl() {
+                order.extend(graphemes.rev());
+            } else {
+                order.extend(graphemes);
+            }
+        }
+    }
+    Some(order)
+}
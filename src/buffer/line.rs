@@ -1,11 +1,13 @@
-use std::{iter::Enumerate, ops::Bound};
+use std::{borrow::Cow, iter::Enumerate, ops::{Bound, Range}};
 use unicode_segmentation::{GraphemeIndices, UnicodeSegmentation};
-use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+use crate::width::{self, DisplayWidth};
+use super::LineEnding;
 use std::ops::RangeBounds;
 
 pub struct ColumnIndices<'a> {
     iter: Enumerate<GraphemeIndices<'a>>,
-    column: usize
+    column: usize,
+    tab_width: usize
 }
 
 pub struct ColumnIndex<'a> {
@@ -22,7 +24,12 @@ impl<'a> Iterator for ColumnIndices<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         if let Some((index, (offset, grapheme))) = self.iter.next() {
             let column = self.column;
-            let width = grapheme.width_cjk(); 
+            let width = if grapheme == "\t" {
+                // Up to the next tab stop
+                self.tab_width - column % self.tab_width
+            } else {
+                grapheme.display_width()
+            };
             self.column += width;
             return Some(
                 ColumnIndex {
@@ -42,7 +49,14 @@ impl<'a> Iterator for ColumnIndices<'a> {
 pub struct Line {
     pub text: String,
     pub size: usize, // Number of graphemes
-    pub width: usize // Number of columns
+    width: usize, // Number of columns, only right without tabs
+    pub ending: Option<LineEnding> // Read with a different ending than the buffer's
+}
+
+impl Default for Line {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Line {
@@ -51,6 +65,7 @@ impl Line {
             text: String::new(),
             size: 0,
             width: 0,
+            ending: None
         }
     }
 
@@ -58,20 +73,47 @@ impl Line {
         Line {
             text: String::from(s),
             size: s.graphemes(true).count(),
-            width: s.width_cjk()
+            width: s.display_width(),
+            ending: None
         }
     }
 
-    pub fn column_indices(&self) -> ColumnIndices {
+    // Columns of the graphemes, with tab stops every `tab_width` columns
+    pub fn column_indices(&self, tab_width: usize) -> ColumnIndices<'_> {
         ColumnIndices {
             iter: self.text.grapheme_indices(true).enumerate(),
-            column: 0
+            column: 0,
+            tab_width: tab_width.max(1)
+        }
+    }
+
+    // Number of columns, which depends on the tab stops if there are tabs
+    pub fn width(&self, tab_width: usize) -> usize {
+        if self.text.contains('\t') {
+            self.column_indices(tab_width).map(|c| c.width).sum()
+        } else {
+            self.width
+        }
+    }
+
+    // Bytes `range` of the text as it's shown, with tabs turned into spaces up to the next
+    // tab stop and control characters spelled out
+    pub fn expand_tabs(&self, range: Range<usize>, tab_width: usize) -> Cow<'_, str> {
+        let text = &self.text[range.clone()];
+        if !text.contains('\t') {
+            return width::reveal_controls(text);
         }
+        Cow::Owned(self.column_indices(tab_width)
+            .skip_while(|c| c.byte < range.start)
+            .take_while(|c| c.byte < range.end)
+            .map(|c| if c.grapheme == "\t" { Cow::Owned(" ".repeat(c.width)) } else { width::reveal_controls(c.grapheme) })
+            .collect())
     }
 
     pub fn insert(&mut self, c: char, i: usize) {
-        let width = c.width_cjk().unwrap_or(0);
-        if width > 0 {
+        // Zero-width characters are dropped, except tabs which widen up to the next tab stop
+        let width = if c == '\t' { Some("\t".display_width()) } else { width::char_width(c).filter(|&w| w > 0) };
+        if let Some(width) = width {
             self.text.insert(i, c);
             self.width += width;
             self.size += 1;
@@ -80,7 +122,7 @@ impl Line {
 
     pub fn insert_str(&mut self, s: &str, i: usize) {
         self.text.insert_str(i, s);
-        self.width += s.width_cjk();
+        self.width += s.display_width();
         self.size += s.graphemes(true).count();
     }
 
@@ -88,7 +130,7 @@ impl Line {
         where R : RangeBounds<usize> 
     {
         let s: String = self.text.drain(i).collect();
-        self.width -= s.width_cjk();
+        self.width -= s.display_width();
         self.size -= s.graphemes(true).count();
         s
     }
@@ -100,25 +142,28 @@ impl Line {
         s
     }
 
+    // The joined line ends where `other` did
     pub fn concat(&mut self, other: &Self) {
         self.text.push_str(&other.text);
+        self.ending = other.ending.clone();
         self.width += other.width;
         self.size += other.size;
     }
 
-    pub fn concat_str(&mut self, s: &String) {
+    pub fn concat_str(&mut self, s: &str) {
         self.text.push_str(s);
-        self.width += s.width_cjk();
+        self.width += s.display_width();
         self.size += s.graphemes(true).count();
     }
 
     pub fn split(&mut self, i: usize) -> Self {
         let s = self.text.split_off(i);
-        let width = s.width_cjk();
+        let width = s.display_width();
         let size = s.graphemes(true).count();  
         self.width -= width;
         self.size -= size;
-        Line { text: s, width, size }
+        // The ending goes with the text after the split
+        Line { text: s, width, size, ending: self.ending.take() }
     }
 
     pub fn replace<R>(&mut self, c: char, i: R) -> String
@@ -131,7 +176,7 @@ impl Line {
         };
         let p = self.delete(i);
         self.insert(c, start);
-        return p;
+        p
     }
 
     pub fn replace_str<R>(&mut self, s: &str, i: R) -> String
@@ -144,6 +189,6 @@ impl Line {
         };
         let p = self.delete(i);
         self.insert_str(s, start);
-        return p;
+        p
     }
 }
\ No newline at end of file
@@ -0,0 +1,512 @@
+use super::line::Line;
+use std::mem;
+use std::ops::{Deref, DerefMut, Index, Range};
+use std::slice;
+
+// Lines are kept in a tree rather than one long vector, so inserting or removing a line
+// and finding the byte offset of one only touch a handful of nodes, even in huge files.
+// Leaves hold up to MAX_LEAF lines and branches up to MAX_BRANCH children.
+const MAX_LEAF: usize = 128;
+const MAX_BRANCH: usize = 16;
+
+#[derive(Clone)]
+enum Kind {
+    Leaf(Vec<Line>),
+    Branch(Vec<Node>)
+}
+
+#[derive(Clone)]
+struct Node {
+    lines: usize, // Lines under this node
+    bytes: usize, // Their text's length, without line endings
+    kind: Kind
+}
+
+impl Node {
+    fn leaf(lines: Vec<Line>) -> Self {
+        let mut node = Node { lines: 0, bytes: 0, kind: Kind::Leaf(lines) };
+        node.count();
+        node
+    }
+
+    fn branch(children: Vec<Node>) -> Self {
+        let mut node = Node { lines: 0, bytes: 0, kind: Kind::Branch(children) };
+        node.count();
+        node
+    }
+
+    // Recalculate the totals from the lines or children directly below
+    fn count(&mut self) {
+        (self.lines, self.bytes) = match &self.kind {
+            Kind::Leaf(lines) => (lines.len(), lines.iter().map(|l| l.text.len()).sum()),
+            Kind::Branch(children) => children.iter().fold((0, 0), |(l, b), c| (l + c.lines, b + c.bytes))
+        };
+    }
+
+    fn is_small(&self) -> bool {
+        match &self.kind {
+            Kind::Leaf(lines) => lines.len() < MAX_LEAF / 4,
+            Kind::Branch(children) => children.len() < MAX_BRANCH / 4
+        }
+    }
+
+    // The child holding line `index` and the index within it. An index one past the end
+    // goes to the last child, for appending
+    fn child(children: &[Node], mut index: usize) -> (usize, usize) {
+        for (i, child) in children.iter().enumerate() {
+            if index < child.lines || i == children.len() - 1 {
+                return (i, index);
+            }
+            index -= child.lines;
+        }
+        (0, index)
+    }
+
+    fn get(&self, index: usize) -> Option<&Line> {
+        match &self.kind {
+            Kind::Leaf(lines) => lines.get(index),
+            Kind::Branch(children) => {
+                let (i, index) = Node::child(children, index);
+                children.get(i)?.get(index)
+            }
+        }
+    }
+
+    // Callers must `refresh` the same index once done, in case the line's length changed
+    fn get_mut(&mut self, index: usize) -> Option<&mut Line> {
+        match &mut self.kind {
+            Kind::Leaf(lines) => lines.get_mut(index),
+            Kind::Branch(children) => {
+                let (i, index) = Node::child(children, index);
+                children.get_mut(i)?.get_mut(index)
+            }
+        }
+    }
+
+    fn refresh(&mut self, index: usize) {
+        if let Kind::Branch(children) = &mut self.kind {
+            let (i, index) = Node::child(children, index);
+            if let Some(child) = children.get_mut(i) {
+                child.refresh(index);
+            }
+        }
+        self.count();
+    }
+
+    // Bytes of text in the lines before `index`
+    fn offset(&self, index: usize) -> usize {
+        match &self.kind {
+            Kind::Leaf(lines) => lines.iter().take(index).map(|l| l.text.len()).sum(),
+            Kind::Branch(children) => {
+                let (i, index) = Node::child(children, index);
+                let before: usize = children[..i].iter().map(|c| c.bytes).sum();
+                before + children.get(i).map_or(0, |c| c.offset(index))
+            }
+        }
+    }
+
+    // Insert `line` before `index`, returns a new sibling to go after this node if it got too big
+    fn insert(&mut self, index: usize, line: Line) -> Option<Node> {
+        let sibling = match &mut self.kind {
+            Kind::Leaf(lines) => {
+                lines.insert(index, line);
+                (lines.len() > MAX_LEAF).then(|| Node::leaf(lines.split_off(lines.len() / 2)))
+            },
+            Kind::Branch(children) => {
+                let (i, index) = Node::child(children, index);
+                if let Some(sibling) = children[i].insert(index, line) {
+                    children.insert(i + 1, sibling);
+                }
+                (children.len() > MAX_BRANCH).then(|| Node::branch(children.split_off(children.len() / 2)))
+            }
+        };
+        self.count();
+        sibling
+    }
+
+    fn remove(&mut self, index: usize) -> Line {
+        let line = match &mut self.kind {
+            Kind::Leaf(lines) => lines.remove(index),
+            Kind::Branch(children) => {
+                let (i, index) = Node::child(children, index);
+                let line = children[i].remove(index);
+                if children[i].lines == 0 {
+                    children.remove(i);
+                } else if children[i].is_small() {
+                    Node::merge(children, i);
+                }
+                line
+            }
+        };
+        self.count();
+        line
+    }
+
+    // Remove the lines in `range`, dropping the children it covers whole and only going
+    // down into the ones at either end
+    fn remove_range(&mut self, range: Range<usize>) {
+        match &mut self.kind {
+            Kind::Leaf(lines) => { lines.drain(range); },
+            Kind::Branch(children) => {
+                let mut start = 0;
+                children.retain_mut(|child| {
+                    let end = start + child.lines;
+                    let (from, to) = (range.start.max(start), range.end.min(end));
+                    let whole = from == start && to == end;
+                    if from < to && !whole {
+                        child.remove_range(from - start..to - start);
+                    }
+                    start = end;
+                    !whole
+                });
+                while let Some(i) = children.iter().position(Node::is_small).filter(|_| children.len() > 1) {
+                    Node::merge(children, i);
+                }
+            }
+        }
+        self.count();
+    }
+
+    // Join the small child at `i` with a neighbour, splitting them evenly again if that's too big
+    fn merge(children: &mut Vec<Node>, i: usize) {
+        if children.len() < 2 {
+            return;
+        }
+        let i = if i + 1 < children.len() { i } else { i - 1 };
+        let right = children.remove(i + 1);
+        let left = &mut children[i];
+
+        let sibling = match (&mut left.kind, right.kind) {
+            (Kind::Leaf(lines), Kind::Leaf(mut more)) => {
+                lines.append(&mut more);
+                (lines.len() > MAX_LEAF).then(|| Node::leaf(lines.split_off(lines.len() / 2)))
+            },
+            (Kind::Branch(nodes), Kind::Branch(mut more)) => {
+                nodes.append(&mut more);
+                (nodes.len() > MAX_BRANCH).then(|| Node::branch(nodes.split_off(nodes.len() / 2)))
+            },
+            _ => unreachable!("nodes at the same depth are the same kind")
+        };
+        left.count();
+        if let Some(sibling) = sibling {
+            children.insert(i + 1, sibling);
+        }
+    }
+}
+
+// The lines of a buffer. There's at least one to start with, even when collected from
+// nothing, but removing them all leaves none: keeping one is up to `Buffer`, whose `drain`
+// puts an empty line back, and whose `insert` only takes one out to put it back split
+#[derive(Clone)]
+pub struct Lines {
+    root: Node
+}
+
+impl Lines {
+    pub fn new() -> Self {
+        Lines { root: Node::leaf(vec![Line::new()]) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.root.lines
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.lines == 0
+    }
+
+    // Length of all the text, without line endings
+    pub fn bytes(&self) -> usize {
+        self.root.bytes
+    }
+
+    // Bytes of text before line `index`, not counting line endings
+    pub fn offset(&self, index: usize) -> usize {
+        self.root.offset(index)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Line> {
+        self.root.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<LineMut<'_>> {
+        self.root.get(index)?;
+        Some(LineMut { lines: self, index })
+    }
+
+    pub fn first(&self) -> Option<&Line> {
+        self.get(0)
+    }
+
+    pub fn last(&self) -> Option<&Line> {
+        self.get(self.len().checked_sub(1)?)
+    }
+
+    pub fn last_mut(&mut self) -> Option<LineMut<'_>> {
+        self.get_mut(self.len().checked_sub(1)?)
+    }
+
+    pub fn insert(&mut self, index: usize, line: Line) {
+        assert!(index <= self.len(), "line {} inserted past the end", index);
+        if let Some(sibling) = self.root.insert(index, line) {
+            let root = mem::replace(&mut self.root, Node::leaf(Vec::new()));
+            self.root = Node::branch(vec![root, sibling]);
+        }
+    }
+
+    pub fn push(&mut self, line: Line) {
+        self.insert(self.len(), line);
+    }
+
+    pub fn remove(&mut self, index: usize) -> Line {
+        assert!(index < self.len(), "line {} removed past the end", index);
+        let line = self.root.remove(index);
+        self.shrink();
+        line
+    }
+
+    // Remove the lines in `range`
+    pub fn remove_range(&mut self, range: Range<usize>) {
+        assert!(range.end <= self.len(), "lines {:?} removed past the end", range);
+        if range.is_empty() {
+            return;
+        }
+        self.root.remove_range(range);
+        self.shrink();
+    }
+
+    // Drop levels left with a single child
+    fn shrink(&mut self) {
+        loop {
+            match &mut self.root.kind {
+                Kind::Branch(children) if children.len() == 1 => self.root = children.remove(0),
+                Kind::Branch(children) if children.is_empty() => self.root = Node::leaf(Vec::new()),
+                _ => break
+            }
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { stack: vec![slice::from_ref(&self.root).iter()], leaf: [].iter(), remaining: self.len() }
+    }
+}
+
+impl Default for Lines {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromIterator<Line> for Lines {
+    // Build the tree bottom up from full leaves
+    fn from_iter<I: IntoIterator<Item = Line>>(iter: I) -> Self {
+        let mut iter = iter.into_iter().peekable();
+        let mut nodes = Vec::new();
+        while iter.peek().is_some() {
+            nodes.push(Node::leaf(iter.by_ref().take(MAX_LEAF).collect()));
+        }
+
+        while nodes.len() > 1 {
+            let mut parents = Vec::new();
+            let mut children = nodes.into_iter().peekable();
+            while children.peek().is_some() {
+                parents.push(Node::branch(children.by_ref().take(MAX_BRANCH).collect()));
+            }
+            nodes = parents;
+        }
+
+        match nodes.pop() {
+            Some(root) => Lines { root },
+            None => Lines::new()
+        }
+    }
+}
+
+impl Index<usize> for Lines {
+    type Output = Line;
+
+    fn index(&self, index: usize) -> &Line {
+        self.get(index).expect("line index out of range")
+    }
+}
+
+impl<'a> IntoIterator for &'a Lines {
+    type Item = &'a Line;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+pub struct Iter<'a> {
+    stack: Vec<slice::Iter<'a, Node>>, // Children still to visit at each level
+    leaf: slice::Iter<'a, Line>,
+    remaining: usize
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Line;
+
+    fn next(&mut self) -> Option<&'a Line> {
+        loop {
+            if let Some(line) = self.leaf.next() {
+                self.remaining -= 1;
+                return Some(line);
+            }
+
+            let node = loop {
+                match self.stack.last_mut()?.next() {
+                    Some(node) => break node,
+                    None => { self.stack.pop(); }
+                }
+            };
+            match &node.kind {
+                Kind::Leaf(lines) => self.leaf = lines.iter(),
+                Kind::Branch(children) => self.stack.push(children.iter())
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+// A line borrowed for changing, the tree's byte counts are updated once it's dropped
+pub struct LineMut<'a> {
+    lines: &'a mut Lines,
+    index: usize
+}
+
+impl Deref for LineMut<'_> {
+    type Target = Line;
+
+    fn deref(&self) -> &Line {
+        &self.lines[self.index]
+    }
+}
+
+impl DerefMut for LineMut<'_> {
+    fn deref_mut(&mut self) -> &mut Line {
+        self.lines.root.get_mut(self.index).expect("line index out of range")
+    }
+}
+
+impl Drop for LineMut<'_> {
+    fn drop(&mut self) {
+        self.lines.root.refresh(self.index);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(n: usize) -> Line {
+        Line::from(&"x".repeat(n % 7))
+    }
+
+    // The tree holds the same lines as `model`, and every node's totals add up
+    fn check(lines: &Lines, model: &[Line]) {
+        fn counts(node: &Node) -> (usize, usize) {
+            let counted = match &node.kind {
+                Kind::Leaf(lines) => (lines.len(), lines.iter().map(|l| l.text.len()).sum()),
+                Kind::Branch(children) => children.iter().map(counts).fold((0, 0), |(l, b), (m, c)| (l + m, b + c))
+            };
+            assert_eq!(counted, (node.lines, node.bytes));
+            counted
+        }
+        counts(&lines.root);
+
+        assert_eq!(lines.len(), model.len());
+        assert_eq!(lines.iter().len(), model.len());
+        assert!(lines.iter().map(|l| &l.text).eq(model.iter().map(|l| &l.text)));
+        for i in (0..=model.len()).step_by(13).chain([model.len()]) {
+            let bytes: usize = model[..i].iter().map(|l| l.text.len()).sum();
+            assert_eq!(lines.offset(i), bytes);
+        }
+    }
+
+    // A small xorshift, so failures can be replayed
+    fn random(seed: &mut u64, n: usize) -> usize {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+        (*seed % n as u64) as usize
+    }
+
+    #[test]
+    fn insert_and_remove_split_and_merge() {
+        let mut lines = Lines::new();
+        let mut model = vec![Line::new()];
+
+        // Enough lines for branches to split, inserted all over
+        let mut seed = 1;
+        for n in 0..MAX_LEAF * MAX_BRANCH * 3 {
+            let i = random(&mut seed, model.len() + 1);
+            lines.insert(i, line(n));
+            model.insert(i, line(n));
+        }
+        check(&lines, &model);
+        assert!(matches!(&lines.root.kind, Kind::Branch(c) if matches!(c[0].kind, Kind::Branch(_))));
+
+        // And all but a few removed again, merging nodes as they get small
+        while model.len() > 3 {
+            let i = random(&mut seed, model.len());
+            assert_eq!(lines.remove(i).text, model.remove(i).text);
+            if model.len() % 500 == 0 {
+                check(&lines, &model);
+            }
+        }
+        check(&lines, &model);
+        assert!(matches!(lines.root.kind, Kind::Leaf(_)));
+    }
+
+    #[test]
+    fn remove_range() {
+        let mut seed = 7;
+        for _ in 0..20 {
+            let mut model: Vec<Line> = (0..random(&mut seed, MAX_LEAF * MAX_BRANCH * 4)).map(line).collect();
+            let mut lines: Lines = model.clone().into_iter().collect();
+            while !model.is_empty() {
+                let start = random(&mut seed, model.len());
+                let end = start + random(&mut seed, (model.len() - start).min(MAX_LEAF * 5)) + 1;
+                lines.remove_range(start..end);
+                model.drain(start..end);
+                check(&lines, &model);
+            }
+        }
+
+        let mut lines: Lines = (0..1000).map(line).collect();
+        lines.remove_range(10..10);
+        lines.remove_range(0..1000);
+        check(&lines, &[]);
+    }
+
+    #[test]
+    fn get_mut_refreshes_counts() {
+        let mut model: Vec<Line> = (0..MAX_LEAF * 40).map(line).collect();
+        let mut lines: Lines = model.clone().into_iter().collect();
+        for i in (0..model.len()).step_by(97) {
+            lines.get_mut(i).unwrap().insert_str("abc", 0);
+            model[i].insert_str("abc", 0);
+        }
+        lines.last_mut().unwrap().clear();
+        model.last_mut().unwrap().clear();
+        check(&lines, &model);
+        assert_eq!(lines.bytes(), model.iter().map(|l| l.text.len()).sum::<usize>());
+    }
+
+    #[test]
+    fn collect() {
+        let lines: Lines = std::iter::empty().collect();
+        check(&lines, &[Line::new()]);
+
+        let model: Vec<Line> = (0..MAX_LEAF * MAX_BRANCH + 1).map(line).collect();
+        check(&model.clone().into_iter().collect(), &model);
+    }
+}
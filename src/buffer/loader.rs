@@ -0,0 +1,97 @@
+use super::{Buffer, Encoding, LineEnding};
+use super::line::Line;
+use std::fs::File;
+use std::io::{self, Read};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+const CHUNKS_AHEAD: usize = 4; // Chunks read before the editor has taken them
+
+// Splits a file into chunks that end with complete lines
+pub struct Chunks {
+    file: File,
+    rest: Vec<u8>, // An unfinished last line, until the rest of it is read
+    pub done: bool
+}
+
+impl Chunks {
+    pub fn new(file: File) -> Self {
+        Chunks { file, rest: Vec::new(), done: false }
+    }
+
+    // The next `size` bytes or so, up to the end of the last complete line in them, and
+    // everything that's left once the file ends
+    pub fn read(&mut self, size: usize) -> io::Result<Vec<u8>> {
+        let mut bytes = std::mem::take(&mut self.rest);
+        let count = (&mut self.file).take(size as u64).read_to_end(&mut bytes)?;
+        if count < size {
+            self.done = true;
+            return Ok(bytes);
+        }
+
+        // A carriage return at the very end may be followed by a line feed
+        let last = bytes.len() - 1;
+        let end = bytes
+            .iter()
+            .enumerate()
+            .rposition(|(i, &b)| b == b'\n' || (b == b'\r' && i < last))
+            .map_or(0, |i| i + 1);
+        self.rest = bytes.split_off(end);
+        Ok(bytes)
+    }
+}
+
+// Reads the rest of a large file on another thread, into lines the buffer can take as
+// they're ready
+pub struct Loader {
+    rx: Receiver<io::Result<(usize, Vec<Line>)>>,
+    read: u64, // How much of the file has been read
+    len: u64,
+    pub done: bool
+}
+
+impl Loader {
+    // Carry on reading `chunks`, `read` bytes into a file of `len` bytes. Lines that
+    // don't end with `ending` keep their own
+    pub fn start(mut chunks: Chunks, size: usize, encoding: Encoding, ending: LineEnding, read: u64, len: u64) -> Self {
+        let (tx, rx) = mpsc::sync_channel(CHUNKS_AHEAD);
+        thread::spawn(move || {
+            while !chunks.done {
+                let lines = chunks.read(size).and_then(|bytes| {
+                    let count = bytes.len();
+                    let mut lines = Buffer::split(&encoding.decode(bytes)?);
+                    for line in lines.iter_mut().filter(|l| l.ending.as_ref() == Some(&ending)) {
+                        line.ending = None;
+                    }
+                    Ok((count, lines))
+                });
+                // Stop if the buffer's gone or there's nothing more that can be read
+                let failed = lines.is_err();
+                if tx.send(lines).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Loader { rx, read, len, done: false }
+    }
+
+    // Lines read since last time, `None` if there aren't any yet
+    pub fn receive(&mut self) -> io::Result<Option<Vec<Line>>> {
+        match self.rx.try_recv() {
+            Ok(result) => result.map(|(count, lines)| {
+                self.read += count as u64;
+                Some(lines)
+            }),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => {
+                self.done = true;
+                Ok(None)
+            }
+        }
+    }
+
+    // How much of the file has been read, from 0 to 100
+    pub fn percent(&self) -> u64 {
+        (self.read * 100).checked_div(self.len).unwrap_or(100).min(100)
+    }
+}
@@ -1,22 +1,60 @@
 pub mod line;
+pub mod lines;
+mod loader;
+pub mod modeline;
 
 use line::Line;
-use crate::Config;
-use unicode_segmentation::GraphemeCursor;
+use lines::Lines;
+use loader::{Chunks, Loader};
+use crate::{elevate, filetype, indent, transform, Config, FileOptions};
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+use std::cell::Cell;
 use std::fmt::Display;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 use std::io::{self, BufRead, BufReader, BufWriter, Write};
-use std::fs::OpenOptions;
+use std::fs::{File, OpenOptions};
+use std::os::unix::fs::{self, MetadataExt};
+use std::process;
+use std::env;
+use std::cmp::min;
 
-#[derive(Clone)]
-pub enum LineEnding { CRLF, LF }
+// How many lines at the start and end of a file are searched for modelines
+const MODELINE_LINES: usize = 5;
+const LAZY_SIZE: u64 = 16 << 20; // Files larger than this are read in the background
+const CHUNK_SIZE: usize = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LineEnding { CRLF, LF, CR }
 
 impl LineEnding {
-    fn value(&self) -> &'static str {
+    pub fn value(&self) -> &'static str {
         match *self {
             Self::CRLF => "\r\n",
-            Self::LF => "\n"
+            Self::LF => "\n",
+            Self::CR => "\r"
+        }
+    }
+
+    // From `lf`, `crlf` or `cr` in the config file
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "lf" => Some(LineEnding::LF),
+            "crlf" => Some(LineEnding::CRLF),
+            "cr" => Some(LineEnding::CR),
+            _ => None
+        }
+    }
+
+    // Where the first line ending in `text` starts, and which one it is
+    pub fn find(text: &str) -> Option<(usize, Self)> {
+        let i = text.find(['\r', '\n'])?;
+        if text[i..].starts_with("\r\n") {
+            Some((i, LineEnding::CRLF))
+        } else if text[i..].starts_with('\r') {
+            Some((i, LineEnding::CR))
+        } else {
+            Some((i, LineEnding::LF))
         }
     }
 
@@ -35,17 +73,229 @@ impl Display for LineEnding {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(match *self {
             Self::CRLF => "CRLF",
-            Self::LF => "LF"
+            Self::LF => "LF",
+            Self::CR => "CR"
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Encoding { UTF8, UTF8BOM, Latin1, UTF16LE, UTF16BE, Binary }
+
+const UTF8_BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+const BINARY_SAMPLE: usize = 8000; // Bytes checked for binary content, like git
+
+impl Encoding {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Encoding::UTF8),
+            "utf-8-bom" | "utf8bom" => Some(Encoding::UTF8BOM),
+            "latin-1" | "latin1" | "iso-8859-1" | "iso8859-1" => Some(Encoding::Latin1),
+            "utf-16le" | "utf16le" | "utf-16" | "utf16" => Some(Encoding::UTF16LE),
+            "utf-16be" | "utf16be" => Some(Encoding::UTF16BE),
+            "binary" | "hex" => Some(Encoding::Binary),
+            _ => None
+        }
+    }
+
+    // By the byte order mark, then binary if there are NULs (or control characters text
+    // wouldn't have, in what isn't UTF-8), then UTF-8 if it's valid, and Latin-1 (which
+    // any bytes are) otherwise
+    pub fn detect(bytes: &[u8]) -> Self {
+        let utf8 = std::str::from_utf8(bytes).is_ok();
+        let sample = &bytes[..bytes.len().min(BINARY_SAMPLE)];
+        let binary = sample.iter().any(|&b| {
+            b == 0 || (!utf8 && b < 0x20 && !b"\t\n\r\x0c\x1b".contains(&b))
+        });
+
+        if bytes.starts_with(UTF8_BOM) && utf8 {
+            Encoding::UTF8BOM
+        } else if bytes.starts_with(&[0xff, 0xfe]) {
+            Encoding::UTF16LE
+        } else if bytes.starts_with(&[0xfe, 0xff]) {
+            Encoding::UTF16BE
+        } else if binary {
+            Encoding::Binary
+        } else if utf8 {
+            Encoding::UTF8
+        } else {
+            Encoding::Latin1
+        }
+    }
+
+    // The text in `bytes`, without a byte order mark, or a hex dump of binary
+    pub fn decode(&self, mut bytes: Vec<u8>) -> io::Result<String> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("File isn't valid {}", self));
+        match self {
+            Encoding::UTF8 | Encoding::UTF8BOM => {
+                if bytes.starts_with(UTF8_BOM) {
+                    bytes.drain(..UTF8_BOM.len());
+                }
+                String::from_utf8(bytes).map_err(|_| invalid())
+            },
+            Encoding::Latin1 => Ok(bytes.iter().map(|&b| b as char).collect()),
+            Encoding::Binary => Ok(transform::hex_view(&bytes)),
+            Encoding::UTF16LE | Encoding::UTF16BE => {
+                if !bytes.len().is_multiple_of(2) {
+                    return Err(invalid());
+                }
+                let units = bytes.chunks(2).map(|pair| match self {
+                    Encoding::UTF16LE => u16::from_le_bytes([pair[0], pair[1]]),
+                    _ => u16::from_be_bytes([pair[0], pair[1]])
+                });
+                let text = char::decode_utf16(units)
+                    .collect::<Result<String, _>>()
+                    .map_err(|_| invalid())?;
+                Ok(text.strip_prefix('\u{feff}').map(String::from).unwrap_or(text))
+            }
+        }
+    }
+
+    // `text` as it's saved, UTF-16 starting with a byte order mark. Fails rather than
+    // lose characters Latin-1 doesn't have
+    pub fn encode(&self, text: &str) -> io::Result<Vec<u8>> {
+        match self {
+            Encoding::UTF8 => Ok(text.as_bytes().to_vec()),
+            Encoding::UTF8BOM => Ok([UTF8_BOM, text.as_bytes()].concat()),
+            Encoding::Latin1 => text
+                .chars()
+                .map(|c| u8::try_from(c).map_err(|_| io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Can't save '{}' as Latin-1", c)
+                )))
+                .collect(),
+            Encoding::Binary => Err(io::Error::new(io::ErrorKind::InvalidData, "Binary files can't be saved")),
+            Encoding::UTF16LE | Encoding::UTF16BE => Ok(std::iter::once(0xfeff)
+                .chain(text.encode_utf16())
+                .flat_map(|u| if *self == Encoding::UTF16LE { u.to_le_bytes() } else { u.to_be_bytes() })
+                .collect())
+        }
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match *self {
+            Self::UTF8 => "UTF-8",
+            Self::UTF8BOM => "UTF-8 BOM",
+            Self::Latin1 => "Latin-1",
+            Self::UTF16LE => "UTF-16LE",
+            Self::UTF16BE => "UTF-16BE",
+            Self::Binary => "binary"
         })
     }
 }
 
+// Per-buffer editing settings
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub tab_width: usize,
+    pub expand_tabs: bool, // Insert spaces instead of tab characters
+    pub auto_indent: bool, // New lines start with the indentation of the one before
+    pub atomic_save: bool, // Write a temporary file and rename it over the original
+    pub backup: bool, // Copy the file to `name~` before saving over it
+    pub backup_dir: Option<PathBuf>, // Keep backups here instead of beside the file
+    pub strip_trailing: bool, // Remove spaces and tabs at the ends of lines when saving
+    pub final_newline: bool, // End saved files with a line ending
+    pub preserve_endings: bool, // Write lines back with the endings they were read with
+    pub fill_column: Option<usize>,
+    pub max_column: Option<usize>, // Text past this column is highlighted
+    pub filetype: Option<String>,
+    pub comment: Option<String> // Line comment prefix
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            tab_width: 4,
+            expand_tabs: true,
+            auto_indent: true,
+            atomic_save: true,
+            backup: false,
+            backup_dir: None,
+            strip_trailing: false,
+            final_newline: false,
+            preserve_endings: false,
+            fill_column: None,
+            max_column: None,
+            filetype: None,
+            comment: None
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Point {
     pub x: usize,
     pub y: usize,
 }
 
+impl PartialEq for Point {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_tuple() == other.as_tuple()
+    }
+}
+
+impl Eq for Point {}
+
+// Points are ordered by line, then by byte
+impl Ord for Point {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.y, self.x).cmp(&(other.y, other.x))
+    }
+}
+
+impl PartialOrd for Point {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// What an edit did: the text from `start` to `removed` was replaced by text ending at `inserted`
+#[derive(Debug, Clone, Copy)]
+pub struct Change {
+    pub start: Point,
+    pub removed: Point,
+    pub inserted: Point
+}
+
+impl Change {
+    pub fn new(start: Point, removed: Point, inserted: Point) -> Self {
+        Change { start, removed, inserted }
+    }
+
+    // Where `point` is after the change, points in removed text move to its start
+    pub fn map(&self, point: Point) -> Point {
+        if point < self.start {
+            point
+        } else if point < self.removed {
+            self.start
+        } else if point.y == self.removed.y {
+            Point { x: self.inserted.x + point.x - self.removed.x, y: self.inserted.y }
+        } else {
+            Point { x: point.x, y: point.y - self.removed.y + self.inserted.y }
+        }
+    }
+
+    // Where the boundary above `row` is after the change. Rows up to the change's first
+    // stay, those it replaced go to its first and the ones after move with the text
+    pub fn map_row(&self, row: usize) -> usize {
+        if row <= self.start.y {
+            row
+        } else if row <= self.removed.y {
+            self.start.y
+        } else {
+            row - self.removed.y + self.inserted.y
+        }
+    }
+}
+
+impl Default for Point {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Point {
     pub fn new() -> Point { 
         Point { x: 0, y: 0 }
@@ -66,37 +316,59 @@ pub enum Edit {
     Replace(Point, Point, String)
 }
 
-#[derive(Clone)]
 pub struct Buffer {
     path: PathBuf,
-    lines: Vec<Line>,
+    lines: Lines,
     modified: SystemTime,
     ending: LineEnding,
+    encoding: Encoding,
+    size: Option<u64>, // Size on disk, if the file exists
     dirty: bool,
-    readonly: bool // Does the user want to be able to write to the file?
+    revision: usize, // Incremented whenever the text changes
+    words: Cell<Option<(usize, usize)>>, // Word count, and the revision it was counted at
+    readonly: bool, // Does the user want to be able to write to the file?
+    loader: Option<Loader>, // The rest of a large file, while it's still being read
+    pub settings: Settings
 }
 
 impl Buffer {
-    pub fn new(path: &str, config: &Config) -> Self {
-        Buffer {
+    pub fn new(path: &str, options: &FileOptions, config: &Config) -> Self {
+        let mut buffer = Buffer {
             path: PathBuf::from(path),
-            lines: vec![Line::new()],
+            lines: Lines::new(),
             ending: LineEnding::default(),
+            encoding: Encoding::UTF8,
             modified: SystemTime::now(),
+            size: None,
             dirty: false,
-            readonly: config.readonly
-        }
+            revision: 0,
+            words: Cell::new(None),
+            readonly: options.readonly,
+            loader: None,
+            settings: Settings::default()
+        };
+
+        buffer.configure(options, config);
+        buffer
+    }
+
+    // A read-only buffer not backed by a file, for things like command output
+    pub fn scratch(text: &str, config: &Config) -> Self {
+        let mut buffer = Buffer::new("", &config.options, config);
+        buffer.lines = text.split('\n').map(Line::from).collect();
+        buffer.readonly = true;
+        buffer
     }
 
-    pub fn build(path: &str, config: &Config) -> io::Result<Self> {
+    pub fn build(path: &str, options: &FileOptions, config: &Config) -> io::Result<Self> {
         let file = OpenOptions::new()
             .read(true)
-            .truncate(config.truncate)
+            .truncate(options.truncate)
             .open(path);
 
         if let Err(e) = file {
             return match e.kind() {
-                io::ErrorKind::NotFound => Ok(Buffer::new(path, config)),
+                io::ErrorKind::NotFound => Ok(Buffer::new(path, options, config)),
                 _ => Err(e)
             };
         }
@@ -104,42 +376,314 @@ impl Buffer {
         let file = file.unwrap();
         let metadata = file.metadata()?;
         let modified = metadata.modified()?;
-        let mut reader = BufReader::new(file);
-        let mut buffer = String::new();
-        let mut lines = Vec::new();
+        let size = metadata.len();
+        let (lines, ending, encoding, loader) = if size > LAZY_SIZE {
+            Buffer::read_start(file, size, options.encoding.as_ref())?
+        } else {
+            let (lines, ending, encoding) = Buffer::read(BufReader::new(file), options.encoding.as_ref())?;
+            (lines, ending, encoding, None)
+        };
+
+        let mut buffer = Buffer {
+            path: PathBuf::from(path),
+            lines,
+            ending,
+            encoding,
+            modified,
+            size: Some(size),
+            dirty: false,
+            revision: 0,
+            words: Cell::new(None),
+            readonly: options.readonly,
+            loader,
+            settings: Settings::default()
+        };
 
-        while BufRead::read_line(&mut reader, &mut buffer)? != 0 {
-            lines.push(buffer.clone());
-            buffer.clear();
+        buffer.configure(options, config);
+        Ok(buffer)
+    }
+
+    // A new buffer with the text piped to stdin, which has yet to be saved anywhere
+    pub fn stdin(options: &FileOptions, config: &Config) -> io::Result<Self> {
+        let stdin = io::stdin();
+        if termion::is_tty(&stdin) {
+            return Err(io::Error::other("Nothing was piped to stdin"));
         }
 
-        let ending = match lines.first() {
-            Some(l) => if l.ends_with("\r\n") { LineEnding::CRLF } else { LineEnding::LF },
+        let (lines, ending, encoding) = Buffer::read(stdin.lock(), options.encoding.as_ref())?;
+        let mut buffer = Buffer {
+            path: PathBuf::new(),
+            lines,
+            ending,
+            encoding,
+            modified: SystemTime::now(),
+            size: None,
+            dirty: true,
+            revision: 0,
+            words: Cell::new(None),
+            readonly: options.readonly,
+            loader: None,
+            settings: Settings::default()
+        };
+
+        buffer.configure(options, config);
+        Ok(buffer)
+    }
+
+    // Lines of text from `reader` in `encoding` (or the one it seems to be in), the ending
+    // most of them have (the first line's if there's a tie) and the encoding. Lines with
+    // another ending keep it, in case it's written back
+    fn read<R>(mut reader: R, encoding: Option<&Encoding>) -> io::Result<(Lines, LineEnding, Encoding)>
+        where R : BufRead
+    {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let encoding = encoding.cloned().unwrap_or_else(|| Encoding::detect(&bytes));
+        let mut lines = Buffer::split(&encoding.decode(bytes)?);
+
+        let first = match lines.first() {
+            Some(l) => l.ending.clone().unwrap_or(LineEnding::LF),
             None => {
-                lines.push(String::new()); // Initialize empty buffer
+                lines.push(Line::new()); // Initialize empty buffer
                 LineEnding::default() // Empty or new file
             }
         };
+        let count = |e: &LineEnding| lines.iter().filter(|l| l.ending.as_ref() == Some(e)).count();
+        let ending = [LineEnding::CRLF, LineEnding::LF, LineEnding::CR]
+            .into_iter()
+            .filter(|e| count(e) > count(&first))
+            .max_by_key(count)
+            .unwrap_or(first);
 
-        // Remove line endings:
-        let lines: Vec<Line> = lines
-            .iter()
-            .map(|s| s.trim_end_matches(ending.value()))
-            .map(Line::from)
-            .collect();
+        // Only the lines that differ remember their ending
+        for line in lines.iter_mut() {
+            if line.ending.as_ref() == Some(&ending) {
+                line.ending = None;
+            }
+        }
+        Ok((lines.into_iter().collect(), ending, encoding))
+    }
 
-        Ok(Buffer {
-            path: PathBuf::from(path),
-            lines,
-            ending,
-            modified,
-            dirty: false,
-            readonly: config.readonly 
-        })
+    // Start reading a large file, only as far as the first chunk if the rest can be read
+    // in the background
+    fn read_start(file: File, len: u64, encoding: Option<&Encoding>) -> io::Result<(Lines, LineEnding, Encoding, Option<Loader>)> {
+        let mut chunks = Chunks::new(file);
+        let mut bytes = Vec::new();
+        while bytes.is_empty() && !chunks.done {
+            bytes = chunks.read(CHUNK_SIZE)?;
+        }
+
+        // Encodings whose lines can be found in their bytes, and not a hex dump, whose
+        // offsets count from the start of the file
+        let detected = encoding.cloned().unwrap_or_else(|| Encoding::detect(&bytes));
+        if !matches!(detected, Encoding::UTF8 | Encoding::UTF8BOM | Encoding::Latin1) {
+            bytes.extend(chunks.read(usize::MAX)?);
+            let (lines, ending, encoding) = Buffer::read(&bytes[..], Some(&detected))?;
+            return Ok((lines, ending, encoding, None));
+        }
+
+        let read = bytes.len() as u64;
+        let (lines, ending, encoding) = Buffer::read(&bytes[..], Some(&detected))?;
+        if chunks.done {
+            return Ok((lines, ending, encoding, None));
+        }
+        // Only the first chunk starts with a byte order mark
+        let rest = if encoding == Encoding::UTF8BOM { Encoding::UTF8 } else { encoding.clone() };
+        let loader = Loader::start(chunks, CHUNK_SIZE, rest, ending.clone(), read, len);
+        Ok((lines, ending, encoding, Some(loader)))
+    }
+
+    // Take the lines of a large file read in the background since last time, returns
+    // whether there were any or it's all been read. What couldn't be read is left out,
+    // and the buffer stays read-only
+    pub fn load_more(&mut self) -> io::Result<bool> {
+        let loader = match self.loader.as_mut() {
+            Some(l) => l,
+            None => return Ok(false)
+        };
+
+        let mut loaded = false;
+        let result = loop {
+            match loader.receive() {
+                Ok(Some(lines)) => {
+                    for line in lines {
+                        self.lines.push(line);
+                    }
+                    loaded = true;
+                },
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e)
+            }
+        };
+
+        let finished = loader.done || result.is_err();
+        if finished {
+            self.loader = None;
+        }
+        if result.is_err() {
+            self.readonly = true;
+        }
+        if loaded {
+            self.revision += 1;
+        }
+        result.map(|_| loaded || finished)
+    }
+
+    // How much of a large file has been read, while it's still being read
+    pub fn loading_percent(&self) -> Option<u64> {
+        self.loader.as_ref().map(|l| l.percent())
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.loader.is_some()
+    }
+
+    // Lines of `text`, each with the ending it has
+    fn split(text: &str) -> Vec<Line> {
+        let mut lines = Vec::new();
+        let mut rest = text;
+        while !rest.is_empty() {
+            let (len, ending) = match LineEnding::find(rest) {
+                Some((i, e)) => (i, Some(e)),
+                None => (rest.len(), None)
+            };
+            let mut line = Line::from(&rest[..len]);
+            rest = &rest[len + ending.as_ref().map_or(0, |e| e.value().len())..];
+            line.ending = ending;
+            lines.push(line);
+        }
+        lines
+    }
+
+    // Detect the filetype and apply its settings from the config file, then any modelines,
+    // then the options the file was opened with
+    fn configure(&mut self, options: &FileOptions, config: &Config) {
+        let first = self.lines.first().map(|l| l.text.as_str());
+        self.settings.filetype = filetype::detect(&self.path, first).map(|f| String::from(f.name));
+        self.settings.max_column = config.file
+            .integer("display", "max_column")
+            .map(|n| n as usize)
+            .filter(|&n| n > 0);
+
+        // A modeline may name a different filetype, so read them before the config file
+        if options.modelines {
+            self.apply_modelines();
+        }
+
+        // Defaults for every file, which filetypes can override
+        let file = &config.file;
+        if let Some(n) = file.integer("editor", "tab_width").filter(|&n| n > 0) {
+            self.settings.tab_width = n as usize;
+        }
+        if let Some(b) = file.boolean("editor", "hard_tabs") {
+            self.settings.expand_tabs = !b;
+        }
+        if let Some(b) = file.boolean("editor", "auto_indent") {
+            self.settings.auto_indent = b;
+        }
+        if let Some(b) = file.boolean("editor", "atomic_save") {
+            self.settings.atomic_save = b;
+        }
+        if let Some(b) = file.boolean("editor", "backup") {
+            self.settings.backup = b;
+        }
+        if let Some(dir) = file.string("editor", "backup_dir").filter(|d| !d.is_empty()) {
+            self.settings.backup_dir = Some(match (dir.strip_prefix("~/"), env::var_os("HOME")) {
+                (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+                _ => PathBuf::from(dir)
+            });
+        }
+        if let Some(b) = file.boolean("editor", "strip_trailing_whitespace") {
+            self.settings.strip_trailing = b;
+        }
+        if let Some(b) = file.boolean("editor", "final_newline") {
+            self.settings.final_newline = b;
+        }
+        if let Some(b) = file.boolean("editor", "preserve_line_endings") {
+            self.settings.preserve_endings = b;
+        }
+        if let Some(n) = file.integer("editor", "fill_column") {
+            self.settings.fill_column = Some(n as usize).filter(|&n| n > 0);
+        }
+        // There's no ending to detect in new and empty files
+        if self.byte_count() == 0 {
+            if let Some(ending) = file.string("editor", "line_ending").and_then(LineEnding::parse) {
+                self.ending = ending;
+            }
+        }
+
+        if let Some(name) = self.settings.filetype.clone() {
+            self.settings.comment = filetype::find(&name)
+                .and_then(|f| f.comment)
+                .map(String::from);
+
+            let table = format!("filetype.{}", name);
+            if let Some(n) = file.integer(&table, "tab_width").filter(|&n| n > 0) {
+                self.settings.tab_width = n as usize;
+            }
+            if let Some(b) = file.boolean(&table, "hard_tabs") {
+                self.settings.expand_tabs = !b;
+            }
+            if let Some(b) = file.boolean(&table, "auto_indent") {
+                self.settings.auto_indent = b;
+            }
+            if let Some(b) = file.boolean(&table, "strip_trailing_whitespace") {
+                self.settings.strip_trailing = b;
+            }
+            if let Some(b) = file.boolean(&table, "final_newline") {
+                self.settings.final_newline = b;
+            }
+            if let Some(s) = file.string(&table, "comment") {
+                self.settings.comment = Some(String::from(s)).filter(|s| !s.is_empty());
+            }
+            if let Some(n) = file.integer(&table, "fill_column") {
+                self.settings.fill_column = Some(n as usize).filter(|&n| n > 0);
+            }
+            if let Some(n) = file.integer(&table, "max_column") {
+                self.settings.max_column = Some(n as usize).filter(|&n| n > 0);
+            }
+        }
+
+        // Modelines take precedence over the config file
+        if options.modelines {
+            self.apply_modelines();
+        }
+
+        // And the command line over everything
+        if let Some(n) = options.tab_width {
+            self.settings.tab_width = n;
+        }
+        // Saved without the byte order mark it was read with, unless it's kept
+        if self.encoding == Encoding::UTF8BOM && !file.boolean("editor", "keep_bom").unwrap_or(true) {
+            self.encoding = Encoding::UTF8;
+        }
+        if let Some(encoding) = &options.encoding {
+            self.encoding = encoding.clone();
+        }
+        // Binary files are only viewed, as a hex dump
+        if self.encoding == Encoding::Binary {
+            self.readonly = true;
+        }
+        if let Some(ending) = options.ending.as_ref().filter(|&e| *e != self.ending) {
+            self.ending = ending.clone();
+            // An existing file has to be saved to be converted
+            self.dirty = self.size.is_some();
+        }
+    }
+
+    // Apply settings from any modelines in the first or last few lines
+    fn apply_modelines(&mut self) {
+        let count = self.lines.len();
+        let head = 0..min(MODELINE_LINES, count);
+        let tail = count.saturating_sub(MODELINE_LINES).max(head.end)..count;
+
+        for i in head.chain(tail) {
+            modeline::apply(&self.lines[i].text, &mut self.settings);
+        }
     }
 
     fn write_to(&self, path: &Path, overwrite: bool) -> io::Result<usize> {
-        if self.readonly {
+        if self.is_readonly() {
             return Err(io::Error::new(
                 io::ErrorKind::ReadOnlyFilesystem,
                 "Buffer is readonly"
@@ -151,37 +695,101 @@ impl Buffer {
                 .modified()
                 .unwrap_or(SystemTime::now());
             if modified > self.modified && !overwrite {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
+                return Err(io::Error::other(
                     "File was modified"
                 ));
             }
         }
 
+        if self.settings.backup && path.try_exists()? {
+            self.backup(path)?;
+        }
+
+        let data = self.contents()?;
+        if self.settings.atomic_save {
+            match write_atomic(path, &data) {
+                // Without a temporary file next to it, the file can still be written in place
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied => (),
+                result => return result.map(|_| data.len())
+            }
+        }
+
         let file = OpenOptions::new()
             .write(true)
             .create(true)
-            .open(&path)?;
+            .truncate(false)
+            .open(path)?;
 
         let mut writer = BufWriter::new(&file);
-        let data = self.to_string();
         let len = data.len();
 
-        writer.write_all(data.as_bytes())
+        writer.write_all(&data)
             .and_then(|_| file.set_len(len as u64))?;
 
         Ok(len)
     }
 
+    // Copy the file at `path` as it is before it's saved over
+    fn backup(&self, path: &Path) -> io::Result<()> {
+        let backup = match &self.settings.backup_dir {
+            // The whole path names the backup, so files with the same name don't share one
+            Some(dir) => {
+                let full = path.canonicalize()?;
+                dir.join(format!("{}~", full.to_string_lossy().replace('/', "%")))
+            },
+            None => {
+                let mut name = path.as_os_str().to_owned();
+                name.push("~");
+                PathBuf::from(name)
+            }
+        };
+
+        std::fs::copy(path, &backup).map(|_| ()).map_err(|e| io::Error::new(
+            // Not `Other` or `PermissionDenied`, which saving takes to mean something else
+            io::ErrorKind::InvalidInput,
+            format!("Couldn't back up to {}: {}", backup.display(), e)
+        ))
+    }
+
+    // The text as it's saved in the buffer's encoding, ending with a line ending if the
+    // settings ask for one (unless it's empty or already does)
+    fn contents(&self) -> io::Result<Vec<u8>> {
+        let mut data = self.to_string();
+        if self.settings.final_newline && self.lines.last().is_some_and(|l| !l.text.is_empty()) {
+            data.push_str(self.ending.value());
+        }
+        self.encoding.encode(&data)
+    }
+
     pub fn save(&mut self, overwrite: bool) -> io::Result<usize> {
         self
             .write_to(&self.path, overwrite)
-            .inspect(|_| {
+            .inspect(|&len| {
                 self.dirty = false;
                 self.modified = SystemTime::now();
+                self.size = Some(len as u64);
             })
     }
 
+    // Write to `path` through `program` (like sudo) when it isn't writable by the user
+    pub fn save_elevated(&mut self, path: &Path, program: &str, password: Option<&str>) -> io::Result<usize> {
+        if self.is_readonly() {
+            return Err(io::Error::new(
+                io::ErrorKind::ReadOnlyFilesystem,
+                "Buffer is readonly"
+            ));
+        }
+
+        let data = self.contents()?;
+        elevate::write(program, path, &data, password)?;
+
+        self.dirty = false;
+        self.modified = SystemTime::now();
+        self.size = Some(data.len() as u64);
+        self.path = PathBuf::from(path);
+        Ok(data.len())
+    }
+
     pub fn save_as(&mut self, path: &Path, overwrite: bool) -> io::Result<usize> {
         if path.try_exists()? && !overwrite {
             return Err(io::Error::new(
@@ -191,23 +799,61 @@ impl Buffer {
         }
 
         self
-            .write_to(&path, overwrite)
-            .inspect(|_| {
+            .write_to(path, overwrite)
+            .inspect(|&len| {
                 self.dirty = false;
                 self.modified = SystemTime::now();
+                self.size = Some(len as u64);
                 self.path = PathBuf::from(path);
             })
     }
 
+    // When the file was last changed, if another program has changed it since it was
+    // read or saved
+    pub fn changed_on_disk(&self) -> Option<SystemTime> {
+        if self.path.as_os_str().is_empty() {
+            return None;
+        }
+        let modified = self.path.metadata().and_then(|m| m.modified()).ok()?;
+        Some(modified).filter(|&m| m > self.modified)
+    }
+
+    // Keep the text as it is after the file changed at `modified`, saving it over the change
+    pub fn keep_changes(&mut self, modified: SystemTime) {
+        self.modified = modified;
+    }
+
+    // Read the file again, dropping any changes to the text
+    pub fn reload(&mut self) -> io::Result<()> {
+        let file = OpenOptions::new().read(true).open(&self.path)?;
+        let metadata = file.metadata()?;
+        // In the encoding it's saved with, which may not be the one it seems to be in
+        let (lines, ending, _) = Buffer::read(BufReader::new(file), Some(&self.encoding))?;
+
+        self.lines = lines;
+        self.ending = ending;
+        self.loader = None;
+        self.modified = metadata.modified()?;
+        self.size = Some(metadata.len());
+        self.dirty = false;
+        self.revision += 1;
+        Ok(())
+    }
+
     pub fn is_dirty(&self) -> bool {
         self.dirty
     }
 
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+
+    // Also while a large file is still being read, since saving would cut it short
     pub fn is_readonly(&self) -> bool {
-        self.readonly
+        self.readonly || self.loader.is_some()
     }
 
-    pub fn lines(&self) -> &Vec<Line> {
+    pub fn lines(&self) -> &Lines {
         &self.lines
     }
 
@@ -219,21 +865,153 @@ impl Buffer {
         self.lines.len()
     }
 
+    // Bytes of text before line `row`, not counting line endings
+    pub fn offset(&self, row: usize) -> usize {
+        self.lines.offset(row)
+    }
+
     pub fn line_ending(&self) -> &LineEnding {
         &self.ending
     }
 
+    // Lines whose ending isn't the buffer's
+    pub fn mixed_endings(&self) -> usize {
+        self.lines.iter().filter(|l| l.ending.is_some()).count()
+    }
+
+    // Choosing an ending converts every line to it
+    pub fn cycle_line_ending(&mut self) {
+        self.ending = match self.ending {
+            LineEnding::LF => LineEnding::CRLF,
+            LineEnding::CRLF => LineEnding::CR,
+            LineEnding::CR => LineEnding::LF
+        };
+        for row in 0..self.lines.len() {
+            if self.lines.get(row).is_some_and(|l| l.ending.is_some()) {
+                if let Some(mut line) = self.lines.get_mut(row) {
+                    line.ending = None;
+                }
+            }
+        }
+        self.dirty = true;
+    }
+
+    pub fn encoding(&self) -> &Encoding {
+        &self.encoding
+    }
+
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    // Length of the buffer's contents once written, in bytes
+    pub fn byte_count(&self) -> usize {
+        self.lines.bytes() + (self.lines.len() - 1) * self.ending.value().len()
+    }
+
+    // Counted again only once the text has changed, since it's shown on every frame
+    pub fn word_count(&self) -> usize {
+        match self.words.get() {
+            Some((words, revision)) if revision == self.revision => words,
+            _ => {
+                let words = self.lines.iter().map(|l| l.text.unicode_words().count()).sum();
+                self.words.set(Some((words, self.revision)));
+                words
+            }
+        }
+    }
+
     pub fn path(&self) -> &Path {
         &self.path
     }
 
+    // Add a line at the end without marking the buffer as modified
+    pub fn append_line(&mut self, s: &str) {
+        self.lines.push(Line::from(s));
+        self.revision += 1;
+    }
+
+    // Add `text` at the end without marking the buffer as modified, continuing the last
+    // line unless `new_line` is set. A final line break doesn't start an empty line.
+    pub fn append(&mut self, text: &str, new_line: bool) {
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        for (i, piece) in text.split('\n').enumerate() {
+            let piece = piece.strip_suffix('\r').unwrap_or(piece);
+            if i == 0 && !new_line {
+                let mut last = self.lines.last_mut().expect("buffer has no lines");
+                let len = last.text.len();
+                last.insert_str(piece, len);
+            } else {
+                self.lines.push(Line::from(piece));
+            }
+        }
+        self.revision += 1;
+    }
+
+    // Remove all text without marking the buffer as modified
+    pub fn clear(&mut self) {
+        self.lines = Lines::new();
+        self.revision += 1;
+    }
+
+    pub fn set_readonly(&mut self, readonly: bool) {
+        self.readonly = readonly;
+    }
+
+    // Flag unsaved changes, like when a save only partly succeeded
+    pub fn set_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    // No unsaved changes after all, like when the only ones were taken back
+    pub fn set_clean(&mut self) {
+        self.dirty = false;
+    }
+
+    // The text `edit` would change, from where it starts to where it stops removing.
+    // Deleting the end of a line takes its ending, up to the start of the next one
+    pub fn span(&self, edit: &Edit) -> (Point, Point) {
+        match edit {
+            Edit::Insert(_, pt) | Edit::Paste(pt, _) => (*pt, *pt),
+            Edit::Overwrite(_, pt) | Edit::Delete(pt) => {
+                let text = self.lines.get(pt.y).map_or("", |l| l.text.as_str());
+                let mut cursor = GraphemeCursor::new(pt.x, text.len(), true);
+                match cursor.next_boundary(text, 0) {
+                    Ok(Some(next)) => (*pt, Point { x: next, y: pt.y }),
+                    _ if matches!(edit, Edit::Delete(_)) && pt.y + 1 < self.line_count() => {
+                        (*pt, Point { x: 0, y: pt.y + 1 })
+                    },
+                    _ => (*pt, *pt)
+                }
+            },
+            Edit::Cut(l, r) | Edit::Replace(l, r, _) => (*l, *r)
+        }
+    }
+
+    // Copy the text between `from` and `to`, joining lines with the line ending
+    pub fn slice(&self, from: &Point, to: &Point) -> String {
+        let mut buffer = String::new();
+
+        for (y, line) in self.lines.iter().enumerate().take(to.y + 1).skip(from.y) {
+            let start = if y == from.y { from.x } else { 0 };
+            let end = if y == to.y { to.x } else { line.text.len() };
+            buffer.push_str(&line.text[start..end]);
+
+            if y < to.y {
+                buffer.push_str(self.ending.value());
+            }
+        }
+
+        buffer
+    }
+
     fn drain(&mut self, from: &Point, to: &Point) -> String {
         let mut buffer = String::new();
-        let mut head = from.clone();
+        let mut head = *from;
 
         // Cut parts of lines between `from` and `to`
         while head.y <= to.y {
-            if let Some(line) = self.lines.get_mut(head.y) {
+            if let Some(mut line) = self.lines.get_mut(head.y) {
                 let limit = if head.y != to.y { line.text.len() } else { to.x };
                 let take = limit - head.x;
                 let cut = if take >= line.text.len() {
@@ -244,7 +1022,7 @@ impl Buffer {
                 buffer.push_str(&cut);
 
                 if head.y < to.y { 
-                    buffer.push_str(&self.ending.value());
+                    buffer.push_str(self.ending.value());
                 }
 
                 head.x = 0;
@@ -252,42 +1030,54 @@ impl Buffer {
             } else { break }
         }
 
-        // Concatenate first and last lines
-        let last = self.lines
+        // Concatenate first and last lines, ending where the last one did
+        let (last, ending) = self.lines
             .get_mut(to.y)
-            .map(|l| l.clear())
+            .map(|mut l| (l.clear(), l.ending.take()))
             .unwrap_or_default();
 
-        if let Some(first) = self.lines.get_mut(from.y) {
+        if let Some(mut first) = self.lines.get_mut(from.y) {
             first.concat_str(&last);
+            first.ending = ending;
         }
 
-        // Delete empty lines between `from` and `to`
-        if from.y != to.y {
-            for i in (from.y..=to.y).rev() {
-                if let Some(line) = self.lines.get(i) {
-                    if line.text.is_empty() {
-                        self.lines.remove(i);
-                    }
-                }
-            }
+        // Delete the now empty lines after `from`
+        let last = min(to.y, self.line_count().saturating_sub(1));
+        if from.y < last {
+            self.lines.remove_range(from.y + 1..last + 1);
         }
 
         if self.line_count() == 0 {
             self.lines.push(Line::new());
         }
 
-        return buffer;
+        buffer
     }
 
-    fn insert(&mut self, start: &Point, s: &String) -> Option<Point> {
+    fn insert(&mut self, start: &Point, s: &str) -> Option<Point> {
         if start.y >= self.line_count() { return None; }
 
         let mut line = self.lines.remove(start.y);
         let tail = line.text.split_off(start.x);
         let buffer = line.text + s + &tail;
 
-        let lines: Vec<Line> = buffer.lines().map(Line::from).collect();
+        let mut lines = Vec::new();
+        let mut rest = buffer.as_str();
+        loop {
+            match LineEnding::find(rest) {
+                Some((i, ending)) => {
+                    lines.push(Line::from(&rest[..i]));
+                    rest = &rest[i + ending.value().len()..];
+                },
+                None => {
+                    lines.push(Line::from(rest));
+                    break;
+                }
+            }
+        }
+        if let Some(last) = lines.last_mut() {
+            last.ending = line.ending;
+        }
         let len = lines.last().map_or(0, |l| l.text.len() - tail.len());
         let count = lines.len() - 1;
 
@@ -298,25 +1088,34 @@ impl Buffer {
         Some(Point { x: len, y: start.y + count })
     }
 
-    pub fn execute(&mut self, edit: &Edit) -> Option<Edit> {
-        let undo: Option<Edit> = match edit {
+    // Apply `edit`, returns the edit that undoes it and what changed
+    pub fn execute(&mut self, edit: &Edit) -> Option<(Edit, Change)> {
+        let result: Option<(Edit, Change)> = match edit {
             Edit::Insert(ch, pt) => {
-                if let Some(line) = self.lines.get_mut(pt.y) {
-                    if *ch == '\n' {
-                        let tail = line.split(pt.x);
-                        let index = pt.y + 1;
-                        self.lines.insert(index, tail);
-                        Some(Edit::Delete(Point { x: 0, y: index - 1 }))
-                    } else {
-                        line.insert(*ch, pt.x);
-                        Some(Edit::Delete(pt.clone()))
-                    }
-                } else {
+                if pt.y >= self.line_count() {
                     None
+                } else if *ch == '\n' {
+                    let auto_indent = self.settings.auto_indent;
+                    let mut line = self.lines.get_mut(pt.y)?;
+                    let mut tail = line.split(pt.x);
+                    // The new line starts with the same indentation, undone along with it
+                    let indent = if auto_indent { indent::leading(&line.text) } else { "" };
+                    tail.insert_str(indent, 0);
+                    let inserted = Point { x: indent.len(), y: pt.y + 1 };
+                    drop(line);
+                    self.lines.insert(inserted.y, tail);
+                    Some((Edit::Cut(*pt, inserted), Change::new(*pt, *pt, inserted)))
+                } else {
+                    let mut line = self.lines.get_mut(pt.y)?;
+                    let before = line.text.len();
+                    line.insert(*ch, pt.x);
+                    let inserted = Point { x: pt.x + line.text.len() - before, y: pt.y };
+                    Some((Edit::Delete(*pt), Change::new(*pt, *pt, inserted)))
                 }
             },
             Edit::Overwrite(ch, pt) => {
-                if let Some(line) = self.lines.get_mut(pt.y) {
+                if let Some(mut line) = self.lines.get_mut(pt.y) {
+                    let before = line.text.len();
                     let mut cursor = GraphemeCursor::new(pt.x, line.text.len(), true);
                     match cursor.next_boundary(&line.text, 0) {
                         Ok(Some(next)) => {
@@ -325,12 +1124,16 @@ impl Buffer {
                                 .chars()
                                 .last()
                                 .expect("No character returned");
-                            Some(Edit::Overwrite(previous, pt.clone()))
+                            let removed = Point { x: next, y: pt.y };
+                            let inserted = Point { x: next + line.text.len() - before, y: pt.y };
+                            Some((Edit::Overwrite(previous, *pt), Change::new(*pt, removed, inserted)))
                         },
                         Ok(None) => {
                             // Append to the end of the line
-                            line.insert(*ch, line.text.len());
-                            Some(Edit::Delete(pt.clone()))
+                            let end = line.text.len();
+                            line.insert(*ch, end);
+                            let inserted = Point { x: line.text.len(), y: pt.y };
+                            Some((Edit::Delete(*pt), Change::new(*pt, *pt, inserted)))
                         },
                         Err(_) => panic!("Incomplete chunk - overwrite")
                     }
@@ -344,21 +1147,25 @@ impl Buffer {
                     match cursor.next_boundary(&line.text, 0) {
                         Ok(Some(next)) => {
                             // Delete some character in this line
-                            let line = self.lines.get_mut(pt.y).unwrap();
+                            let mut line = self.lines.get_mut(pt.y).unwrap();
                             let ch = line.delete(pt.x..next)
                                 .chars()
                                 .last()
                                 .expect("No character returned");
-                            Some(Edit::Insert(ch, pt.clone()))
+                            let removed = Point { x: next, y: pt.y };
+                            Some((Edit::Insert(ch, *pt), Change::new(*pt, removed, *pt)))
                         },
                         Ok(None) => { 
                             // Delete ending and join with next line
                             if pt.y < self.line_count() - 1 {
                                 let next = self.lines.remove(pt.y + 1);
-                                let line = self.lines.get_mut(pt.y).unwrap();
+                                let mut line = self.lines.get_mut(pt.y).unwrap();
                                 let len = line.text.len();
                                 line.concat(&next);
-                                Some(Edit::Insert('\n', Point { x: len, y: pt.y }))
+                                let start = Point { x: len, y: pt.y };
+                                let removed = Point { x: 0, y: pt.y + 1 };
+                                // Pasted back rather than typed, so it isn't indented again
+                                Some((Edit::Paste(start, String::from("\n")), Change::new(start, removed, start)))
                             } else {
                                 None
                             }
@@ -371,21 +1178,24 @@ impl Buffer {
             },
             Edit::Cut(l, r) => {
                 let buffer = self.drain(l, r);
-                Some(Edit::Paste(l.clone(), buffer))
+                Some((Edit::Paste(*l, buffer), Change::new(*l, *r, *l)))
             },
             Edit::Paste(pt, s) => {
                 self.insert(pt, s)
-                    .map(|end| Edit::Cut(pt.clone(), end))
+                    .map(|end| (Edit::Cut(*pt, end), Change::new(*pt, *pt, end)))
             },
             Edit::Replace(l, r, s) => {
                 let buffer = self.drain(l, r);
                 self.insert(l, s)
-                    .map(|end| Edit::Replace(l.clone(), end, buffer))
+                    .map(|end| (Edit::Replace(*l, end, buffer), Change::new(*l, *r, end)))
             }
         };
         
-        self.dirty |= undo.is_some();
-        return undo;
+        if result.is_some() {
+            self.dirty = true;
+            self.revision += 1;
+        }
+        result
     }
 }
 
@@ -394,7 +1204,8 @@ impl Display for Buffer {
         for (i, line) in self.lines.iter().enumerate() {
             write!(f, "{}", line.text)?;
             if i < self.lines.len() - 1 {
-                write!(f, "{}", self.ending.value())?;
+                let own = line.ending.as_ref().filter(|_| self.settings.preserve_endings);
+                write!(f, "{}", own.unwrap_or(&self.ending).value())?;
             }
         }
         
@@ -408,9 +1219,111 @@ impl std::fmt::Debug for Buffer {
          .field("path", &self.path)
          .field("length", &self.lines.len())
          .field("ending", &self.ending.value())
+         .field("encoding", &self.encoding.to_string())
+         .field("size", &self.size)
          .field("modified", &self.modified)
          .field("dirty", &self.dirty)
          .field("readonly", &self.readonly)
          .finish()
     }
-}
\ No newline at end of file
+}
+// Write `data` to a temporary file beside `path` and rename it over the original, so a crash
+// leaves either the old file or the new one. Fails with `PermissionDenied` where that can't
+// keep the file as it was, like when the directory isn't writable or the owner would change
+fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    // Replace the file a link points to, not the link
+    let target = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let name = target.file_name().ok_or_else(|| io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "Not a file name"
+    ))?;
+    let directory = target.parent()
+        .filter(|d| !d.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let temp = directory.join(format!(".{}.ted-{}~", name.to_string_lossy(), process::id()));
+
+    // Only replace files that could be written in place anyway
+    let original = match OpenOptions::new().write(true).open(&target) {
+        Ok(file) => Some(file.metadata()?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e)
+    };
+
+    let result = (|| {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&temp)?;
+        if let Some(metadata) = &original {
+            fs::fchown(&file, Some(metadata.uid()), Some(metadata.gid()))?;
+            file.set_permissions(metadata.permissions())?;
+        }
+
+        let mut writer = BufWriter::new(&file);
+        writer.write_all(data)?;
+        writer.flush()?;
+        drop(writer);
+        file.sync_all()?;
+        std::fs::rename(&temp, &target)
+    })();
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    } else if let Ok(directory) = File::open(directory) {
+        // Make the rename itself last
+        let _ = directory.sync_all();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(encoding: Encoding, text: &str) -> Vec<u8> {
+        let bytes = encoding.encode(text).unwrap();
+        assert_eq!(Encoding::detect(&bytes), encoding);
+        assert_eq!(encoding.decode(bytes.clone()).unwrap(), text);
+        bytes
+    }
+
+    #[test]
+    fn latin1() {
+        let bytes = round_trip(Encoding::Latin1, "café\tÿ\r\n");
+        assert_eq!(bytes, b"caf\xe9\t\xff\r\n");
+        assert!(Encoding::Latin1.encode("✓").is_err());
+    }
+
+    #[test]
+    fn utf8_bom() {
+        let bytes = round_trip(Encoding::UTF8BOM, "é ✓\n");
+        assert!(bytes.starts_with(UTF8_BOM));
+        assert_eq!(Encoding::UTF8.decode(bytes).unwrap(), "é ✓\n");
+    }
+
+    #[test]
+    fn utf16() {
+        let text = "héllo ✓ \u{1f600}\n";
+        let le = round_trip(Encoding::UTF16LE, text);
+        let be = round_trip(Encoding::UTF16BE, text);
+        assert!(le.starts_with(&[0xff, 0xfe]));
+        assert!(be.starts_with(&[0xfe, 0xff]));
+        assert_eq!(le.len(), 2 + 2 * text.encode_utf16().count());
+
+        // An odd byte at the end, and half of a surrogate pair on its own
+        for (encoding, mut bytes) in [(Encoding::UTF16LE, le), (Encoding::UTF16BE, be)] {
+            let error = format!("File isn't valid {}", encoding);
+            bytes.push(b'a');
+            assert_eq!(encoding.decode(bytes.clone()).unwrap_err().to_string(), error);
+
+            let lone = [0xd83d, u16::from(b'a')].into_iter().flat_map(|u| match encoding {
+                Encoding::UTF16LE => u.to_le_bytes(),
+                _ => u.to_be_bytes()
+            });
+            bytes.pop();
+            bytes.splice(2..2, lone);
+            assert_eq!(encoding.decode(bytes).unwrap_err().to_string(), error);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use super::Settings;
+
+// Apply the settings from a Vim or Emacs modeline in `line`, if it has one
+pub fn apply(line: &str, settings: &mut Settings) -> bool {
+    vim(line, settings) || emacs(line, settings)
+}
+
+// Vim modelines look like `vim: ts=4 et` or `vim: set ts=4 et :`
+fn vim(line: &str, settings: &mut Settings) -> bool {
+    let start = ["vim:", "vi:", "ex:"]
+        .iter()
+        .filter_map(|m| {
+            line.match_indices(m)
+                .find(|(i, _)| *i == 0 || line[..*i].ends_with(char::is_whitespace))
+                .map(|(i, _)| i + m.len())
+        })
+        .min();
+
+    let rest = match start {
+        Some(i) => line[i..].trim_start(),
+        None => return false
+    };
+
+    // The `set` form ends at the next colon, otherwise colons separate options too
+    let options: Vec<&str> = match rest.strip_prefix("set ").or(rest.strip_prefix("se ")) {
+        Some(rest) => rest
+            .split(':')
+            .next()
+            .unwrap_or_default()
+            .split_whitespace()
+            .collect(),
+        None => rest
+            .split(|c: char| c == ':' || c.is_whitespace())
+            .filter(|o| !o.is_empty())
+            .collect()
+    };
+
+    for option in options {
+        let (key, value) = option.split_once('=').unwrap_or((option, ""));
+        match key {
+            "ts" | "tabstop" => {
+                if let Ok(n) = value.parse() { settings.tab_width = n; }
+            },
+            "tw" | "textwidth" => {
+                settings.fill_column = value.parse().ok().filter(|&n| n > 0);
+            },
+            "et" | "expandtab" => settings.expand_tabs = true,
+            "noet" | "noexpandtab" => settings.expand_tabs = false,
+            "ai" | "autoindent" => settings.auto_indent = true,
+            "noai" | "noautoindent" => settings.auto_indent = false,
+            "ft" | "filetype" if !value.is_empty() => {
+                settings.filetype = Some(value.to_lowercase());
+            },
+            _ => ()
+        }
+    }
+
+    true
+}
+
+// Emacs modelines look like `-*- mode: python; tab-width: 4 -*-` or just `-*- python -*-`
+fn emacs(line: &str, settings: &mut Settings) -> bool {
+    let start = match line.find("-*-") {
+        Some(i) => i + 3,
+        None => return false
+    };
+    let end = match line[start..].find("-*-") {
+        Some(i) => start + i,
+        None => return false
+    };
+    let content = line[start..end].trim();
+
+    if !content.contains(':') {
+        if !content.is_empty() {
+            settings.filetype = Some(content.to_lowercase());
+        }
+        return true;
+    }
+
+    for variable in content.split(';') {
+        let (key, value) = match variable.split_once(':') {
+            Some((k, v)) => (k.trim(), v.trim()),
+            None => continue
+        };
+
+        match key {
+            "mode" => settings.filetype = Some(value.to_lowercase()),
+            "tab-width" => {
+                if let Ok(n) = value.parse() { settings.tab_width = n; }
+            },
+            "fill-column" => settings.fill_column = value.parse().ok(),
+            "indent-tabs-mode" => settings.expand_tabs = value == "nil",
+            _ => ()
+        }
+    }
+
+    true
+}
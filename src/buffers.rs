@@ -0,0 +1,75 @@
+use crate::screen::colors::Colors;
+use crate::screen::{format_size, Screen};
+use crate::terminal;
+use crate::width::DisplayWidth;
+use termion as t;
+use std::io::{self, Write};
+
+const NAME_WIDTH: usize = 24;
+
+fn row(index: &str, flags: &str, name: &str, size: &str, path: &str, width: usize) -> String {
+    let name_pad = NAME_WIDTH.saturating_sub(name.display_width());
+    let s = format!(" {:>3} {:<2} {}{:name_pad$} {:>10}  {}", index, flags, name, "", size, path);
+    let pad = width.saturating_sub(s.display_width());
+    format!("{}{:pad$}", s, "")
+}
+
+// Draw a list of all screens, `selected` is highlighted and `marked` ones flagged for closing
+pub fn draw<W>(out: &mut W, screens: &[Screen], selected: usize, marked: &[bool], help: &str,
+    colors: &Colors) -> io::Result<()> where W : Write
+{
+    let (width, height) = terminal::size()?;
+    let width = width as usize;
+    let rows = height as usize - 2;
+
+    write!(out, "{}{}", t::clear::All, t::cursor::Goto(1, 1))?;
+    write!(out, "{}{}{}",
+        t::color::Bg(colors.status_bg),
+        t::color::Fg(colors.status_fg),
+        row("#", "", "Name", "Size", "Path", width)
+    )?;
+    write!(out, "{}{}", t::color::Bg(t::color::Reset), t::color::Fg(t::color::Reset))?;
+
+    // Scroll so that the selected row is visible
+    let first = (selected + 1).saturating_sub(rows);
+
+    for (i, screen) in screens.iter().enumerate().skip(first).take(rows) {
+        let mut flags = String::new();
+        flags.push(if marked[i] { 'D' } else { ' ' });
+        flags.push(if screen.is_dirty() { '*' } else { ' ' });
+
+        let path = screen.path().display().to_string();
+        let line = row(
+            &(i + 1).to_string(),
+            &flags,
+            screen.display_name(),
+            &format_size(screen.byte_count() as u64),
+            &path,
+            width
+        );
+
+        write!(out, "{}", t::cursor::Goto(1, (i - first + 2) as u16))?;
+        if i == selected {
+            write!(out, "{}{}{}{}{}",
+                t::color::Bg(colors.selection_bg),
+                t::color::Fg(colors.selection_fg),
+                line,
+                t::color::Bg(t::color::Reset),
+                t::color::Fg(t::color::Reset)
+            )?;
+        } else {
+            write!(out, "{}", line)?;
+        }
+    }
+
+    let pad = width - 1;
+    write!(out, "{}{}{} {:<pad$}{}{}",
+        t::cursor::Goto(1, height),
+        t::color::Bg(colors.status_bg),
+        t::color::Fg(colors.status_fg),
+        help,
+        t::color::Bg(t::color::Reset),
+        t::color::Fg(t::color::Reset)
+    )?;
+    out.flush()
+}
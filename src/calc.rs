@@ -0,0 +1,227 @@
+use std::f64::consts;
+use std::iter::Peekable;
+use std::str::Chars;
+
+// Evaluates arithmetic with + - * / % ^, parentheses, and a few functions and constants
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        self.chars.next_if_eq(&c).is_some()
+    }
+
+    // sum = product (('+' | '-') product)*
+    fn sum(&mut self) -> Result<f64, String> {
+        let mut value = self.product()?;
+        loop {
+            if self.eat('+') {
+                value += self.product()?;
+            } else if self.eat('-') {
+                value -= self.product()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    // product = unary (('*' | '/' | '%') unary)*
+    fn product(&mut self) -> Result<f64, String> {
+        let mut value = self.unary()?;
+        loop {
+            if self.eat('*') {
+                value *= self.unary()?;
+            } else if self.eat('/') {
+                value /= self.unary()?;
+            } else if self.eat('%') {
+                value %= self.unary()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    // unary = ('-' | '+') unary | power
+    fn unary(&mut self) -> Result<f64, String> {
+        if self.eat('-') {
+            Ok(-self.unary()?)
+        } else if self.eat('+') {
+            self.unary()
+        } else {
+            self.power()
+        }
+    }
+
+    // power = atom ('^' unary)?, so it binds right to left
+    fn power(&mut self) -> Result<f64, String> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            Ok(base.powf(self.unary()?))
+        } else {
+            Ok(base)
+        }
+    }
+
+    // atom = number | name | name '(' sum ')' | '(' sum ')'
+    fn atom(&mut self) -> Result<f64, String> {
+        if self.eat('(') {
+            let value = self.sum()?;
+            return if self.eat(')') { Ok(value) } else { Err(String::from("Missing ')'")) };
+        }
+
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some(c) if c.is_ascii_digit() || *c == '.' => self.number(),
+            Some(c) if c.is_alphabetic() => {
+                let mut name = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_alphanumeric()) {
+                    name.push(c);
+                }
+                self.call(&name)
+            },
+            Some(c) => Err(format!("Unexpected '{}'", c)),
+            None => Err(String::from("Unexpected end of expression"))
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, String> {
+        let mut text = String::new();
+        while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit() || *c == '.' || *c == '_') {
+            text.push(c);
+        }
+        // An exponent, like `1e-3`
+        if let Some(e) = self.chars.next_if(|c| *c == 'e' || *c == 'E') {
+            text.push(e);
+            if let Some(sign) = self.chars.next_if(|c| *c == '-' || *c == '+') {
+                text.push(sign);
+            }
+            while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit()) {
+                text.push(c);
+            }
+        }
+        text.replace('_', "").parse().map_err(|_| format!("Invalid number '{}'", text))
+    }
+
+    fn call(&mut self, name: &str) -> Result<f64, String> {
+        match name {
+            "pi" => return Ok(consts::PI),
+            "e" => return Ok(consts::E),
+            _ => ()
+        }
+
+        let function: fn(f64) -> f64 = match name {
+            "sqrt" => f64::sqrt,
+            "abs" => f64::abs,
+            "floor" => f64::floor,
+            "ceil" => f64::ceil,
+            "round" => f64::round,
+            "exp" => f64::exp,
+            "ln" => f64::ln,
+            "log" => f64::log10,
+            "sin" => f64::sin,
+            "cos" => f64::cos,
+            "tan" => f64::tan,
+            _ => return Err(format!("Unknown name '{}'", name))
+        };
+        if !self.eat('(') {
+            return Err(format!("Missing '(' after {}", name));
+        }
+        let argument = self.sum()?;
+        if !self.eat(')') {
+            return Err(String::from("Missing ')'"));
+        }
+        Ok(function(argument))
+    }
+}
+
+pub fn evaluate(expression: &str) -> Result<f64, String> {
+    let mut parser = Parser { chars: expression.chars().peekable() };
+    let value = parser.sum()?;
+
+    parser.skip_whitespace();
+    match parser.chars.peek() {
+        Some(c) => Err(format!("Unexpected '{}'", c)),
+        None => Ok(value)
+    }
+}
+
+// Show `value` without floating point noise, like `0.1 + 0.2` = `0.3`
+pub fn format(value: f64) -> String {
+    if !value.is_finite() {
+        return value.to_string();
+    }
+    if value != 0.0 && (value.abs() < 1e-6 || value.abs() >= 1e15) {
+        return format!("{:e}", value);
+    }
+
+    let fixed = format!("{:.10}", value);
+    let trimmed = fixed.trim_end_matches('0').trim_end_matches('.');
+    if trimmed == "-0" { String::from("0") } else { String::from(trimmed) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calc(expression: &str) -> String {
+        format(evaluate(expression).unwrap())
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(calc("1 + 2 * 3"), "7");
+        assert_eq!(calc("(1 + 2) * 3"), "9");
+        assert_eq!(calc("2^3^2"), "512");
+        assert_eq!(calc("-2^2"), "-4");
+        assert_eq!(calc("2^-1"), "0.5");
+        assert_eq!(calc("7 % 4 - 10 / 4"), "0.5");
+        assert_eq!(calc("--3"), "3");
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(calc("0.1+0.2"), "0.3");
+        assert_eq!(calc("1e-3"), "0.001");
+        assert_eq!(calc("2.5E+2"), "250");
+        assert_eq!(calc("1_000_000 * 2"), "2000000");
+        assert_eq!(calc(".5"), "0.5");
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(calc("sqrt(16) + abs(-1)"), "5");
+        assert_eq!(calc("round(pi * 100)"), "314");
+        assert_eq!(calc("ln(e)"), "1");
+        assert_eq!(calc("log(1000)"), "3");
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(format(0.0), "0");
+        assert_eq!(format(-0.0), "0");
+        assert_eq!(format(-1e-12), "-1e-12");
+        assert_eq!(format(1e20), "1e20");
+        assert_eq!(format(1.0 / 3.0), "0.3333333333");
+        assert_eq!(format(f64::INFINITY), "inf");
+        assert_eq!(format(f64::NAN), "NaN");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(evaluate("1 +"), Err(String::from("Unexpected end of expression")));
+        assert_eq!(evaluate("(1 + 2"), Err(String::from("Missing ')'")));
+        assert_eq!(evaluate("1 2"), Err(String::from("Unexpected '2'")));
+        assert_eq!(evaluate("2 * $"), Err(String::from("Unexpected '$'")));
+        assert_eq!(evaluate("1..2"), Err(String::from("Invalid number '1..2'")));
+        assert_eq!(evaluate("foo(1)"), Err(String::from("Unknown name 'foo'")));
+        assert_eq!(evaluate("sqrt 4"), Err(String::from("Missing '(' after sqrt")));
+        assert_eq!(evaluate("sqrt(4"), Err(String::from("Missing ')'")));
+        assert_eq!(evaluate(""), Err(String::from("Unexpected end of expression")));
+    }
+}
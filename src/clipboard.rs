@@ -0,0 +1,87 @@
+use crate::transform;
+use std::env;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// A program that copies stdin to the clipboard, its arguments for that and for printing
+// the clipboard back
+type Tool = (&'static str, &'static [&'static str], &'static [&'static str]);
+
+const MACOS: &[Tool] = &[
+    ("pbcopy", &[], &[])
+];
+const WAYLAND: &[Tool] = &[
+    ("wl-copy", &[], &[])
+];
+const X11: &[Tool] = &[
+    ("xclip", &["-selection", "clipboard"], &["-selection", "clipboard", "-o"]),
+    ("xsel", &["--clipboard", "--input"], &["--clipboard", "--output"])
+];
+
+// The clipboard tools that could work in this session, in order of preference
+fn tools() -> Vec<Tool> {
+    let mut tools = Vec::new();
+    if cfg!(target_os = "macos") {
+        tools.extend_from_slice(MACOS);
+    }
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        tools.extend_from_slice(WAYLAND);
+    }
+    if env::var_os("DISPLAY").is_some() {
+        tools.extend_from_slice(X11);
+    }
+    tools
+}
+
+fn paste_command(program: &str, args: &[&str]) -> Command {
+    // pbpaste and wl-paste are separate programs from the ones that copy
+    let mut command = match program {
+        "pbcopy" => Command::new("pbpaste"),
+        "wl-copy" => {
+            let mut command = Command::new("wl-paste");
+            command.arg("--no-newline");
+            command
+        },
+        _ => Command::new(program)
+    };
+    command.args(args);
+    command
+}
+
+// Put `text` on the system clipboard, returns `false` if no tool for it worked
+pub fn copy(text: &str) -> bool {
+    tools().into_iter().any(|(program, args, _)| {
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(c) => c,
+            Err(_) => return false
+        };
+        let written = child.stdin.take().is_some_and(|mut stdin| stdin.write_all(text.as_bytes()).is_ok());
+        child.wait().is_ok_and(|s| s.success()) && written
+    })
+}
+
+// The text on the system clipboard, if a tool could read it
+pub fn paste() -> Option<String> {
+    tools().into_iter().find_map(|(program, _, args)| {
+        let output = paste_command(program, args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()
+            .filter(|o| o.status.success())?;
+        String::from_utf8(output.stdout).ok()
+    })
+}
+
+// An OSC 52 sequence asking the terminal itself to set the clipboard to `text`. This
+// works over SSH too, but the clipboard can't be read back this way
+pub fn osc52(text: &str) -> String {
+    let encoded = transform::base64_encode(text).unwrap_or_default();
+    format!("\x1b]52;c;{}\x07", encoded)
+}
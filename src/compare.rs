@@ -0,0 +1,130 @@
+use crate::diff::diff;
+use crate::screen::colors::{Colors, Paint};
+use crate::screen::truncate;
+use crate::terminal;
+use crate::width::DisplayWidth;
+use termion as t;
+use std::io::{self, Write};
+
+
+// A row of the side-by-side view, with the line shown from each side (if any)
+#[derive(Debug, Clone, Copy)]
+pub struct Row {
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+    pub changed: bool
+}
+
+// Line up `left` and `right` so unchanged lines share a row
+pub fn align(left: &[&str], right: &[&str]) -> Vec<Row> {
+    let mut rows = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    let hunks = diff(left, right);
+    for hunk in hunks.iter() {
+        while i < hunk.old.start {
+            rows.push(Row { left: Some(i), right: Some(j), changed: false });
+            i += 1;
+            j += 1;
+        }
+
+        let count = hunk.old.len().max(hunk.new.len());
+        for k in 0..count {
+            rows.push(Row {
+                left: Some(hunk.old.start + k).filter(|l| hunk.old.contains(l)),
+                right: Some(hunk.new.start + k).filter(|r| hunk.new.contains(r)),
+                changed: true
+            });
+        }
+        i = hunk.old.end;
+        j = hunk.new.end;
+    }
+
+    while i < left.len() {
+        rows.push(Row { left: Some(i), right: Some(j), changed: false });
+        i += 1;
+        j += 1;
+    }
+    rows
+}
+
+// The first row of each run of changed rows
+pub fn change_starts(rows: &[Row]) -> Vec<usize> {
+    (0..rows.len())
+        .filter(|&i| rows[i].changed && (i == 0 || !rows[i - 1].changed))
+        .collect()
+}
+
+fn draw_pane<W>(out: &mut W, lines: &[&str], line: Option<usize>, bg: Option<Paint>, width: usize, colors: &Colors)
+    -> io::Result<()> where W : Write
+{
+    let number_width = lines.len().to_string().len();
+    let text_width = width.saturating_sub(number_width + 1);
+
+    if let Some(bg) = bg {
+        write!(out, "{}", t::color::Bg(bg))?;
+    }
+    match line {
+        Some(n) => {
+            let text = truncate(lines[n], text_width);
+            let pad = text_width - text.display_width();
+            write!(out, "{}{:>number_width$}{} {}{:pad$}",
+                t::color::Fg(colors.diff_number_fg),
+                n + 1,
+                t::color::Fg(t::color::Reset),
+                text,
+                ""
+            )?;
+        },
+        None => write!(out, "{}{:width$}", t::color::Bg(colors.diff_filler_bg), "")?
+    }
+    write!(out, "{}", t::color::Bg(t::color::Reset))
+}
+
+// Draw `rows` starting at `top`, with the left and right `lines` side by side
+pub fn draw<W>(out: &mut W, names: (&str, &str), lines: (&[&str], &[&str]), rows: &[Row], top: usize,
+    help: &str, colors: &Colors) -> io::Result<()> where W : Write
+{
+    let (left, right) = lines;
+    let (width, height) = terminal::size()?;
+    let width = width as usize;
+    let pane = (width - 1) / 2;
+    let right_pane = width - 1 - pane;
+
+    write!(out, "{}{}", t::clear::All, t::cursor::Goto(1, 1))?;
+    let (l, r) = (truncate(names.0, pane - 1), truncate(names.1, right_pane - 1));
+    let (lpad, rpad) = (pane - 1 - l.display_width(), right_pane - 1 - r.display_width());
+    write!(out, "{}{} {}{:lpad$}│ {}{:rpad$}{}{}",
+        t::color::Bg(colors.status_bg),
+        t::color::Fg(colors.status_fg),
+        l, "",
+        r, "",
+        t::color::Bg(t::color::Reset),
+        t::color::Fg(t::color::Reset)
+    )?;
+
+    for (i, row) in rows.iter().skip(top).take(height as usize - 2).enumerate() {
+        let (lbg, rbg) = match (row.changed, row.left, row.right) {
+            (false, _, _) => (None, None),
+            (true, Some(_), Some(_)) => (Some(colors.diff_changed_bg), Some(colors.diff_changed_bg)),
+            (true, Some(_), None) => (Some(colors.diff_removed_bg), None),
+            (true, None, _) => (None, Some(colors.diff_added_bg))
+        };
+
+        write!(out, "{}", t::cursor::Goto(1, i as u16 + 2))?;
+        draw_pane(out, left, row.left, lbg, pane, colors)?;
+        write!(out, "│")?;
+        draw_pane(out, right, row.right, rbg, right_pane, colors)?;
+    }
+
+    let pad = width - 1;
+    write!(out, "{}{}{} {:<pad$}{}{}",
+        t::cursor::Goto(1, height),
+        t::color::Bg(colors.status_bg),
+        t::color::Fg(colors.status_fg),
+        help,
+        t::color::Bg(t::color::Reset),
+        t::color::Fg(t::color::Reset)
+    )?;
+    out.flush()
+}
@@ -0,0 +1,162 @@
+use std::io::{self, BufRead, BufReader, Read};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+
+// Where a compiler message points to, zero-based
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub path: PathBuf,
+    pub row: usize,
+    pub column: usize
+}
+
+// Find a `file:line[:column]` location in a line of compiler output, such as
+// `src/main.c:10:5: error: ...` or rustc's `  --> src/main.rs:10:5`
+pub fn parse_location(line: &str, directory: &Path) -> Option<Location> {
+    let line = line.trim_start();
+    let line = line.strip_prefix("--> ").unwrap_or(line);
+
+    let mut parts = line.splitn(4, ':');
+    let path = parts.next()?;
+    if path.is_empty() || path.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let row: usize = parts.next()?.parse().ok()?;
+    let column: usize = parts
+        .next()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(1);
+
+    // Ignore anything that only looks like a location, e.g. timestamps
+    let path = directory.join(path);
+    if !path.is_file() {
+        return None;
+    }
+
+    Some(Location {
+        path,
+        row: row.saturating_sub(1),
+        column: column.saturating_sub(1)
+    })
+}
+
+fn forward<R>(reader: R, tx: Sender<String>) where R : Read + Send + 'static {
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines().map_while(Result::ok) {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+// A build command running in the background
+pub struct Compilation {
+    child: Child,
+    rx: Receiver<String>,
+    directory: PathBuf,
+    pub screen: Option<usize>, // Index of the screen showing the output, if still open
+    lines: usize, // Lines of output so far
+    errors: Vec<(usize, Location)>, // Output line of each error and where it points
+    next: usize, // The error `next_error` will visit
+    finished: bool
+}
+
+impl Compilation {
+    pub fn start(command: &str, directory: &Path, screen: usize) -> io::Result<Self> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .current_dir(directory)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // In a group of its own, so stopping it stops whatever the shell started too
+            .process_group(0)
+            .spawn()?;
+
+        let (tx, rx) = mpsc::channel();
+        if let Some(stdout) = child.stdout.take() {
+            forward(stdout, tx.clone());
+        }
+        if let Some(stderr) = child.stderr.take() {
+            forward(stderr, tx);
+        }
+
+        Ok(Compilation {
+            child,
+            rx,
+            directory: directory.to_path_buf(),
+            screen: Some(screen),
+            lines: 0,
+            errors: Vec::new(),
+            next: 0,
+            finished: false
+        })
+    }
+
+    // Collect any new output, once the command exits its status is reported as the last line
+    pub fn read(&mut self) -> Vec<String> {
+        let mut output = Vec::new();
+        loop {
+            match self.rx.try_recv() {
+                Ok(line) => output.push(line),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if !self.finished {
+                        self.finished = true;
+                        output.push(match self.child.wait() {
+                            Ok(s) if s.success() => String::from("Compilation finished"),
+                            Ok(s) => format!("Compilation exited abnormally ({})", s),
+                            Err(e) => format!("Compilation failed: {}", e)
+                        });
+                    }
+                    break;
+                }
+            }
+        }
+
+        for line in output.iter() {
+            if let Some(location) = parse_location(line, &self.directory) {
+                self.errors.push((self.lines, location));
+            }
+            self.lines += 1;
+        }
+        output
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.errors.len()
+    }
+
+    // The next error's number, line in the output and location, wrapping around after the last
+    pub fn next_error(&mut self) -> Option<(usize, usize, Location)> {
+        if self.errors.is_empty() {
+            return None;
+        }
+        if self.next >= self.errors.len() {
+            self.next = 0;
+        }
+        let (line, location) = self.errors[self.next].clone();
+        self.next += 1;
+        Some((self.next, line, location))
+    }
+}
+
+impl Drop for Compilation {
+    fn drop(&mut self) {
+        if !self.finished {
+            // The group has the same ID as the shell leading it
+            unsafe { libc::kill(-(self.child.id() as libc::pid_t), libc::SIGKILL) };
+            let _ = self.child.wait();
+        }
+    }
+}
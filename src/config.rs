@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool)
+}
+
+// A configuration file in a small subset of TOML: `[table]` headers and `key = value`
+// pairs, where values are strings, integers or booleans
+#[derive(Debug, Default)]
+pub struct ConfigFile {
+    tables: HashMap<String, HashMap<String, Value>> // Top-level keys are in the "" table
+}
+
+fn unquote(s: &str) -> Option<String> {
+    let inner = s.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::new();
+    let mut chars = inner.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            'r' => out.push('\r'),
+            '"' => out.push('"'),
+            '\\' => out.push('\\'),
+            _ => return None
+        }
+    }
+
+    Some(out)
+}
+
+// Remove a trailing comment, ignoring any `#` inside strings
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\\' if quoted => { escaped = !escaped; continue; },
+            '"' if !escaped => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => ()
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn parse_key(s: &str) -> Option<String> {
+    let s = s.trim();
+    if s.starts_with('"') {
+        return unquote(s);
+    }
+
+    let valid = !s.is_empty() && s
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.');
+    if valid { Some(String::from(s)) } else { None }
+}
+
+fn parse_value(s: &str) -> Option<Value> {
+    let s = s.trim();
+    match s {
+        "true" => Some(Value::Boolean(true)),
+        "false" => Some(Value::Boolean(false)),
+        _ if s.starts_with('"') => unquote(s).map(Value::String),
+        _ => s.replace('_', "").parse().ok().map(Value::Integer)
+    }
+}
+
+impl ConfigFile {
+    // Where the user's configuration is read from
+    pub fn path() -> Option<PathBuf> {
+        let base = env::var_os("XDG_CONFIG_HOME")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+        Some(base.join("ted").join("config.toml"))
+    }
+
+    // Load the user's configuration, which is empty if the file doesn't exist
+    pub fn load() -> Result<Self, String> {
+        let path = match ConfigFile::path() {
+            Some(p) => p,
+            None => return Ok(ConfigFile::default())
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(s) => ConfigFile::parse(&s)
+                .map_err(|e| format!("{}:{}", path.display(), e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(ConfigFile::default()),
+            Err(e) => Err(format!("{}: {}", path.display(), e))
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut file = ConfigFile::default();
+        let mut table = String::new();
+
+        for (i, line) in s.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            let error = |m: &str| format!("{}: {}", i + 1, m);
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[') {
+                let name = header
+                    .strip_suffix(']')
+                    .and_then(parse_key)
+                    .ok_or_else(|| error("invalid table header"))?;
+                table = name;
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected `key = value`"))?;
+            let key = parse_key(key).ok_or_else(|| error("invalid key"))?;
+            let value = parse_value(value).ok_or_else(|| error("invalid value"))?;
+
+            file.tables
+                .entry(table.clone())
+                .or_default()
+                .insert(key, value);
+        }
+
+        Ok(file)
+    }
+
+    pub fn get(&self, table: &str, key: &str) -> Option<&Value> {
+        self.tables.get(table)?.get(key)
+    }
+
+    // Every key and value in `table`, in no particular order
+    pub fn table(&self, table: &str) -> impl Iterator<Item = (&str, &Value)> {
+        self.tables
+            .get(table)
+            .into_iter()
+            .flat_map(|t| t.iter().map(|(k, v)| (k.as_str(), v)))
+    }
+
+    pub fn string(&self, table: &str, key: &str) -> Option<&str> {
+        match self.get(table, key)? {
+            Value::String(s) => Some(s),
+            _ => None
+        }
+    }
+
+    pub fn integer(&self, table: &str, key: &str) -> Option<i64> {
+        match self.get(table, key)? {
+            Value::Integer(n) => Some(*n),
+            _ => None
+        }
+    }
+
+    pub fn boolean(&self, table: &str, key: &str) -> Option<bool> {
+        match self.get(table, key)? {
+            Value::Boolean(b) => Some(*b),
+            _ => None
+        }
+    }
+}
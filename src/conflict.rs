@@ -0,0 +1,115 @@
+use std::ops::Range;
+
+// A merge conflict, by the rows of its markers:
+//
+// <<<<<<< ours
+// ...
+// ||||||| base (only with `merge.conflictStyle = diff3`)
+// ...
+// =======
+// ...
+// >>>>>>> theirs
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub start: usize,
+    pub base: Option<usize>,
+    pub separator: usize,
+    pub end: usize
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Region {
+    Marker,
+    Ours,
+    Base,
+    Theirs
+}
+
+fn is_marker(line: &str, marker: &str) -> bool {
+    line.strip_prefix(marker)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+}
+
+impl Conflict {
+    pub fn ours(&self) -> Range<usize> {
+        self.start + 1..self.base.unwrap_or(self.separator)
+    }
+
+    pub fn theirs(&self) -> Range<usize> {
+        self.separator + 1..self.end
+    }
+
+    pub fn contains(&self, row: usize) -> bool {
+        self.start <= row && row <= self.end
+    }
+
+    pub fn region(&self, row: usize) -> Option<Region> {
+        if !self.contains(row) {
+            None
+        } else if row == self.start || row == self.separator || row == self.end || Some(row) == self.base {
+            Some(Region::Marker)
+        } else if self.ours().contains(&row) {
+            Some(Region::Ours)
+        } else if row < self.separator {
+            Some(Region::Base)
+        } else {
+            Some(Region::Theirs)
+        }
+    }
+}
+
+// Carry on looking for conflicts with `line`, at `row`. Returns a conflict it completes
+fn step(current: &mut Option<Conflict>, row: usize, line: &str) -> Option<Conflict> {
+    if is_marker(line, "<<<<<<<") {
+        *current = Some(Conflict { start: row, base: None, separator: 0, end: 0 });
+        return None;
+    }
+
+    let conflict = current.as_mut()?;
+    if is_marker(line, "|||||||") && conflict.separator == 0 {
+        conflict.base = Some(row);
+    } else if line.trim_end() == "=======" && conflict.separator == 0 {
+        conflict.separator = row;
+    } else if is_marker(line, ">>>>>>>") && conflict.separator != 0 {
+        conflict.end = row;
+        return current.take();
+    }
+    None
+}
+
+// Find all complete conflicts, in order
+pub fn find<'a, I>(lines: I) -> Vec<Conflict> where I : Iterator<Item = &'a str> {
+    let mut current = None;
+    lines
+        .enumerate()
+        .filter_map(|(row, line)| step(&mut current, row, line))
+        .collect()
+}
+
+// Find the conflicts again after the `edited` rows changed, given those found before
+// with the ones below the edit already moved down with the text. Looks from the last
+// conflict above the edit until one ends where an old one did, after which the rest
+// are as they were
+pub fn refind<'a, F>(old: &[Conflict], edited: Range<usize>, line: F) -> Vec<Conflict>
+    where F : Fn(usize) -> Option<&'a str>
+{
+    let above = old.iter().take_while(|c| c.end < edited.start).count();
+    let below = &old[above..];
+    let below = &below[below.iter().take_while(|c| c.start < edited.end).count()..];
+
+    let mut conflicts = old[..above].to_vec();
+    let mut current = None;
+    let mut row = above.checked_sub(1).map_or(0, |i| old[i].end + 1);
+    while let Some(text) = line(row) {
+        if let Some(conflict) = step(&mut current, row, text) {
+            let end = conflict.end;
+            conflicts.push(conflict);
+            if let Some(i) = below.iter().position(|c| c.end == end).filter(|_| end >= edited.end) {
+                conflicts.extend_from_slice(&below[i + 1..]);
+                break;
+            }
+        }
+        row += 1;
+    }
+    conflicts
+}
@@ -0,0 +1,123 @@
+use crate::screen::colors::Colors;
+use crate::screen::truncate;
+use crate::terminal;
+use crate::width::DisplayWidth;
+use termion::event::{Event, Key};
+use termion as t;
+use std::cmp::min;
+use std::io::{self, Write};
+
+
+const KEYS: &[(&str, &str)] = &[
+    ("C-x o", "Open a file"),
+    ("C-x n", "New buffer"),
+    ("C-x s", "Save"),
+    ("C-x z / y", "Undo / redo"),
+    ("C-x .", "Next buffer"),
+    ("C-x b", "Buffer list"),
+    ("C-x g", "Go to line"),
+    ("C-x x", "Command palette"),
+    ("C-x q", "Quit")
+];
+
+pub enum Choice {
+    Open(usize), // One of the recent files
+    Dismiss(Option<Event>) // With the key that closed the dashboard, to be handled as usual
+}
+
+fn draw<W>(out: &mut W, recent: &[String], selected: usize, colors: &Colors) -> io::Result<()>
+    where W : Write
+{
+    let (width, height) = terminal::size()?;
+    let width = width as usize;
+    let mut lines: Vec<(String, bool)> = Vec::new(); // Text and whether it's highlighted
+
+    if !recent.is_empty() {
+        lines.push((String::from("Recent files"), false));
+        for (i, file) in recent.iter().enumerate() {
+            lines.push((format!("  {}  {}", i + 1, file), i == selected));
+        }
+        lines.push((String::new(), false));
+    }
+    lines.push((String::from("Keys"), false));
+    for (key, description) in KEYS {
+        lines.push((format!("  {:<12}{}", key, description), false));
+    }
+
+    write!(out, "{}", t::clear::All)?;
+    write!(out, "{}{}ted - A toy Text EDitor{}", t::cursor::Goto(3, 2), t::style::Bold, t::style::Reset)?;
+
+    for (i, (text, highlighted)) in lines.iter().enumerate().take(height.saturating_sub(5) as usize) {
+        let text = truncate(text, width.saturating_sub(3));
+        write!(out, "{}", t::cursor::Goto(3, i as u16 + 4))?;
+        if *highlighted {
+            write!(out, "{}{}{}{}{}",
+                t::color::Bg(colors.selection_bg),
+                t::color::Fg(colors.selection_fg),
+                text,
+                t::color::Bg(t::color::Reset),
+                t::color::Fg(t::color::Reset)
+            )?;
+        } else if !text.starts_with(' ') {
+            write!(out, "{}{}{}", t::color::Fg(colors.section_fg), text, t::color::Fg(t::color::Reset))?;
+        } else {
+            write!(out, "{}", text)?;
+        }
+    }
+
+    let footer = if recent.is_empty() {
+        "Press any key to start editing"
+    } else {
+        "1-9 or Enter: open a recent file, any other key: start editing"
+    };
+    let footer = truncate(footer, width - 1);
+    let pad = width - 1 - footer.display_width();
+    write!(out, "{}{}{} {}{:pad$}{}{}",
+        t::cursor::Goto(1, height),
+        t::color::Bg(colors.status_bg),
+        t::color::Fg(colors.status_fg),
+        footer,
+        "",
+        t::color::Bg(t::color::Reset),
+        t::color::Fg(t::color::Reset)
+    )?;
+    out.flush()
+}
+
+// Show the start screen until the user opens a recent file or starts typing
+pub fn show<T, I>(events: &mut I, out: &mut T, recent: &[String], colors: &Colors) -> io::Result<Choice>
+    where T : Write
+        , I : Iterator<Item = io::Result<Event>>
+{
+    let mut selected = 0;
+    write!(out, "{}", t::cursor::Hide)?;
+    let choice = loop {
+        draw(out, recent, selected, colors)?;
+
+        let event = match events.next() {
+            Some(event) => event?,
+            None => break Choice::Dismiss(None)
+        };
+
+        // Recent files are numbered from 1
+        let number = match event {
+            Event::Key(Key::Char(c)) => c.to_digit(10).map(|d| d as usize),
+            _ => None
+        };
+        if let Some(n) = number.filter(|n| (1..=recent.len()).contains(n)) {
+            break Choice::Open(n - 1);
+        }
+
+        match event {
+            _ if recent.is_empty() => break Choice::Dismiss(Some(event)),
+            Event::Key(Key::Up) => selected = selected.saturating_sub(1),
+            Event::Key(Key::Down) => selected = min(selected + 1, recent.len() - 1),
+            Event::Key(Key::Char('\n')) => break Choice::Open(selected),
+            Event::Key(Key::Esc) => break Choice::Dismiss(None),
+            _ => break Choice::Dismiss(Some(event))
+        }
+    };
+
+    write!(out, "{}", t::cursor::Show)?;
+    Ok(choice)
+}
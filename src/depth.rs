@@ -0,0 +1,241 @@
+use crate::grid::{Cell, Color};
+use std::env;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// How many colors the terminal can show. Everything is drawn in 24-bit color and brought
+// down to this on its way out, see `terminal::Output`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Depth {
+    Mono, // No colors, only reverse video and bold
+    Ansi, // The 16 named colors
+    Palette, // The 256 color palette
+    True
+}
+
+// Global like the ambiguous width, since anything written to the terminal has to agree
+static DEPTH: AtomicU8 = AtomicU8::new(Depth::True as u8);
+
+// The 16 colors as xterm shows them by default
+const ANSI: [(u8, u8, u8); 16] = [
+    (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0),
+    (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+    (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+    (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255)
+];
+// Steps of each channel in the palette's 6x6x6 cube
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+// Set from `[display] colors`, which is `truecolor`, `256`, `16`, `none` or `auto` (the default)
+pub fn configure(setting: Option<&str>) -> Result<(), String> {
+    let depth = match setting.unwrap_or("auto") {
+        "truecolor" => Depth::True,
+        "256" => Depth::Palette,
+        "16" => Depth::Ansi,
+        "none" => Depth::Mono,
+        "auto" => detect(),
+        other => return Err(format!("Invalid colors '{}', expected truecolor, 256, 16, none or auto", other))
+    };
+    DEPTH.store(depth as u8, Ordering::Relaxed);
+    Ok(())
+}
+
+// Guess from the environment, `NO_COLOR` (when it's not empty) wins over anything else
+fn detect() -> Depth {
+    let var = |name| env::var(name).unwrap_or_default();
+    let term = var("TERM");
+    if !var("NO_COLOR").is_empty() || term == "dumb" {
+        Depth::Mono
+    } else if ["truecolor", "24bit"].contains(&var("COLORTERM").as_str()) || term.ends_with("direct") {
+        Depth::True
+    } else if term.contains("256color") {
+        Depth::Palette
+    } else {
+        Depth::Ansi
+    }
+}
+
+pub fn current() -> Depth {
+    match DEPTH.load(Ordering::Relaxed) {
+        0 => Depth::Mono,
+        1 => Depth::Ansi,
+        2 => Depth::Palette,
+        _ => Depth::True
+    }
+}
+
+fn rgb(color: Color) -> Option<(u8, u8, u8)> {
+    match color {
+        Color::Default => None,
+        Color::Indexed(n @ 0..=15) => Some(ANSI[n as usize]),
+        Color::Indexed(n @ 16..=231) => {
+            let n = n as usize - 16;
+            Some((CUBE[n / 36], CUBE[n / 6 % 6], CUBE[n % 6]))
+        },
+        Color::Indexed(n) => {
+            let grey = 8 + (n - 232) * 10;
+            Some((grey, grey, grey))
+        },
+        Color::Rgb(r, g, b) => Some((r, g, b))
+    }
+}
+
+fn distance((r, g, b): (u8, u8, u8), (s, h, c): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+    d(r, s) + d(g, h) + d(b, c)
+}
+
+// The closest palette color, from either the cube or the grey ramp
+fn palette(color: (u8, u8, u8)) -> u8 {
+    let step = |c: u8| (0..6).min_by_key(|&i| (CUBE[i] as i32 - c as i32).abs()).unwrap_or(0);
+    let (r, g, b) = (step(color.0), step(color.1), step(color.2));
+    let cube = 16 + 36 * r + 6 * g + b;
+
+    let average = (color.0 as u32 + color.1 as u32 + color.2 as u32) / 3;
+    let grey = 232 + (average.saturating_sub(3) / 10).min(23) as usize;
+
+    [cube as u8, grey as u8]
+        .into_iter()
+        .min_by_key(|&n| distance(color, rgb(Color::Indexed(n)).unwrap_or_default()))
+        .unwrap_or(cube as u8)
+}
+
+fn ansi(color: (u8, u8, u8)) -> u8 {
+    (0..16u8).min_by_key(|&n| distance(color, ANSI[n as usize])).unwrap_or(0)
+}
+
+// The nearest color `depth` can show, or none at all in monochrome
+pub fn reduce(color: Color, depth: Depth) -> Color {
+    match (depth, rgb(color)) {
+        (_, None) | (Depth::Mono, _) => Color::Default,
+        (Depth::True, _) => color,
+        (Depth::Palette, Some(c)) => match color {
+            Color::Rgb(..) => Color::Indexed(palette(c)),
+            _ => color
+        },
+        (Depth::Ansi, Some(c)) => match color {
+            Color::Indexed(0..=15) => color,
+            _ => Color::Indexed(ansi(c))
+        }
+    }
+}
+
+// How monochrome stands in for a background: light ones like the selection turn into
+// reverse video, middling ones like the status line into bold, dark ones are dropped
+fn stand_in(bg: Color) -> (bool, bool) {
+    match rgb(bg) {
+        Some((r, g, b)) => {
+            let luma = (2126 * r as u32 + 7152 * g as u32 + 722 * b as u32) / 10000;
+            ((64..128).contains(&luma), luma >= 128)
+        },
+        None => (false, false)
+    }
+}
+
+// `cell` as the terminal can show it
+pub fn reduce_cell(cell: &Cell, depth: Depth) -> Cell {
+    let (bold, reverse) = match depth {
+        Depth::Mono => stand_in(cell.bg),
+        _ => (false, false)
+    };
+    Cell {
+        symbol: cell.symbol.clone(),
+        fg: reduce(cell.fg, depth),
+        bg: reduce(cell.bg, depth),
+        bold: cell.bold || bold,
+        underline: cell.underline,
+        reverse: cell.reverse || reverse
+    }
+}
+
+// Parameters of the sequence that sets `color`, as the foreground or background
+pub fn sgr(color: Color, background: bool) -> String {
+    let base = if background { 40 } else { 30 };
+    match color {
+        Color::Default => format!("{}", base + 9),
+        Color::Indexed(n @ 0..=7) => format!("{}", base + n as u16),
+        Color::Indexed(n @ 8..=15) => format!("{}", base + 60 + n as u16 - 8),
+        Color::Indexed(n) => format!("{};5;{}", base + 8, n),
+        Color::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b)
+    }
+}
+
+// Bring the colors in `bytes` down to `depth`, for what's written to the terminal without
+// going through a frame, like pickers and prompts. Sequences split between writes are
+// passed on as they are
+pub fn rewrite(bytes: &[u8], depth: Depth) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        let end = match rest.starts_with(b"\x1b[") {
+            true => rest[2..].iter().position(|b| (0x40..=0x7e).contains(b)),
+            false => None
+        };
+        match end.filter(|&e| rest[2 + e] == b'm') {
+            Some(e) => {
+                let params = String::from_utf8_lossy(&rest[2..2 + e]);
+                let params = rewrite_style(&params, depth);
+                if !params.is_empty() {
+                    out.extend_from_slice(format!("\x1b[{}m", params).as_bytes());
+                }
+                i += e + 3;
+            },
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+// The parameters of a `m` sequence with its colors reduced
+fn rewrite_style(params: &str, depth: Depth) -> String {
+    let numbers: Vec<u16> = params.split(';').map(|n| n.parse().unwrap_or(0)).collect();
+    let mut kept = Vec::new();
+    let mut i = 0;
+    while i < numbers.len() {
+        let (color, background) = match numbers[i] {
+            n @ 30..=37 => (Color::Indexed(n as u8 - 30), false),
+            n @ 40..=47 => (Color::Indexed(n as u8 - 40), true),
+            n @ 90..=97 => (Color::Indexed(n as u8 - 82), false),
+            n @ 100..=107 => (Color::Indexed(n as u8 - 92), true),
+            39 => (Color::Default, false),
+            49 => (Color::Default, true),
+            n @ (38 | 48) => {
+                let color = match numbers.get(i + 1) {
+                    Some(5) => {
+                        i += 2;
+                        Color::Indexed(numbers.get(i).copied().unwrap_or(0) as u8)
+                    },
+                    Some(2) => {
+                        let channel = |j: usize| numbers.get(i + j).copied().unwrap_or(0) as u8;
+                        let color = Color::Rgb(channel(2), channel(3), channel(4));
+                        i += 4;
+                        color
+                    },
+                    _ => Color::Default
+                };
+                (color, n == 48)
+            },
+            n => {
+                kept.push(n.to_string());
+                i += 1;
+                continue;
+            }
+        };
+        i += 1;
+
+        match (depth, background) {
+            (Depth::Mono, false) => (),
+            (Depth::Mono, true) => match stand_in(color) {
+                (true, _) => kept.push(String::from("1")),
+                (_, true) => kept.push(String::from("7")),
+                _ if color == Color::Default => kept.push(String::from("22;27")),
+                _ => ()
+            },
+            _ => kept.push(sgr(reduce(color, depth), background))
+        }
+    }
+    kept.join(";")
+}
@@ -0,0 +1,165 @@
+use std::iter;
+use std::ops::Range;
+
+// Give up looking for a minimal diff after this many edits, and report the rest as one hunk
+const MAX_EDITS: usize = 2000;
+
+// A region where the sequences differ: the `old` lines were replaced by the `new` ones
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>
+}
+
+// Indices of the elements common to `a` and `b` in order, using Myers' algorithm
+fn common<T>(a: &[T], b: &[T]) -> Option<Vec<(usize, usize)>> where T : PartialEq {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (a.len() + b.len()).min(MAX_EDITS) as isize;
+
+    // `v[k + offset]` is the furthest x reached on diagonal k, a copy is kept for each step
+    let offset = max + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let at = |k: isize| (k + offset) as usize;
+
+    let mut found = false;
+    for d in 0..=max {
+        trace.push(v[at(-d)..=at(d)].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+                v[at(k + 1)]
+            } else {
+                v[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[at(k)] = x;
+
+            if x >= n && y >= m {
+                found = true;
+                break;
+            }
+        }
+        if found {
+            break;
+        }
+    }
+
+    if !found {
+        return None;
+    }
+
+    // Walk back through the steps, collecting the diagonal moves
+    let mut pairs = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().skip(1).rev() {
+        let d = d as isize;
+        let k = x - y;
+        let get = |k: isize| v[(k + d) as usize];
+        let previous = if k == -d || (k != d && get(k - 1) < get(k + 1)) { k + 1 } else { k - 1 };
+        let px = get(previous);
+        let py = px - previous;
+
+        while x > px && y > py {
+            x -= 1;
+            y -= 1;
+            pairs.push((x as usize, y as usize));
+        }
+        x = px;
+        y = py;
+    }
+    while x > 0 && y > 0 {
+        x -= 1;
+        y -= 1;
+        pairs.push((x as usize, y as usize));
+    }
+
+    pairs.reverse();
+    Some(pairs)
+}
+
+// The hunks that turn `a` into `b`
+pub fn diff<T>(a: &[T], b: &[T]) -> Vec<Hunk> where T : PartialEq {
+    // Only the middle, after any common prefix and suffix, needs diffing
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let a = &a[prefix..a.len() - suffix];
+    let b = &b[prefix..b.len() - suffix];
+
+    let pairs = common(a, b).unwrap_or_default();
+    let end = iter::once((a.len(), b.len()));
+
+    let mut hunks = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (x, y) in pairs.into_iter().chain(end) {
+        if x > i || y > j {
+            hunks.push(Hunk {
+                old: prefix + i..prefix + x,
+                new: prefix + j..prefix + y
+            });
+        }
+        i = x + 1;
+        j = y + 1;
+    }
+    hunks
+}
+
+fn unified_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len)
+    }
+}
+
+// Format the differences between `a` and `b` as a unified diff with `context` lines
+// around each change, or nothing if they're the same
+pub fn unified(a: &[&str], b: &[&str], old_name: &str, new_name: &str, context: usize) -> Vec<String> {
+    let hunks = diff(a, b);
+    if hunks.is_empty() {
+        return Vec::new();
+    }
+
+    let mut out = vec![format!("--- {}", old_name), format!("+++ {}", new_name)];
+
+    // Hunks close enough for their context to overlap are shown together
+    let mut groups: Vec<&[Hunk]> = Vec::new();
+    let mut first = 0;
+    for i in 1..=hunks.len() {
+        if i == hunks.len() || hunks[i].old.start - hunks[i - 1].old.end > 2 * context {
+            groups.push(&hunks[first..i]);
+            first = i;
+        }
+    }
+
+    for group in groups {
+        let (head, tail) = (&group[0], &group[group.len() - 1]);
+        let old_start = head.old.start.saturating_sub(context);
+        let old_end = (tail.old.end + context).min(a.len());
+        let new_start = head.new.start - (head.old.start - old_start);
+        let new_end = tail.new.end + (old_end - tail.old.end);
+
+        out.push(format!("@@ -{} +{} @@",
+            unified_range(old_start, old_end - old_start),
+            unified_range(new_start, new_end - new_start)
+        ));
+
+        let mut position = old_start;
+        for hunk in group {
+            out.extend(a[position..hunk.old.start].iter().map(|l| format!(" {}", l)));
+            out.extend(a[hunk.old.clone()].iter().map(|l| format!("-{}", l)));
+            out.extend(b[hunk.new.clone()].iter().map(|l| format!("+{}", l)));
+            position = hunk.old.end;
+        }
+        out.extend(a[position..old_end].iter().map(|l| format!(" {}", l)));
+    }
+    out
+}
@@ -0,0 +1,1352 @@
+use crate::compile::Compilation;
+use crate::Config;
+use crate::grid::Grid;
+use crate::panes::Panes;
+use crate::terminal::{self, Output};
+use crate::screen::{Message, Rect, Screen, StatusFields};
+use crate::{buffers, calc, clipboard, compare, elevate, export, format, picker, recent, tags, tabs, transform};
+use termion as t;
+use termion::event::{Event, Key};
+use std::cmp::min;
+use std::fs;
+use std::io::{self, ErrorKind, Write};
+use std::path::{self, Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// How many closed buffers are remembered for reopening
+const CLOSED_HISTORY: usize = 10;
+
+// How many positions are remembered for jumping back to
+const JUMP_HISTORY: usize = 100;
+
+// Used to insert the date unless the config file has `[date] format`
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+// A buffer that was closed, and where its cursor was
+struct Closed {
+    path: PathBuf,
+    row: usize,
+    column: usize
+}
+
+// A position jumped away from
+struct Jump {
+    path: PathBuf,
+    row: usize,
+    column: usize
+}
+
+// Parse a one-based `line[:column]` position into zero-based coordinates
+pub fn parse_position(s: &str) -> Option<(usize, usize)> {
+    let mut parts = s.trim().splitn(2, ':');
+    let line = parts.next()?.parse::<usize>().ok()?;
+    let column = match parts.next() {
+        Some(c) => c.parse::<usize>().ok()?,
+        None => 1
+    };
+    Some((line.saturating_sub(1), column.saturating_sub(1)))
+}
+
+// Run `command` with the shell in `directory` and return its output, minus the final newline
+pub fn run_shell(command: &str, directory: &Path) -> io::Result<String> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(directory)
+        .stdin(Stdio::null())
+        .output()?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = match stderr.lines().find(|l| !l.trim().is_empty()) {
+            Some(l) => String::from(l.trim()),
+            None => format!("Command failed ({})", output.status)
+        };
+        return Err(io::Error::other(reason));
+    }
+
+    let mut stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    if stdout.ends_with('\n') {
+        stdout.pop();
+        if stdout.ends_with('\r') {
+            stdout.pop();
+        }
+    }
+    Ok(stdout)
+}
+
+// The local date and time in a strftime-style `format`, as given by `date`
+pub fn format_date(format: &str) -> io::Result<String> {
+    let output = Command::new("date")
+        .arg(format!("+{}", format))
+        .stdin(Stdio::null())
+        .output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::other("Invalid date format"));
+    }
+    let date = String::from_utf8_lossy(&output.stdout);
+    Ok(String::from(date.trim_end_matches('\n')))
+}
+
+// Replace the selected arithmetic with its result, or prompt for an expression
+// and insert the result at the cursor
+pub fn calculate<T, I>(screen: &mut Screen, events: &mut I, out: &mut T) -> io::Result<()>
+    where T : Write
+        , I : Iterator<Item = io::Result<Event>>
+{
+    let expression = match screen.selected_text() {
+        Some(text) => text,
+        None => match screen.prompt(events, out, "Calculate:")? {
+            Some(e) if !e.trim().is_empty() => e,
+            _ => return Ok(())
+        }
+    };
+
+    match calc::evaluate(&expression) {
+        Ok(value) => screen.insert_str(&calc::format(value)),
+        Err(e) => screen.set_message(Message::Warning(e))
+    }
+    Ok(())
+}
+
+// Prompt for a position and move the cursor there
+pub fn goto_line<T, I>(screen: &mut Screen, events: &mut I, out: &mut T) -> io::Result<()>
+    where T : Write
+        , I : Iterator<Item = io::Result<Event>>
+{
+    if let Some(reply) = screen.prompt(events, out, "Go to line[:column]:")? {
+        match parse_position(&reply) {
+            Some((row, column)) => screen.goto(row, column),
+            None => screen.set_message(Message::Warning(String::from("Invalid position")))
+        }
+    }
+    Ok(())
+}
+
+// Prompt for how to sort the selected lines, then sort them
+pub fn sort_lines<T, I>(screen: &mut Screen, events: &mut I, out: &mut T) -> io::Result<()>
+    where T : Write
+        , I : Iterator<Item = io::Result<Event>>
+{
+    if screen.selected_text().is_none() {
+        screen.set_message(Message::Warning(String::from("Select the lines to sort")));
+        return Ok(());
+    }
+    let reply = match screen.prompt(events, out, "Sort with any of r(everse), n(umeric), u(nique):")? {
+        Some(r) => r,
+        None => return Ok(())
+    };
+
+    match reply.chars().find(|c| !"rnu ".contains(*c)) {
+        Some(c) => {
+            let m = format!("Unknown sort option '{}'", c);
+            screen.set_message(Message::Warning(m));
+        },
+        None => {
+            let (reverse, numeric, unique) = (reply.contains('r'), reply.contains('n'), reply.contains('u'));
+            screen.edit_lines(|lines| transform::sort_lines(lines, reverse, numeric, unique));
+        }
+    }
+    Ok(())
+}
+
+// Prompt for text and select its next match, an empty reply repeats the last search
+pub fn search<T, I>(screen: &mut Screen, events: &mut I, out: &mut T) -> io::Result<()>
+    where T : Write
+        , I : Iterator<Item = io::Result<Event>>
+{
+    let reply = match screen.prompt(events, out, "Search:")? {
+        Some(r) => r,
+        None => return Ok(())
+    };
+
+    if reply.is_empty() {
+        search_again(screen, true);
+    } else if !screen.search(&reply, true) {
+        let m = format!("No matches for '{}'", reply);
+        screen.set_message(Message::Warning(m));
+    }
+    Ok(())
+}
+
+// Select the next (or previous) match of the last search
+pub fn search_again(screen: &mut Screen, forward: bool) {
+    match screen.search_again(forward) {
+        Some(true) => (),
+        Some(false) => screen.set_message(Message::Warning(String::from("No matches"))),
+        None => screen.set_message(Message::Warning(String::from("No previous search")))
+    }
+}
+
+// Prompt for a JSON or XML style and reformat the selection (or buffer) with it,
+// defaulting to pretty-printing the buffer's own filetype
+pub fn reformat<T, I>(screen: &mut Screen, events: &mut I, out: &mut T) -> io::Result<()>
+    where T : Write
+        , I : Iterator<Item = io::Result<Event>>
+{
+    let prompt = "Format as (j)son, (x)ml, or minify as (J)son, (X)ml:";
+    let reply = match screen.prompt(events, out, prompt)? {
+        Some(r) => r,
+        None => return Ok(())
+    };
+    let choice = match reply.trim() {
+        "" => match screen.filetype() {
+            Some("json") => "j",
+            Some("xml") | Some("html") => "x",
+            _ => ""
+        },
+        r => r
+    };
+
+    let indent = screen.indent_unit();
+    let result = match choice {
+        "j" => screen.reformat(|s| format::json(s, Some(&indent))),
+        "x" => screen.reformat(|s| format::xml(s, Some(&indent))),
+        "J" => screen.reformat(|s| format::json(s, None)),
+        "X" => screen.reformat(|s| format::xml(s, None)),
+        _ => Err(String::from("Unknown format"))
+    };
+    if let Err(e) = result {
+        screen.set_message(Message::Warning(e));
+    }
+    Ok(())
+}
+
+// Prompt for a path and write the buffer there as highlighted HTML
+pub fn export_html<T, I>(screen: &mut Screen, events: &mut I, out: &mut T) -> io::Result<()>
+    where T : Write
+        , I : Iterator<Item = io::Result<Event>>
+{
+    let default = format!("{}.html", screen.display_name());
+    let path = match screen.prompt(events, out, &format!("Export HTML to ({}):", default))? {
+        Some(reply) if reply.trim().is_empty() => screen.resolve(&default),
+        Some(reply) => screen.resolve(reply.trim()),
+        None => return Ok(())
+    };
+    if path.exists() && !screen.confirm_prompt(events, out, "Overwrite (y/N)?", false)? {
+        return Ok(());
+    }
+    let line_numbers = screen.confirm_prompt(events, out, "Include line numbers (Y/n)?", true)?;
+
+    let html = export::html(&screen.lines(), screen.filetype(), screen.display_name(), line_numbers);
+    match fs::write(&path, html) {
+        Ok(()) => {
+            let m = format!("Exported to {}", path.display());
+            screen.set_message(Message::Info(m));
+        },
+        Err(e) => screen.set_message(Message::Error(e.to_string()))
+    }
+    Ok(())
+}
+
+// Write through `program` without a password if it allows that, otherwise
+// ask for one (only sudo can read it from a pipe)
+fn save_elevated<T, I>(screen: &mut Screen, events: &mut I, out: &mut T, path: &Path, program: &str)
+    -> io::Result<io::Result<usize>>
+    where T : Write
+        , I : Iterator<Item = io::Result<Event>>
+{
+    let result = screen.save_elevated(path, program, None);
+    if result.is_ok() || program != "sudo" {
+        return Ok(result);
+    }
+
+    match screen.secret_prompt(events, out, "[sudo] password:")? {
+        Some(password) => Ok(screen.save_elevated(path, program, Some(&password))),
+        None => Ok(result)
+    }
+}
+
+// What saving wrote, for the message after
+pub struct Saved {
+    pub bytes: usize,
+    pub stripped: usize // Lines trailing whitespace was removed from first
+}
+
+impl Saved {
+    pub fn message(&self) -> Message {
+        let mut m = format!("Wrote {} bytes", self.bytes);
+        if self.stripped > 0 {
+            m.push_str(&format!(", stripped trailing whitespace from {} lines", self.stripped));
+        }
+        Message::Info(m)
+    }
+}
+
+// Save the screen's buffer, prompting for a path if it has none (or `save_as` is set)
+// and for confirmation before overwriting. Returns the number of bytes written, or
+// `None` if the save was cancelled or failed, in which case the error is shown.
+pub fn save<T, I>(screen: &mut Screen, events: &mut I, out: &mut T, save_as: bool)
+    -> io::Result<Option<Saved>>
+    where T : Write
+        , I : Iterator<Item = io::Result<Event>>
+{
+    let needs_path = screen.path().as_os_str().is_empty() || save_as;
+    let mut path = PathBuf::from(screen.path());
+
+    if needs_path {
+        match screen.prompt(events, out, "Save as:")? {
+            Some(reply) => path = screen.resolve(&reply),
+            None => return Ok(None)
+        }
+    }
+
+    // Taken back unless the file is written, so a cancelled save changes nothing
+    let strip = screen.strips_trailing().then(|| screen.strip_for_save());
+    let result = write_screen(screen, events, out, &path, needs_path);
+    match result {
+        Ok(Some(bytes)) => Ok(Some(Saved { bytes, stripped: strip.map_or(0, |s| s.lines) })),
+        result => {
+            if let Some(strip) = strip {
+                screen.unstrip(strip);
+            }
+            result.map(|_| None)
+        }
+    }
+}
+
+// Write the screen's buffer to `path`, asking before overwriting or going through sudo
+fn write_screen<T, I>(screen: &mut Screen, events: &mut I, out: &mut T, path: &Path, needs_path: bool)
+    -> io::Result<Option<usize>>
+    where T : Write
+        , I : Iterator<Item = io::Result<Event>>
+{
+    let write = |screen: &mut Screen, overwrite: bool| {
+        if needs_path {
+            screen.save_as(path, overwrite)
+        } else {
+            screen.save(overwrite)
+        }
+    };
+
+    // Try normally first...
+    let result = match write(screen, false) {
+        Err(e) if matches!(e.kind(), ErrorKind::Other | ErrorKind::AlreadyExists) => {
+            // ...if it fails, ask user if they want to overwrite
+            if !screen.confirm_prompt(events, out, "Overwrite (y/N)?", false)? {
+                return Ok(None);
+            }
+            write(screen, true)
+        },
+        result => result
+    };
+
+    // Offer to write files the user can't through sudo or doas
+    let result = match result {
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => match elevate::program() {
+            Some(program) => {
+                let prompt = format!("Permission denied. Save with {} (y/N)?", program);
+                if !screen.confirm_prompt(events, out, &prompt, false)? {
+                    return Ok(None);
+                }
+                save_elevated(screen, events, out, path, program)?
+            },
+            None => Err(e)
+        },
+        result => result
+    };
+
+    match result {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(e) => {
+            // don't crash if we still can't save
+            screen.set_message(Message::Error(e.to_string()));
+            Ok(None)
+        }
+    }
+}
+
+// All open screens and which one is shown
+pub struct Editor {
+    pub screens: Vec<Screen>,
+    pub index: usize,
+    pub config: Config,
+    pub fields: StatusFields,
+    closed: Vec<Closed>,
+    recent: Vec<usize>, // Screen indices by access order, most recent last
+    compilation: Option<Compilation>,
+    compile_command: Option<String>, // The last command compiled
+    jumps: Vec<Jump>,
+    clipboard: Option<String>, // The text last copied or cut
+    system_clipboard: bool, // Copy to and paste from the system's clipboard too
+    osc: Option<String>, // A sequence for the terminal to be sent with the next frame
+    drawn: Option<usize>, // The screen in the last frame, switching redraws everything
+    size: Option<(u16, u16)>, // The terminal's size in the last frame
+    panes: Panes,
+    areas: Vec<Rect> // Where each pane was last drawn
+}
+
+impl Editor {
+    pub fn new(config: Config) -> Self {
+        let mut screens: Vec<Screen> = config.paths
+            .iter()
+            .map(|(p, options)| Screen::open(p, options, &config))
+            .collect();
+
+        if screens.is_empty() {
+            screens.push(Screen::new("", &config));
+        }
+        let system_clipboard = config.file.boolean("clipboard", "system").unwrap_or(true);
+
+        let editor = Editor {
+            screens,
+            index: 0,
+            config,
+            fields: StatusFields::default(),
+            closed: Vec::new(),
+            recent: vec![0],
+            compilation: None,
+            compile_command: None,
+            jumps: Vec::new(),
+            clipboard: None,
+            system_clipboard,
+            osc: None,
+            drawn: None,
+            size: None,
+            panes: Panes::new(0),
+            areas: Vec::new()
+        };
+        for i in 0..editor.screens.len() {
+            editor.remember(i);
+        }
+        editor
+    }
+
+    // Add the file of screen `index` to the recent files shown at startup
+    fn remember(&self, index: usize) {
+        let screen = &self.screens[index];
+        if screen.path().as_os_str().is_empty() {
+            return;
+        }
+        let location = screen.location();
+        let location = if screen.is_remote() {
+            location
+        } else {
+            location.canonicalize().or_else(|_| path::absolute(&location)).unwrap_or(location)
+        };
+        recent::record(&location.to_string_lossy());
+    }
+
+    pub fn current(&mut self) -> &mut Screen {
+        &mut self.screens[self.index]
+    }
+
+    pub fn switch(&mut self, index: usize) {
+        assert!(index < self.screens.len(), "screen index out-of-range");
+        self.index = index;
+        self.panes.focused().screen = index;
+        self.recent.retain(|&i| i != index);
+        self.recent.push(index);
+    }
+
+    // Switch back to the previously used screen, returns `false` if there is none
+    pub fn switch_recent(&mut self) -> bool {
+        match self.recent.iter().rev().find(|&&i| i != self.index) {
+            Some(&i) => {
+                self.switch(i);
+                true
+            },
+            None => false
+        }
+    }
+
+    // Open a new screen for `path` (empty for a new buffer) and switch to it
+    pub fn open(&mut self, path: &str) {
+        self.screens.push(Screen::new(path, &self.config));
+        self.switch(self.screens.len() - 1);
+        self.remember(self.index);
+    }
+
+    // Open `path` in place of the current screen, like the empty one the editor starts with
+    pub fn open_instead(&mut self, path: &str) {
+        self.screens[self.index] = Screen::new(path, &self.config);
+        self.remember(self.index);
+    }
+
+    // Where the panes go, below the tab bar if it's shown
+    fn area(&self) -> io::Result<Rect> {
+        let (width, height) = terminal::size()?;
+        let top = if self.has_tabs() { 1 } else { 0 };
+        Ok(Rect { x: 0, y: top, width, height: height - top })
+    }
+
+    // Draw the tab bar (if there's more than one buffer) and each pane, the focused one
+    // showing the current screen
+    pub fn draw<T>(&mut self, out: &mut Output<T>) -> io::Result<()> where T : Write {
+        let (width, height) = terminal::size()?;
+        self.size = Some((width, height));
+        let mut grid = Grid::new(width, height);
+        if self.has_tabs() {
+            tabs::draw(&mut grid, &self.screens, self.index, width, &self.config.colors)?;
+        }
+
+        let fields = self.fields;
+        let (areas, separators) = self.panes.areas(self.area()?);
+        let focus = self.panes.focus;
+        for (i, &area) in areas.iter().enumerate().filter(|&(i, _)| i != focus) {
+            // Show the pane's own view of the screen, leaving the screen as it was
+            let pane = self.panes.get_mut(i);
+            let screen = &mut self.screens[pane.screen];
+            let current = screen.view();
+            if let Some(view) = pane.view.take() {
+                screen.set_view(view);
+            }
+            screen.resize(area);
+            screen.draw(&mut grid, &fields)?;
+            pane.view = Some(screen.view());
+            screen.set_view(current);
+        }
+        for separator in separators {
+            for y in separator.y..separator.y + separator.height {
+                write!(grid, "{}{}\u{2502}", t::cursor::Goto(separator.x + 1, y + 1), t::color::Fg(self.config.colors.separator_fg))?;
+            }
+            write!(grid, "{}", t::color::Fg(t::color::Reset))?;
+        }
+
+        // Last, so the terminal's cursor ends up in it
+        let screen = self.current();
+        screen.resize(areas[focus]);
+        screen.draw(&mut grid, &fields)?;
+        self.areas = areas;
+
+        let full = self.drawn != Some(self.index);
+        self.drawn = Some(self.index);
+        out.frame(grid, full)?;
+        if let Some(sequence) = self.osc.take() {
+            out.send(&sequence)?;
+        }
+        Ok(())
+    }
+
+    // Only show the tab bar when there's more than one buffer
+    pub fn has_tabs(&self) -> bool {
+        self.screens.len() > 1
+    }
+
+    // Remove the screen at `target`, keeping at least one open
+    fn remove(&mut self, target: usize) {
+        let screen = self.screens.remove(target);
+        if !screen.path().as_os_str().is_empty() {
+            let (row, column) = screen.cursor_position();
+            let path = screen.location();
+            self.closed.retain(|c| c.path != path);
+            self.closed.push(Closed { path, row, column });
+            if self.closed.len() > CLOSED_HISTORY {
+                self.closed.remove(0);
+            }
+        }
+
+        if self.screens.is_empty() {
+            self.screens.push(Screen::new("", &self.config));
+        }
+
+        self.recent.retain(|&i| i != target);
+        for i in self.recent.iter_mut() {
+            if *i > target { *i -= 1; }
+        }
+
+        if let Some(c) = self.compilation.as_mut() {
+            c.screen = match c.screen {
+                Some(i) if i == target => None,
+                Some(i) if i > target => Some(i - 1),
+                s => s
+            };
+        }
+
+        if target < self.index {
+            self.index -= 1;
+        } else {
+            self.index = min(self.screens.len() - 1, self.index);
+        }
+        self.panes.remove_screen(target, self.index);
+        self.switch(self.index);
+    }
+
+    // Remember the current position so it can be jumped back to
+    fn push_jump(&mut self) {
+        let screen = &self.screens[self.index];
+        if screen.path().as_os_str().is_empty() {
+            return;
+        }
+
+        let (row, column) = screen.cursor_position();
+        self.jumps.push(Jump { path: screen.path().to_path_buf(), row, column });
+        if self.jumps.len() > JUMP_HISTORY {
+            self.jumps.remove(0);
+        }
+    }
+
+    // Return to the position before the last jump, returns `false` if there is none
+    pub fn jump_back(&mut self) -> bool {
+        match self.jumps.pop() {
+            Some(jump) => {
+                self.visit(&jump.path);
+                self.current().goto(jump.row, jump.column);
+                true
+            },
+            None => false
+        }
+    }
+
+    // Jump to the definition of the identifier under the cursor using the nearest tags
+    // file, letting the user choose if there's more than one
+    pub fn goto_definition<T, I>(&mut self, events: &mut I, out: &mut T) -> io::Result<()>
+        where T : Write
+            , I : Iterator<Item = io::Result<Event>>
+    {
+        let colors = self.config.colors;
+        let screen = self.current();
+        let name = match screen.word_at_cursor() {
+            Some(w) => String::from(w),
+            None => {
+                screen.set_message(Message::Warning(String::from("No identifier under cursor")));
+                return Ok(());
+            }
+        };
+        let file = match tags::find_file(screen.directory()) {
+            Some(f) => f,
+            None => {
+                screen.set_message(Message::Warning(String::from("No tags file found")));
+                return Ok(());
+            }
+        };
+
+        let found = match tags::lookup(&file, &name) {
+            Ok(t) => t,
+            Err(e) => {
+                screen.set_message(Message::Error(e.to_string()));
+                return Ok(());
+            }
+        };
+        let tag = match found.len() {
+            0 => {
+                screen.set_message(Message::Warning(format!("No definition of '{}'", name)));
+                return Ok(());
+            },
+            1 => &found[0],
+            _ => {
+                let items: Vec<String> = found.iter().map(|t| t.describe()).collect();
+                let title = format!("Definitions of '{}'", name);
+                match picker::pick(events, out, &title, &items, &colors)? {
+                    Some(i) => &found[i],
+                    None => return Ok(())
+                }
+            }
+        };
+
+        self.push_jump();
+        self.visit(&tag.path);
+        let screen = self.current();
+        let lines = screen.lines();
+        match tag.address.locate(&lines) {
+            Some(row) => {
+                let byte = lines[row].find(&tag.name).unwrap_or(0);
+                screen.goto_byte(row, byte);
+            },
+            None => {
+                let m = format!("Definition of '{}' not found, tags file may be out of date", name);
+                screen.set_message(Message::Warning(m));
+            }
+        }
+        Ok(())
+    }
+
+    // Copy or cut the selection, saying how much was taken since the selection goes away
+    pub fn copy(&mut self, cut: bool) {
+        let screen = &mut self.screens[self.index];
+        let text = if cut { screen.cut() } else { screen.selected_text() };
+
+        match text {
+            Some(text) => {
+                let lines = text.lines().count().max(1);
+                let chars = text.chars().count();
+                let m = format!("{} {} line{} ({} char{})",
+                    if cut { "Cut" } else { "Copied" },
+                    lines,
+                    if lines == 1 { "" } else { "s" },
+                    chars,
+                    if chars == 1 { "" } else { "s" }
+                );
+                screen.deselect();
+                screen.set_message(Message::Info(m));
+
+                // Without a clipboard tool, ask the terminal to take it
+                if self.system_clipboard && !clipboard::copy(&text) {
+                    self.osc = Some(clipboard::osc52(&text));
+                }
+                self.clipboard = Some(text);
+            },
+            None if screen.selected_text().is_some() => (), // The buffer refused the cut and said why
+            None => screen.set_message(Message::Warning(String::from("Nothing selected")))
+        }
+    }
+
+    // Paste from the system clipboard, or what was last copied here if it can't be read.
+    // The text is shifted to the indentation at the cursor if `reindent` is set
+    pub fn paste(&mut self, reindent: bool) {
+        let system = if self.system_clipboard { clipboard::paste() } else { None };
+        let text = system.filter(|t| !t.is_empty()).or_else(|| self.clipboard.clone());
+        let screen = &mut self.screens[self.index];
+        match &text {
+            Some(text) if reindent => screen.insert_indented(text),
+            Some(text) => screen.insert_str(text),
+            None => screen.set_message(Message::Warning(String::from("Nothing to paste")))
+        }
+    }
+
+    // Show how the current buffer differs from its file on disk in a new scratch buffer
+    pub fn disk_diff(&mut self) {
+        let screen = self.current();
+        if screen.path().as_os_str().is_empty() {
+            screen.set_message(Message::Warning(String::from("Buffer has no file to compare with")));
+            return;
+        }
+
+        match screen.disk_diff() {
+            Ok(lines) if lines.is_empty() => {
+                screen.set_message(Message::Info(String::from("No changes since last save")));
+            },
+            Ok(lines) => {
+                let directory = screen.directory().to_path_buf();
+                let diff = Screen::scratch("*diff*", &lines.join("\n"), &directory, &self.config);
+                self.screens.push(diff);
+                self.switch(self.screens.len() - 1);
+            },
+            Err(e) => screen.set_message(Message::Error(e.to_string()))
+        }
+    }
+
+    // Insert the current date and time, prompting for the format
+    pub fn insert_date<T, I>(&mut self, events: &mut I, out: &mut T) -> io::Result<()>
+        where T : Write
+            , I : Iterator<Item = io::Result<Event>>
+    {
+        let default = String::from(self.config.file.string("date", "format").unwrap_or(DATE_FORMAT));
+        let screen = self.current();
+        let format = match screen.prompt(events, out, &format!("Date format ({}):", default))? {
+            Some(f) if f.is_empty() => default,
+            Some(f) => f,
+            None => return Ok(())
+        };
+
+        match format_date(&format) {
+            Ok(date) => screen.insert_str(&date),
+            Err(e) => screen.set_message(Message::Error(e.to_string()))
+        }
+        Ok(())
+    }
+
+    // Prompt for a shell command and insert its output as a single paste at the cursor,
+    // or into a new scratch buffer if the current one is read-only
+    pub fn shell_command<T, I>(&mut self, events: &mut I, out: &mut T) -> io::Result<()>
+        where T : Write
+            , I : Iterator<Item = io::Result<Event>>
+    {
+        let screen = self.current();
+        let command = match screen.prompt(events, out, "Shell command:")? {
+            Some(c) if !c.trim().is_empty() => c,
+            _ => return Ok(())
+        };
+
+        let output = match run_shell(&command, screen.directory()) {
+            Ok(o) => o,
+            Err(e) => {
+                screen.set_message(Message::Error(e.to_string()));
+                return Ok(());
+            }
+        };
+
+        if output.is_empty() {
+            screen.set_message(Message::Info(String::from("Command produced no output")));
+            return Ok(());
+        }
+
+        if screen.is_readonly() {
+            self.open("");
+        }
+        self.current().insert_str(&output);
+        Ok(())
+    }
+
+    // Switch to the screen editing `path`, opening it if there isn't one
+    pub fn visit(&mut self, path: &Path) {
+        let target = path.canonicalize().unwrap_or(path.to_path_buf());
+        let found = self.screens
+            .iter()
+            .position(|s| s.path().canonicalize().is_ok_and(|p| p == target));
+
+        match found {
+            Some(i) => self.switch(i),
+            None => self.open(&path.to_string_lossy())
+        }
+    }
+
+    // Prompt for a build command and run it in the background, its output is shown
+    // in a read-only buffer which replaces that of any previous compilation
+    pub fn compile<T, I>(&mut self, events: &mut I, out: &mut T) -> io::Result<()>
+        where T : Write
+            , I : Iterator<Item = io::Result<Event>>
+    {
+        let default = self.compile_command
+            .clone()
+            .or(self.config.file.string("compile", "command").map(String::from))
+            .unwrap_or(String::from("make"));
+
+        let screen = self.current();
+        let prompt = format!("Compile command ({}):", default);
+        let command = match screen.prompt(events, out, &prompt)? {
+            Some(c) if c.trim().is_empty() => default,
+            Some(c) => String::from(c.trim()),
+            None => return Ok(())
+        };
+        let directory = screen.directory().to_path_buf();
+        self.compile_command = Some(command.clone());
+
+        // Stops the previous compilation if it's still running
+        let previous = self.compilation.take().and_then(|c| c.screen);
+        let output = Screen::scratch("*compilation*", &format!("$ {}", command), &directory, &self.config);
+        let index = match previous {
+            Some(i) => {
+                self.screens[i] = output;
+                i
+            },
+            None => {
+                self.screens.push(output);
+                self.screens.len() - 1
+            }
+        };
+        self.switch(index);
+
+        match Compilation::start(&command, &directory, index) {
+            Ok(c) => self.compilation = Some(c),
+            Err(e) => self.current().set_message(Message::Error(e.to_string()))
+        }
+        Ok(())
+    }
+
+    // Jump to the location of the next error in the compilation output
+    pub fn next_error(&mut self) {
+        let compilation = self.compilation.as_mut();
+        let (number, location) = match compilation.and_then(|c| Some((c.screen, c.next_error()?))) {
+            Some((screen, (number, line, location))) => {
+                // The output starts with a line showing the command
+                if let Some(i) = screen {
+                    self.screens[i].goto(line + 1, 0);
+                }
+                (number, location)
+            },
+            None => {
+                let m = String::from("No errors to visit");
+                self.current().set_message(Message::Warning(m));
+                return;
+            }
+        };
+        let count = self.compilation.as_ref().map_or(0, |c| c.error_count());
+
+        self.push_jump();
+        self.visit(&location.path);
+        let screen = self.current();
+        screen.goto(location.row, location.column);
+        screen.set_message(Message::Info(format!("Location {} of {}", number, count)));
+    }
+
+    // Check on background jobs and whether the terminal was resized, returns `true` if
+    // anything needs redrawing
+    pub fn poll(&mut self) -> bool {
+        let mut redraw = terminal::size().is_ok_and(|size| Some(size) != self.size);
+        for (i, screen) in self.screens.iter_mut().enumerate() {
+            let expired = screen.tick();
+            redraw |= (screen.poll() || expired) && i == self.index;
+        }
+        self.poll_compilation() || redraw
+    }
+
+    // Collect output from the compilation, returns `true` if there was any
+    fn poll_compilation(&mut self) -> bool {
+        let compilation = match self.compilation.as_mut() {
+            Some(c) => c,
+            None => return false
+        };
+
+        let running = !compilation.is_finished();
+        let lines = compilation.read();
+        if lines.is_empty() {
+            return false;
+        }
+
+        if let Some(i) = compilation.screen {
+            for line in lines.iter() {
+                self.screens[i].append_line(line);
+            }
+        }
+
+        if running && compilation.is_finished() {
+            let m = match compilation.error_count() {
+                0 => lines[lines.len() - 1].clone(),
+                n => format!("{}, {} locations found (C-x ` to visit)", lines[lines.len() - 1], n)
+            };
+            self.current().set_message(Message::Info(m));
+        }
+        true
+    }
+
+    // Reopen the most recently closed buffer, returns `false` if there is none
+    pub fn reopen(&mut self) -> bool {
+        match self.closed.pop() {
+            Some(Closed { path, row, column }) => {
+                self.open(&path.to_string_lossy());
+                self.current().goto(row, column);
+                true
+            },
+            None => false
+        }
+    }
+
+    // Show a list of all buffers where Enter switches to the selected one (closing any
+    // marked with `d` first), `s` saves it, and Esc leaves without closing anything
+    pub fn buffer_list<T, I>(&mut self, events: &mut I, out: &mut T) -> io::Result<()>
+        where T : Write
+            , I : Iterator<Item = io::Result<Event>>
+    {
+        const HELP: &str = "Enter: switch, d: mark for closing, s: save, Esc: cancel";
+        let mut selected = self.index;
+        let mut marked = vec![false; self.screens.len()];
+
+        write!(out, "{}", termion::cursor::Hide)?;
+        loop {
+            buffers::draw(out, &self.screens, selected, &marked, HELP, &self.config.colors)?;
+
+            let event = match events.next() {
+                Some(event) => event?,
+                None => break
+            };
+
+            match event {
+                Event::Key(Key::Up) => selected = selected.saturating_sub(1),
+                Event::Key(Key::Down) => selected = min(selected + 1, self.screens.len() - 1),
+                Event::Key(Key::Char('d')) => marked[selected] = !marked[selected],
+                Event::Key(Key::Char('s')) => {
+                    write!(out, "{}", termion::cursor::Show)?;
+                    let screen = &mut self.screens[selected];
+                    if let Some(saved) = save(screen, events, out, false)? {
+                        screen.set_message(saved.message());
+                    }
+                    write!(out, "{}", termion::cursor::Hide)?;
+                },
+                Event::Key(Key::Char('\n')) => {
+                    write!(out, "{}", termion::cursor::Show)?;
+                    self.switch(selected);
+
+                    // Close marked buffers from the back so indices stay valid
+                    for i in (0..marked.len()).rev().filter(|&i| marked[i]) {
+                        self.close(i, events, out)?;
+                    }
+                    return Ok(());
+                },
+                Event::Key(Key::Esc) | Event::Key(Key::Char('q')) => break,
+                _ => ()
+            }
+        }
+
+        write!(out, "{}", termion::cursor::Show)?;
+        Ok(())
+    }
+
+    // Find a screen whose display or file name starts with `name`
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.screens
+            .iter()
+            .position(|s| {
+                let file_name = s.path()
+                    .file_name()
+                    .and_then(|o| o.to_str());
+                s.display_name().starts_with(name) ||
+                    file_name.is_some_and(|n| n.starts_with(name))
+            })
+    }
+
+    // Prompt for another buffer and show it side by side with the current one, with
+    // changed lines highlighted
+    pub fn compare<T, I>(&mut self, events: &mut I, out: &mut T) -> io::Result<()>
+        where T : Write
+            , I : Iterator<Item = io::Result<Event>>
+    {
+        const HELP: &str = "n/p: next/previous change, Up/Down/PgUp/PgDn: scroll, Esc: close";
+
+        let screen = self.current();
+        let other = match screen.prompt(events, out, "Compare with buffer:")? {
+            Some(reply) => match self.find(&reply) {
+                Some(i) => i,
+                None => {
+                    let m = format!("Buffer '{reply}' not found");
+                    self.current().set_message(Message::Warning(m));
+                    return Ok(());
+                }
+            },
+            None => return Ok(())
+        };
+
+        let (left, right) = (&self.screens[self.index], &self.screens[other]);
+        let names = (left.display_name(), right.display_name());
+        let (left, right) = (left.lines(), right.lines());
+        let rows = compare::align(&left, &right);
+        let changes = compare::change_starts(&rows);
+
+        if changes.is_empty() {
+            let m = format!("{} and {} are identical", names.0, names.1);
+            self.current().set_message(Message::Info(m));
+            return Ok(());
+        }
+
+        let mut top = changes[0].saturating_sub(3);
+        write!(out, "{}", termion::cursor::Hide)?;
+        loop {
+            let (_, height) = terminal::size()?;
+            let page = height as usize - 2;
+            let last = rows.len().saturating_sub(page);
+            compare::draw(out, names, (&left, &right), &rows, top, HELP, &self.config.colors)?;
+
+            let event = match events.next() {
+                Some(event) => event?,
+                None => break
+            };
+
+            match event {
+                Event::Key(Key::Up) => top = top.saturating_sub(1),
+                Event::Key(Key::Down) => top = min(top + 1, last),
+                Event::Key(Key::PageUp) => top = top.saturating_sub(page),
+                Event::Key(Key::PageDown) => top = min(top + page, last),
+                Event::Key(Key::Char('n')) => {
+                    if let Some(&c) = changes.iter().find(|&&c| c > top + 3) {
+                        top = min(c - 3, last);
+                    }
+                },
+                Event::Key(Key::Char('p')) => {
+                    if let Some(&c) = changes.iter().rev().find(|&&c| c + 3 < top) {
+                        top = c.saturating_sub(3);
+                    }
+                },
+                Event::Key(Key::Esc) | Event::Key(Key::Char('q')) => break,
+                _ => ()
+            }
+        }
+
+        write!(out, "{}", termion::cursor::Show)?;
+        Ok(())
+    }
+
+    // Discard the screen at `target` without saving, asking first if it's dirty
+    pub fn kill<T, I>(&mut self, target: usize, events: &mut I, out: &mut T) -> io::Result<()>
+        where T : Write
+            , I : Iterator<Item = io::Result<Event>>
+    {
+        let screen = &self.screens[target];
+        if screen.is_dirty() {
+            let prompt = format!("Discard changes to {} (y/N)?", screen.display_name());
+            if !screen.confirm_prompt(events, out, &prompt, false)? {
+                return Ok(());
+            }
+        }
+
+        self.remove(target);
+        Ok(())
+    }
+
+    // Close the screen at `target`, offering to save it first if it's dirty
+    pub fn close<T, I>(&mut self, target: usize, events: &mut I, out: &mut T) -> io::Result<()>
+        where T : Write
+            , I : Iterator<Item = io::Result<Event>>
+    {
+        let screen = &mut self.screens[target];
+        let mut saved = None;
+
+        if screen.is_dirty() && screen.confirm_prompt(events, out, "Save changes (Y/n)", true)? {
+            saved = save(screen, events, out, false)?;
+            if saved.is_none() {
+                return Ok(());
+            }
+        }
+
+        self.remove(target);
+        if let Some(saved) = saved {
+            self.current().set_message(saved.message());
+        }
+        Ok(())
+    }
+
+    // Search the current screen as the pattern is typed, with Up/Down (or C-r/C-s) moving
+    // between matches. Enter stays on the match, Esc goes back to where the search started
+    pub fn incremental_search<T, I>(&mut self, events: &mut I, out: &mut Output<T>) -> io::Result<()>
+        where T : Write
+            , I : Iterator<Item = io::Result<Event>>
+    {
+        let mut pattern = String::new();
+        self.current().begin_incremental();
+
+        let accept = loop {
+            self.draw(out)?;
+            let event = match events.next() {
+                Some(event) => event?,
+                None => break false
+            };
+
+            let screen = self.current();
+            match event {
+                Event::Key(Key::Char('\n')) => break true,
+                Event::Key(Key::Esc) => break false,
+                Event::Key(Key::Char(c)) => {
+                    pattern.push(c);
+                    screen.update_incremental(&pattern);
+                },
+                Event::Key(Key::Backspace) => {
+                    pattern.pop();
+                    screen.update_incremental(&pattern);
+                },
+                Event::Key(Key::Down) | Event::Key(Key::Ctrl('s')) => screen.step_incremental(true),
+                Event::Key(Key::Up) | Event::Key(Key::Ctrl('r')) => screen.step_incremental(false),
+                _ => ()
+            }
+        };
+
+        let screen = self.current();
+        if let Some(pattern) = screen.end_incremental(accept) {
+            let m = format!("No matches for '{}'", pattern);
+            screen.set_message(Message::Warning(m));
+        }
+        Ok(())
+    }
+
+    // Close every screen but the current one, offering to save each dirty one first
+    pub fn close_others<T, I>(&mut self, events: &mut I, out: &mut Output<T>) -> io::Result<()>
+        where T : Write
+            , I : Iterator<Item = io::Result<Event>>
+    {
+        for i in (0..self.screens.len()).rev() {
+            let current = self.index;
+            if i == current {
+                continue;
+            }
+
+            if self.screens[i].is_dirty() {
+                // Show the buffer while asking about it
+                self.index = i;
+                self.draw(out)?;
+                self.index = current;
+            }
+            self.close(i, events, out)?;
+        }
+        Ok(())
+    }
+
+    // Offer to read the current buffer's file again if another program changed it,
+    // returns `false` if it hadn't changed
+    pub fn confirm_reload<T, I>(&mut self, events: &mut I, out: &mut Output<T>) -> io::Result<bool>
+        where T : Write
+            , I : Iterator<Item = io::Result<Event>>
+    {
+        let screen = self.current();
+        let modified = match screen.changed_on_disk() {
+            Some(m) => m,
+            None => return Ok(false)
+        };
+
+        let dirty = screen.is_dirty();
+        let prompt = if dirty {
+            format!("{} changed on disk, reload and lose your changes? (y/N)", screen.display_name())
+        } else {
+            format!("{} changed on disk, reload it? (Y/n)", screen.display_name())
+        };
+        if screen.confirm_prompt(events, out, &prompt, !dirty)? {
+            match screen.reload() {
+                Ok(()) => {
+                    let message = screen.endings_warning()
+                        .unwrap_or_else(|| Message::Info(String::from("Reloaded from disk")));
+                    screen.set_message(message);
+                },
+                Err(e) => screen.set_message(Message::Error(e.to_string()))
+            }
+        } else {
+            screen.keep_changes(modified);
+        }
+        Ok(true)
+    }
+
+    // Offer to save any dirty buffers before quitting, returns `false` if quitting was
+    // cancelled. If saving a buffer fails, it's switched to so the error is shown.
+    pub fn confirm_quit<T, I>(&mut self, events: &mut I, out: &mut Output<T>) -> io::Result<bool>
+        where T : Write
+            , I : Iterator<Item = io::Result<Event>>
+    {
+        let dirty: Vec<usize> = (0..self.screens.len())
+            .filter(|&i| self.screens[i].is_dirty())
+            .collect();
+
+        if dirty.is_empty() {
+            return Ok(true);
+        }
+
+        let names = dirty.iter()
+            .map(|&i| self.screens[i].display_name())
+            .collect::<Vec<&str>>()
+            .join(", ");
+        let prompt = format!(
+            "Unsaved changes in {}. (s)ave each, save (a)ll, (d)iscard all, (c)ancel:",
+            names
+        );
+        let reply = self.current().prompt(events, out, &prompt)?;
+        let each = match reply.and_then(|r| r.chars().next()) {
+            Some('s') => true,
+            Some('a') => false,
+            Some('d') => return Ok(true),
+            _ => return Ok(false)
+        };
+
+        let previous = self.index;
+        for i in dirty {
+            // Show each buffer while asking about it
+            self.index = i;
+            self.draw(out)?;
+
+            let screen = self.current();
+            let prompt = format!("Save changes to {} (Y/n)", screen.display_name());
+            let should_save = !each || screen.confirm_prompt(events, out, &prompt, true)?;
+            if should_save && save(screen, events, out, false)?.is_none() {
+                self.switch(i);
+                return Ok(false);
+            }
+        }
+
+        self.index = previous;
+        Ok(true)
+    }
+
+    // Walk every dirty buffer offering to save, discard or skip it before quitting. Returns
+    // `true` if it's safe to exit, otherwise only the skipped buffers (and any that failed
+    // to save) are left open.
+    pub fn quit_each<T, I>(&mut self, events: &mut I, out: &mut Output<T>) -> io::Result<bool>
+        where T : Write
+            , I : Iterator<Item = io::Result<Event>>
+    {
+        let previous = self.index;
+        let mut keep = vec![false; self.screens.len()];
+
+        for (i, kept) in keep.iter_mut().enumerate() {
+            if !self.screens[i].is_dirty() {
+                continue;
+            }
+
+            self.index = i;
+            self.draw(out)?;
+
+            let screen = self.current();
+            let prompt = format!(
+                "Unsaved changes in {}. (s)ave, (d)iscard, s(k)ip, (c)ancel:",
+                screen.display_name()
+            );
+            let reply = screen.prompt(events, out, &prompt)?;
+
+            match reply.and_then(|r| r.chars().next()) {
+                Some('s') => *kept = save(screen, events, out, false)?.is_none(),
+                Some('d') => (),
+                Some('k') => *kept = true,
+                _ => {
+                    self.index = previous;
+                    return Ok(false);
+                }
+            }
+        }
+
+        if !keep.contains(&true) {
+            return Ok(true);
+        }
+
+        let mut i = 0;
+        self.screens.retain(|_| { i += 1; keep[i - 1] });
+        // The compilation's screen moves down by however many before it were dropped
+        if let Some(c) = self.compilation.as_mut() {
+            c.screen = c.screen.filter(|&s| keep[s]).map(|s| keep[..s].iter().filter(|&&k| k).count());
+        }
+        self.recent.clear();
+        self.panes = Panes::new(0);
+        self.switch(0);
+        Ok(false)
+    }
+
+    // Split the focused pane in two showing the current screen, side by side if `vertical`
+    pub fn split(&mut self, vertical: bool) {
+        let area = match self.areas.get(self.panes.focus) {
+            Some(&area) => area,
+            None => return
+        };
+        if self.panes.split(area, vertical) {
+            // The new pane starts where the focused one is
+            let view = self.current().view();
+            self.panes.get_mut(self.panes.focus + 1).view = Some(view);
+            self.drawn = None;
+        } else {
+            let m = String::from("Pane is too small to split");
+            self.current().set_message(Message::Warning(m));
+        }
+    }
+
+    // Give focus to pane `index`, switching to the screen it shows
+    pub fn focus(&mut self, index: usize) {
+        if index == self.panes.focus {
+            return;
+        }
+        let view = self.current().view();
+        self.panes.focused().view = Some(view);
+
+        self.panes.focus = index;
+        let pane = self.panes.focused();
+        let (screen, view) = (pane.screen, pane.view.take());
+        self.switch(screen);
+        if let Some(view) = view {
+            self.current().set_view(view);
+        }
+        if let Some(&area) = self.areas.get(index) {
+            self.current().resize(area);
+        }
+        self.drawn = None;
+    }
+
+    pub fn next_pane(&mut self) {
+        if self.panes.count() == 1 {
+            let m = String::from("No other pane");
+            self.current().set_message(Message::Warning(m));
+        } else {
+            self.focus((self.panes.focus + 1) % self.panes.count());
+        }
+    }
+
+    // Close the focused pane, its screen stays open
+    pub fn close_pane(&mut self) {
+        if !self.panes.close() {
+            let m = String::from("Can't close the only pane");
+            self.current().set_message(Message::Warning(m));
+            return;
+        }
+        // Focus moves to the pane that took its place, as it was left
+        let pane = self.panes.focused();
+        let (screen, view) = (pane.screen, pane.view.take());
+        self.switch(screen);
+        if let Some(view) = view {
+            self.current().set_view(view);
+        }
+        self.drawn = None;
+    }
+
+    // Close every pane but the focused one
+    pub fn only_pane(&mut self) {
+        self.panes.only();
+        self.drawn = None;
+    }
+
+    // The pane at terminal cell `x`, `y` as last drawn
+    pub fn pane_at(&self, x: u16, y: u16) -> Option<usize> {
+        self.areas
+            .iter()
+            .position(|a| a.x <= x && x < a.x + a.width && a.y <= y && y < a.y + a.height)
+    }
+}
@@ -0,0 +1,54 @@
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::env;
+
+// Programs that can write a file as root, in order of preference
+const PROGRAMS: &[&str] = &["sudo", "doas"];
+
+// The first privilege escalation program found on `PATH`
+pub fn program() -> Option<&'static str> {
+    let path = env::var_os("PATH")?;
+    PROGRAMS
+        .iter()
+        .find(|p| env::split_paths(&path).any(|dir| dir.join(p).is_file()))
+        .copied()
+}
+
+// Write `data` to `path` by piping it to `tee` run through `program`, without
+// prompting unless a `password` is given (sudo reads it from stdin then)
+pub fn write(program: &str, path: &Path, data: &[u8], password: Option<&str>) -> io::Result<()> {
+    let mut command = Command::new(program);
+    match password {
+        Some(_) => command.args(["-S", "-p", ""]),
+        None => command.arg("-n")
+    };
+
+    let mut child = command
+        .arg("tee")
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // The pipe breaks if the program gives up early, its exit status says why
+    let written = match child.stdin.take() {
+        Some(mut stdin) => password
+            .map_or(Ok(()), |p| writeln!(stdin, "{}", p))
+            .and_then(|_| stdin.write_all(data)),
+        None => Ok(())
+    };
+
+    let output = child.wait_with_output()?;
+    if output.status.success() {
+        return written;
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let reason = match stderr.lines().find(|l| !l.trim().is_empty()) {
+        Some(l) => String::from(l.trim()),
+        None => format!("{} failed ({})", program, output.status)
+    };
+    Err(io::Error::new(io::ErrorKind::PermissionDenied, reason))
+}
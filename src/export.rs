@@ -0,0 +1,76 @@
+use crate::highlight::{self, State, Style};
+use std::fmt::Write;
+
+const STYLESHEET: &str = "body { background: #222; color: #ddd; margin: 0; }
+pre { font-family: monospace; padding: 1em; margin: 0; }
+.ln { color: #777; user-select: none; }
+.kw { color: #c678dd; }
+.ty { color: #e5c07b; }
+.st { color: #98c379; }
+.nu { color: #d19a66; }
+.cm { color: #7f848e; font-style: italic; }
+.hd { color: #61afef; font-weight: bold; }
+.em { color: #e06c75; font-style: italic; }
+.lk { color: #56b6c2; text-decoration: underline; }";
+
+fn escape(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c)
+        }
+    }
+}
+
+fn class(style: Style) -> Option<&'static str> {
+    match style {
+        Style::Normal => None,
+        Style::Keyword => Some("kw"),
+        Style::Type => Some("ty"),
+        Style::String => Some("st"),
+        Style::Number => Some("nu"),
+        Style::Comment => Some("cm"),
+        Style::Heading => Some("hd"),
+        Style::Emphasis => Some("em"),
+        Style::Link => Some("lk")
+    }
+}
+
+// Render `lines` as a standalone HTML page, highlighted according to `filetype`
+pub fn html(lines: &[&str], filetype: Option<&str>, title: &str, line_numbers: bool) -> String {
+    let syntax = filetype.and_then(highlight::syntax);
+    let number_width = lines.len().to_string().len();
+    let mut state = State::default();
+
+    let mut out = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>");
+    escape(&mut out, title);
+    let _ = write!(out, "</title>\n<style>\n{}\n</style>\n</head>\n<body>\n<pre>", STYLESHEET);
+
+    for (i, line) in lines.iter().enumerate() {
+        if line_numbers {
+            let _ = write!(out, "<span class=\"ln\">{:>number_width$} </span>", i + 1);
+        }
+
+        let spans = match syntax {
+            Some(syntax) => highlight::highlight_line(syntax, line, &mut state),
+            None => vec![(0..line.len(), Style::Normal)]
+        };
+        for (range, style) in spans {
+            match class(style) {
+                Some(class) => {
+                    let _ = write!(out, "<span class=\"{}\">", class);
+                    escape(&mut out, &line[range]);
+                    out.push_str("</span>");
+                },
+                None => escape(&mut out, &line[range])
+            }
+        }
+        out.push('\n');
+    }
+
+    out.push_str("</pre>\n</body>\n</html>\n");
+    out
+}
@@ -0,0 +1,69 @@
+use std::path::Path;
+
+pub struct Filetype {
+    pub name: &'static str,
+    extensions: &'static [&'static str],
+    filenames: &'static [&'static str],
+    interpreters: &'static [&'static str], // Programs named in a shebang line
+    pub comment: Option<&'static str> // Line comment prefix
+}
+
+const FILETYPES: &[Filetype] = &[
+    Filetype { name: "rust", extensions: &["rs"], filenames: &[], interpreters: &[], comment: Some("//") },
+    Filetype { name: "c", extensions: &["c", "h"], filenames: &[], interpreters: &[], comment: Some("//") },
+    Filetype { name: "cpp", extensions: &["cpp", "cc", "cxx", "hpp", "hh"], filenames: &[], interpreters: &[], comment: Some("//") },
+    Filetype { name: "go", extensions: &["go"], filenames: &[], interpreters: &[], comment: Some("//") },
+    Filetype { name: "java", extensions: &["java"], filenames: &[], interpreters: &[], comment: Some("//") },
+    Filetype { name: "javascript", extensions: &["js", "mjs", "cjs"], filenames: &[], interpreters: &["node"], comment: Some("//") },
+    Filetype { name: "typescript", extensions: &["ts", "tsx"], filenames: &[], interpreters: &[], comment: Some("//") },
+    Filetype { name: "python", extensions: &["py", "pyw"], filenames: &[], interpreters: &["python", "python2", "python3"], comment: Some("#") },
+    Filetype { name: "ruby", extensions: &["rb"], filenames: &["Rakefile", "Gemfile"], interpreters: &["ruby"], comment: Some("#") },
+    Filetype { name: "perl", extensions: &["pl", "pm"], filenames: &[], interpreters: &["perl"], comment: Some("#") },
+    Filetype { name: "sh", extensions: &["sh", "bash", "zsh"], filenames: &[".bashrc", ".zshrc", ".profile", ".bash_profile"], interpreters: &["sh", "bash", "zsh", "dash"], comment: Some("#") },
+    Filetype { name: "make", extensions: &["mk"], filenames: &["Makefile", "makefile", "GNUmakefile"], interpreters: &[], comment: Some("#") },
+    Filetype { name: "dockerfile", extensions: &[], filenames: &["Dockerfile", "Containerfile"], interpreters: &[], comment: Some("#") },
+    Filetype { name: "toml", extensions: &["toml"], filenames: &["Cargo.lock"], interpreters: &[], comment: Some("#") },
+    Filetype { name: "yaml", extensions: &["yaml", "yml"], filenames: &[], interpreters: &[], comment: Some("#") },
+    Filetype { name: "json", extensions: &["json"], filenames: &[], interpreters: &[], comment: None },
+    Filetype { name: "xml", extensions: &["xml", "svg", "xhtml"], filenames: &[], interpreters: &[], comment: None },
+    Filetype { name: "html", extensions: &["html", "htm"], filenames: &[], interpreters: &[], comment: None },
+    Filetype { name: "css", extensions: &["css"], filenames: &[], interpreters: &[], comment: None },
+    Filetype { name: "markdown", extensions: &["md", "markdown"], filenames: &[], interpreters: &[], comment: None },
+    Filetype { name: "lua", extensions: &["lua"], filenames: &[], interpreters: &["lua"], comment: Some("--") },
+    Filetype { name: "sql", extensions: &["sql"], filenames: &[], interpreters: &[], comment: Some("--") },
+    Filetype { name: "haskell", extensions: &["hs"], filenames: &[], interpreters: &["runhaskell"], comment: Some("--") },
+    Filetype { name: "vim", extensions: &["vim"], filenames: &[".vimrc"], interpreters: &[], comment: Some("\"") },
+    Filetype { name: "csv", extensions: &["csv"], filenames: &[], interpreters: &[], comment: None },
+    Filetype { name: "tsv", extensions: &["tsv"], filenames: &[], interpreters: &[], comment: None },
+    Filetype { name: "diff", extensions: &["diff", "patch"], filenames: &[], interpreters: &[], comment: None },
+    Filetype { name: "gitcommit", extensions: &[], filenames: &["COMMIT_EDITMSG"], interpreters: &[], comment: Some("#") },
+    Filetype { name: "text", extensions: &["txt"], filenames: &[], interpreters: &[], comment: None },
+];
+
+pub fn find(name: &str) -> Option<&'static Filetype> {
+    FILETYPES.iter().find(|f| f.name == name)
+}
+
+// Guess the filetype from the file name, or failing that a shebang on the first line
+pub fn detect(path: &Path, first_line: Option<&str>) -> Option<&'static Filetype> {
+    let filename = path.file_name().and_then(|n| n.to_str());
+    let extension = path.extension().and_then(|e| e.to_str());
+
+    let by_name = FILETYPES.iter().find(|f| {
+        filename.is_some_and(|n| f.filenames.contains(&n)) ||
+            extension.is_some_and(|e| f.extensions.contains(&e))
+    });
+    if by_name.is_some() {
+        return by_name;
+    }
+
+    // Shebangs look like `#!/bin/sh` or `#!/usr/bin/env python3`
+    let shebang = first_line?.strip_prefix("#!")?;
+    let mut words = shebang.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|w| !w.starts_with('-'))?;
+    }
+
+    FILETYPES.iter().find(|f| f.interpreters.contains(&program))
+}
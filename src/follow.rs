@@ -0,0 +1,61 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+// What's new in a followed file
+pub enum Update {
+    Appended(String),
+    Truncated // The file shrank, so it has to be read again from the start
+}
+
+// Reads what's appended to a file, like `tail -f`
+pub struct Follow {
+    position: u64, // How much of the file has been read
+    pub new_line: bool, // Whether what's read next starts a new line
+    pub readonly: bool // Whether the buffer was read-only before following
+}
+
+impl Follow {
+    pub fn start(path: &Path, readonly: bool) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let position = file.metadata()?.len();
+
+        let mut new_line = false;
+        if position > 0 {
+            let mut last = [0];
+            file.seek(SeekFrom::Start(position - 1))?;
+            file.read_exact(&mut last)?;
+            new_line = last[0] == b'\n';
+        }
+
+        Ok(Follow { position, new_line, readonly })
+    }
+
+    pub fn restart(&mut self) {
+        self.position = 0;
+        self.new_line = false;
+    }
+
+    // Check for new lines, leaving an unfinished last line until it's complete
+    pub fn read(&mut self, path: &Path) -> io::Result<Option<Update>> {
+        let mut file = File::open(path)?;
+        let len = file.metadata()?.len();
+        if len < self.position {
+            return Ok(Some(Update::Truncated));
+        }
+        if len == self.position {
+            return Ok(None);
+        }
+
+        let mut bytes = Vec::new();
+        file.seek(SeekFrom::Start(self.position))?;
+        file.take(len - self.position).read_to_end(&mut bytes)?;
+
+        let end = match bytes.iter().rposition(|&b| b == b'\n') {
+            Some(i) => i + 1,
+            None => return Ok(None)
+        };
+        self.position += end as u64;
+        Ok(Some(Update::Appended(String::from_utf8_lossy(&bytes[..end]).into_owned())))
+    }
+}
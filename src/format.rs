@@ -0,0 +1,201 @@
+// Reformat JSON and XML text, indenting nested values with `indent`
+// or stripping all insignificant whitespace if it's `None`
+
+fn newline(out: &mut String, indent: Option<&str>, depth: usize) {
+    if let Some(indent) = indent {
+        out.push('\n');
+        for _ in 0..depth {
+            out.push_str(indent);
+        }
+    }
+}
+
+pub fn json(text: &str, indent: Option<&str>) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut stack: Vec<char> = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                out.push(c);
+                let mut escaped = false;
+                loop {
+                    match chars.next() {
+                        Some(d) => {
+                            out.push(d);
+                            if escaped {
+                                escaped = false;
+                            } else if d == '\\' {
+                                escaped = true;
+                            } else if d == '"' {
+                                break;
+                            }
+                        },
+                        None => return Err(String::from("Unterminated string"))
+                    }
+                }
+            },
+            '{' | '[' => {
+                out.push(c);
+                let close = if c == '{' { '}' } else { ']' };
+                while chars.next_if(|d| d.is_whitespace()).is_some() {}
+                if chars.next_if_eq(&close).is_some() {
+                    // Keep empty objects and arrays on one line
+                    out.push(close);
+                } else {
+                    stack.push(close);
+                    newline(&mut out, indent, stack.len());
+                }
+            },
+            '}' | ']' => {
+                if stack.pop() != Some(c) {
+                    return Err(format!("Unexpected '{}'", c));
+                }
+                newline(&mut out, indent, stack.len());
+                out.push(c);
+            },
+            ',' => {
+                out.push(c);
+                newline(&mut out, indent, stack.len());
+            },
+            ':' => {
+                out.push(c);
+                if indent.is_some() {
+                    out.push(' ');
+                }
+            },
+            c if c.is_whitespace() => (),
+            c => out.push(c)
+        }
+    }
+
+    match stack.last() {
+        Some(c) => Err(format!("Missing '{}'", c)),
+        None => Ok(out)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    Open(&'a str, &'a str), // Tag and element name
+    Close(&'a str, &'a str),
+    Empty(&'a str), // `<tag/>`
+    Other(&'a str), // Comments, CDATA, declarations...
+    Text(&'a str)
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token<'_>>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        if !rest.starts_with('<') {
+            let end = rest.find('<').unwrap_or(rest.len());
+            tokens.push(Token::Text(&rest[..end]));
+            rest = &rest[end..];
+            continue;
+        }
+
+        let special = [("<!--", "-->"), ("<![CDATA[", "]]>"), ("<?", "?>"), ("<!", ">")];
+        if let Some((_, close)) = special.iter().find(|(open, _)| rest.starts_with(open)) {
+            let end = rest.find(close).ok_or("Unterminated markup")? + close.len();
+            tokens.push(Token::Other(&rest[..end]));
+            rest = &rest[end..];
+            continue;
+        }
+
+        // Find the end of the tag, skipping over quoted attribute values
+        let mut quote = None;
+        let end = rest
+            .char_indices()
+            .find(|&(_, c)| match quote {
+                Some(q) => {
+                    if c == q {
+                        quote = None;
+                    }
+                    false
+                },
+                None if c == '"' || c == '\'' => {
+                    quote = Some(c);
+                    false
+                },
+                None => c == '>'
+            })
+            .ok_or("Unterminated tag")?
+            .0 + 1;
+
+        let tag = &rest[..end];
+        let name = tag
+            .trim_start_matches(['<', '/'])
+            .split(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .next()
+            .unwrap_or_default();
+
+        tokens.push(if tag.starts_with("</") {
+            Token::Close(tag, name)
+        } else if tag.ends_with("/>") {
+            Token::Empty(tag)
+        } else {
+            Token::Open(tag, name)
+        });
+        rest = &rest[end..];
+    }
+    Ok(tokens)
+}
+
+pub fn xml(text: &str, indent: Option<&str>) -> Result<String, String> {
+    // Whitespace between tags isn't significant
+    let tokens: Vec<Token> = tokenize(text)?
+        .into_iter()
+        .filter(|t| !matches!(t, Token::Text(s) if s.trim().is_empty()))
+        .collect();
+
+    let mut out = String::with_capacity(text.len());
+    let mut stack: Vec<&str> = Vec::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        // A closing tag belongs at its parent's depth
+        if let Token::Close(tag, name) = tokens[i] {
+            if stack.pop() != Some(name) {
+                return Err(format!("Unexpected {}", tag));
+            }
+        }
+        if i > 0 {
+            newline(&mut out, indent, stack.len());
+        }
+
+        match tokens[i] {
+            Token::Open(tag, name) => {
+                // Keep elements holding only text on one line
+                match (tokens.get(i + 1), tokens.get(i + 2)) {
+                    (Some(Token::Close(close, n)), _) if *n == name => {
+                        out.push_str(tag);
+                        out.push_str(close);
+                        i += 2;
+                        continue;
+                    },
+                    (Some(Token::Text(s)), Some(Token::Close(close, n))) if *n == name => {
+                        out.push_str(tag);
+                        out.push_str(s.trim());
+                        out.push_str(close);
+                        i += 3;
+                        continue;
+                    },
+                    _ => ()
+                }
+                out.push_str(tag);
+                stack.push(name);
+            },
+            Token::Close(tag, _) | Token::Empty(tag) | Token::Other(tag) => out.push_str(tag),
+            Token::Text(s) => out.push_str(s.trim())
+        }
+        i += 1;
+    }
+
+    match stack.last() {
+        Some(name) => Err(format!("Missing </{}>", name)),
+        None => Ok(out)
+    }
+}
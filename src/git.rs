@@ -0,0 +1,347 @@
+use crate::buffer::{Buffer, Change};
+use crate::diff::{diff, Hunk};
+use std::collections::HashMap;
+use std::io::Write;
+use std::ops::Range;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// The directory containing `path`, which git is run from
+fn directory(path: &Path) -> &Path {
+    match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new(".")
+    }
+}
+
+// Run git in the directory containing `path`, returning its output if it succeeds
+fn git(path: &Path, args: &[&str]) -> Option<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(directory(path))
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    if output.status.success() { Some(output.stdout) } else { None }
+}
+
+// The contents of `path` in the git index, if it's tracked
+pub fn index_text(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    let text = git(path, &["show", &format!(":./{}", name)])?;
+    String::from_utf8(text).ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Marker {
+    Added,
+    Modified,
+    Deleted // Lines were removed just above this one
+}
+
+// Tracks how a buffer differs from the version of its file in the git index
+pub struct Gutter {
+    base: Vec<String>,
+    revision: Option<usize>, // Buffer revision the hunks were computed for
+    hunks: Vec<Hunk>,
+    stale: Option<Range<usize>>, // Rows edited since, which the hunks don't cover yet
+    markers: Vec<Option<Marker>> // For each line of the buffer
+}
+
+impl Gutter {
+    pub fn load(path: &Path) -> Option<Self> {
+        let base = index_text(path)?
+            .lines()
+            .map(String::from)
+            .collect();
+
+        Some(Gutter {
+            base,
+            revision: None,
+            hunks: Vec::new(),
+            stale: None,
+            markers: Vec::new()
+        })
+    }
+
+    // Move the hunks below an edit that took the buffer to `revision`, leaving the rows it
+    // touched for `update` to diff again
+    pub fn edited(&mut self, change: &Change, revision: usize) {
+        // If some other change was missed, `update` starts over
+        if self.revision.is_none_or(|r| r + 1 != revision) {
+            return;
+        }
+        self.revision = Some(revision);
+
+        let (first, inserted) = (change.start.y, change.inserted.y);
+        for hunk in self.hunks.iter_mut() {
+            hunk.new = change.map_row(hunk.new.start)..change.map_row(hunk.new.end);
+        }
+
+        let stale = match self.stale.take() {
+            Some(s) => change.map_row(s.start).min(first)..change.map_row(s.end).max(inserted + 1),
+            None => first..inserted + 1
+        };
+        self.stale = Some(stale);
+    }
+
+    // Work out the differences again if the buffer has changed since last time, only
+    // around the edited rows if every edit since was passed to `edited`
+    pub fn update(&mut self, buffer: &Buffer) {
+        let count = buffer.line_count();
+        let stale = self.stale.take();
+        if self.revision != Some(buffer.revision()) {
+            self.revision = Some(buffer.revision());
+            let base: Vec<&str> = self.base.iter().map(String::as_str).collect();
+            let current: Vec<&str> = buffer.lines().iter().map(|l| l.text.as_str()).collect();
+            self.hunks = diff(&base, &current);
+        } else if let Some(stale) = stale {
+            if !self.rediff(buffer, stale) {
+                self.revision = None;
+                return self.update(buffer);
+            }
+        } else if self.markers.len() == count {
+            return;
+        }
+
+        self.markers = vec![None; count];
+        for hunk in self.hunks.iter() {
+            if hunk.new.is_empty() {
+                let row = hunk.new.start.min(count - 1);
+                self.markers[row].get_or_insert(Marker::Deleted);
+            } else {
+                let marker = if hunk.old.is_empty() { Marker::Added } else { Marker::Modified };
+                for row in hunk.new.clone() {
+                    self.markers[row] = Some(marker);
+                }
+            }
+        }
+    }
+
+    // Diff the `rows` of the buffer, along with any hunks touching them, against the
+    // lines of the base they stand for. Returns `false` if the hunks don't add up, when
+    // it's diffed all over
+    fn rediff(&mut self, buffer: &Buffer, rows: Range<usize>) -> bool {
+        let count = buffer.line_count();
+        let mut rows = rows.start.min(count)..rows.end.min(count);
+        // Take in the hunks touching the rows, and any those reach
+        let (before, after) = loop {
+            let before = self.hunks.iter().take_while(|h| h.new.end < rows.start).count();
+            let after = self.hunks[before..].iter().rev().take_while(|h| h.new.start > rows.end).count();
+            let touching = &self.hunks[before..self.hunks.len() - after];
+            let start = touching.iter().map(|h| h.new.start).fold(rows.start, usize::min);
+            let end = touching.iter().map(|h| h.new.end).fold(rows.end, usize::max);
+            if (start..end) == rows {
+                break (before, after);
+            }
+            rows = start..end;
+        };
+
+        // Lines outside the hunks match the base, offset by how many each hunk adds
+        let growth = |hunks: &[Hunk]| hunks.iter().map(|h| h.new.len() as isize - h.old.len() as isize).sum::<isize>();
+        let old_start = rows.start as isize - growth(&self.hunks[..before]);
+        let old_end = self.base.len() as isize - (count - rows.end) as isize + growth(&self.hunks[self.hunks.len() - after..]);
+        if old_start < 0 || old_end < old_start || old_end as usize > self.base.len() {
+            return false;
+        }
+        let old = old_start as usize..old_end as usize;
+
+        let base: Vec<&str> = self.base[old.clone()].iter().map(String::as_str).collect();
+        let current: Vec<&str> = rows.clone().filter_map(|y| buffer.line(y)).map(|l| l.text.as_str()).collect();
+        let hunks = diff(&base, &current).into_iter().map(|h| Hunk {
+            old: h.old.start + old.start..h.old.end + old.start,
+            new: h.new.start + rows.start..h.new.end + rows.start
+        });
+        let end = self.hunks.len() - after;
+        self.hunks.splice(before..end, hunks);
+        true
+    }
+
+    pub fn marker(&self, row: usize) -> Option<Marker> {
+        self.markers.get(row).copied().flatten()
+    }
+
+    // The first row of each changed region, in order
+    pub fn hunk_starts(&self) -> impl Iterator<Item = usize> + '_ {
+        let last = self.markers.len().saturating_sub(1);
+        self.hunks.iter().map(move |h| h.new.start.min(last))
+    }
+}
+
+// Who last changed a line
+#[derive(Debug, Clone)]
+pub struct BlameLine {
+    pub commit: String,
+    pub author: String,
+    pub time: u64, // Seconds since the epoch
+    pub summary: String
+}
+
+impl BlameLine {
+    pub fn is_committed(&self) -> bool {
+        !self.commit.chars().all(|c| c == '0')
+    }
+
+    // e.g. `1a2b3c4d Jane Doe, 3 days ago: Fix the thing`
+    pub fn describe(&self) -> String {
+        if !self.is_committed() {
+            return String::from("Not committed yet");
+        }
+        format!("{} {}, {}: {}", self.commit, self.author, age(self.time), self.summary)
+    }
+}
+
+fn age(time: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let seconds = now.saturating_sub(time);
+
+    let (n, unit) = match seconds {
+        0..60 => return String::from("just now"),
+        60..3600 => (seconds / 60, "minute"),
+        3600..86400 => (seconds / 3600, "hour"),
+        86400..2592000 => (seconds / 86400, "day"),
+        2592000..31536000 => (seconds / 2592000, "month"),
+        _ => (seconds / 31536000, "year")
+    };
+    format!("{} {}{} ago", n, unit, if n == 1 { "" } else { "s" })
+}
+
+// The blame for each line of a file, if any
+type BlameLines = Vec<Option<BlameLine>>;
+
+// Parse `git blame --porcelain` output into the blame for each line
+fn parse_blame(text: &str) -> BlameLines {
+    let mut commits: HashMap<String, BlameLine> = HashMap::new();
+    let mut lines = Vec::new();
+    let mut current: Option<(String, usize)> = None; // Commit and final line of the entry being read
+
+    for line in text.lines() {
+        if line.starts_with('\t') {
+            // The line's content ends each entry
+            if let Some((commit, row)) = current.take() {
+                if lines.len() <= row {
+                    lines.resize(row + 1, None);
+                }
+                lines[row] = commits.get(&commit).cloned();
+            }
+            continue;
+        }
+
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        match &current {
+            None => {
+                let mut fields = value.split(' ');
+                let row = fields.nth(1).and_then(|r| r.parse::<usize>().ok());
+                if let Some(row) = row {
+                    let commit = String::from(key);
+                    commits.entry(commit.clone()).or_insert_with(|| BlameLine {
+                        commit: key.chars().take(8).collect(),
+                        author: String::new(),
+                        time: 0,
+                        summary: String::new()
+                    });
+                    current = Some((commit, row.saturating_sub(1)));
+                }
+            },
+            Some((commit, _)) => {
+                let entry = match commits.get_mut(commit) {
+                    Some(e) => e,
+                    None => continue
+                };
+                match key {
+                    "author" => entry.author = String::from(value),
+                    "author-time" => entry.time = value.parse().unwrap_or(0),
+                    "summary" => entry.summary = String::from(value),
+                    _ => ()
+                }
+            }
+        }
+    }
+    lines
+}
+
+// Blame `text` as the new contents of `path`, so unsaved edits are accounted for
+fn run_blame(path: &Path, text: String) -> Option<BlameLines> {
+    let name = path.file_name()?.to_str()?;
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(directory(path))
+        .args(["blame", "--porcelain", "--contents", "-", "--", name])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(parse_blame(&String::from_utf8_lossy(&output.stdout)))
+}
+
+// Blame information for a buffer, fetched in the background
+#[derive(Default)]
+pub struct Blame {
+    lines: BlameLines,
+    revision: Option<usize>, // Buffer revision `lines` describes
+    job: Option<(usize, Receiver<Option<BlameLines>>)>, // Revision being blamed, and its result
+    available: bool // Whether git could blame the file last time
+}
+
+impl Blame {
+    pub fn is_current(&self, buffer: &Buffer) -> bool {
+        self.revision == Some(buffer.revision())
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.available
+    }
+
+    // Start blaming the buffer's current contents, unless that's already done or a
+    // job is underway, in which case this should be called again once it's finished
+    pub fn request(&mut self, buffer: &Buffer) {
+        if self.is_current(buffer) || self.job.is_some() {
+            return;
+        }
+        let revision = buffer.revision();
+
+        let (tx, rx) = mpsc::channel();
+        let path = buffer.path().to_path_buf();
+        let text = format!("{}{}", buffer, buffer.line_ending().value());
+        thread::spawn(move || {
+            let _ = tx.send(run_blame(&path, text));
+        });
+        self.job = Some((revision, rx));
+    }
+
+    // Collect the result of a finished job, returns `true` if there was one
+    pub fn poll(&mut self) -> bool {
+        let result = match self.job.as_ref().map(|(_, rx)| rx.try_recv()) {
+            Some(Ok(r)) => r,
+            Some(Err(TryRecvError::Disconnected)) => None,
+            _ => return false
+        };
+
+        let (revision, _) = self.job.take().unwrap();
+        self.available = result.is_some();
+        self.lines = result.unwrap_or_default();
+        self.revision = Some(revision);
+        true
+    }
+
+    pub fn line(&self, row: usize) -> Option<&BlameLine> {
+        self.lines.get(row)?.as_ref()
+    }
+}
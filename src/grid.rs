@@ -0,0 +1,289 @@
+use crate::width;
+use std::io::{self, Write};
+use std::str;
+
+// What the terminal shows, cell by cell. Frames are drawn into one and compared with the
+// last, so only what changed is sent; tests read them to see what the user would
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    Default,
+    Indexed(u8),
+    Rgb(u8, u8, u8)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub symbol: String, // Empty for the right half of a wide character
+    pub fg: Color,
+    pub bg: Color,
+    pub bold: bool,
+    pub underline: bool,
+    pub reverse: bool
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell {
+            symbol: String::from(" "),
+            fg: Color::Default,
+            bg: Color::Default,
+            bold: false,
+            underline: false,
+            reverse: false
+        }
+    }
+}
+
+// A pretend terminal that understands the escape sequences the editor writes
+pub struct Grid {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+    cursor: (u16, u16), // Zero-based column and row
+    pen: Cell, // Colors and style for the next character
+    pub cursor_visible: bool,
+    pub cursor_style: Option<u16>, // Last `\x1b[<n> q` seen, if any
+    pending: Vec<u8> // Part of a sequence or character cut off at the end of a write
+}
+
+impl Grid {
+    pub fn new(width: u16, height: u16) -> Self {
+        Grid {
+            width,
+            height,
+            cells: vec![Cell::default(); width as usize * height as usize],
+            cursor: (0, 0),
+            pen: Cell::default(),
+            cursor_visible: true,
+            cursor_style: None,
+            pending: Vec::new()
+        }
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    pub fn cursor(&self) -> (u16, u16) {
+        self.cursor
+    }
+
+    pub fn cell(&self, x: u16, y: u16) -> &Cell {
+        &self.cells[y as usize * self.width as usize + x as usize]
+    }
+
+    pub fn cells(&self, y: u16) -> &[Cell] {
+        let start = y as usize * self.width as usize;
+        &self.cells[start..start + self.width as usize]
+    }
+
+    // Text of row `y` without trailing blanks
+    pub fn row(&self, y: u16) -> String {
+        let text: String = self.cells(y)
+            .iter()
+            .map(|c| c.symbol.as_str())
+            .collect();
+        String::from(text.trim_end())
+    }
+
+    pub fn rows(&self) -> Vec<String> {
+        (0..self.height).map(|y| self.row(y)).collect()
+    }
+
+    fn clear(&mut self) {
+        let blank = Cell { symbol: String::from(" "), ..self.pen.clone() };
+        self.cells.iter_mut().for_each(|c| *c = blank.clone());
+    }
+
+    fn put(&mut self, c: char) {
+        let (x, y) = self.cursor;
+        if y >= self.height {
+            return;
+        }
+
+        let index = y as usize * self.width as usize + x as usize;
+        match width::char_width(c) {
+            // Marks and joiners belong to the character before
+            Some(0) if x > 0 && index - 1 < self.cells.len() => self.cells[index - 1].symbol.push(c),
+            Some(w) if x < self.width => {
+                self.cells[index] = Cell { symbol: c.to_string(), ..self.pen.clone() };
+                if w == 2 && x + 1 < self.width {
+                    self.cells[index + 1] = Cell { symbol: String::new(), ..self.pen.clone() };
+                }
+                // A wide character in the last column stops at the edge like any other
+                self.cursor.0 = (x + w as u16).min(self.width);
+            },
+            _ => ()
+        }
+    }
+
+    // Handle a control sequence like `\x1b[1;5H`, given what's between `[` and the final byte
+    fn control(&mut self, params: &str, last: char) {
+        let numbers: Vec<u16> = params
+            .trim_start_matches('?')
+            .trim_end_matches(' ')
+            .split(';')
+            .map(|n| n.parse().unwrap_or(0))
+            .collect();
+        let first = numbers[0];
+
+        match last {
+            'H' => {
+                let y = first.max(1) - 1;
+                let x = numbers.get(1).copied().unwrap_or(1).max(1) - 1;
+                self.cursor = (x.min(self.width.saturating_sub(1)), y.min(self.height.saturating_sub(1)));
+            },
+            'J' if first == 2 => self.clear(),
+            'K' => {
+                let (x, y) = self.cursor;
+                let blank = Cell { symbol: String::from(" "), ..self.pen.clone() };
+                for x in x..self.width {
+                    self.cells[y as usize * self.width as usize + x as usize] = blank.clone();
+                }
+            },
+            'h' | 'l' if params == "?25" => self.cursor_visible = last == 'h',
+            'm' => self.style(&numbers),
+            'q' if params.ends_with(' ') => self.cursor_style = Some(first),
+            _ => () // Mouse reporting and so on don't change the cells
+        }
+    }
+
+    fn style(&mut self, numbers: &[u16]) {
+        let mut i = 0;
+        while i < numbers.len() {
+            match numbers[i] {
+                0 => self.pen = Cell::default(),
+                1 => self.pen.bold = true,
+                4 => self.pen.underline = true,
+                7 => self.pen.reverse = true,
+                22 => self.pen.bold = false,
+                24 => self.pen.underline = false,
+                27 => self.pen.reverse = false,
+                n @ 30..=37 => self.pen.fg = Color::Indexed(n as u8 - 30),
+                n @ 40..=47 => self.pen.bg = Color::Indexed(n as u8 - 40),
+                n @ 90..=97 => self.pen.fg = Color::Indexed(n as u8 - 82),
+                n @ 100..=107 => self.pen.bg = Color::Indexed(n as u8 - 92),
+                39 => self.pen.fg = Color::Default,
+                49 => self.pen.bg = Color::Default,
+                n @ (38 | 48) => {
+                    let color = match numbers.get(i + 1) {
+                        Some(5) => {
+                            i += 2;
+                            Color::Indexed(numbers.get(i).copied().unwrap_or(0) as u8)
+                        },
+                        Some(2) => {
+                            let channel = |j: usize| numbers.get(i + j).copied().unwrap_or(0) as u8;
+                            let color = Color::Rgb(channel(2), channel(3), channel(4));
+                            i += 4;
+                            color
+                        },
+                        _ => Color::Default
+                    };
+                    if n == 38 {
+                        self.pen.fg = color;
+                    } else {
+                        self.pen.bg = color;
+                    }
+                },
+                _ => ()
+            }
+            i += 1;
+        }
+    }
+
+    // Consume as much of `pending` as makes complete characters and sequences
+    fn parse(&mut self) {
+        let mut start = 0;
+        while start < self.pending.len() {
+            let rest = &self.pending[start..];
+            if rest[0] == 0x1b {
+                if rest.len() < 2 {
+                    break;
+                }
+                if rest[1] == b']' {
+                    // Operating system commands like setting the clipboard, which end
+                    // with a bell or `ESC \`
+                    match rest.iter().position(|&b| b == 0x07 || b == b'\\') {
+                        Some(end) => start += end + 1,
+                        None => break
+                    }
+                    continue;
+                }
+                if rest[1] != b'[' {
+                    // Not something the editor writes, skip the escape alone
+                    start += 1;
+                    continue;
+                }
+                match rest[2..].iter().position(|b| (0x40..=0x7e).contains(b)) {
+                    Some(end) => {
+                        let params = String::from_utf8_lossy(&rest[2..2 + end]).into_owned();
+                        let last = rest[2 + end] as char;
+                        self.control(&params, last);
+                        start += 3 + end;
+                    },
+                    None => break
+                }
+                continue;
+            }
+
+            let end = rest.iter().position(|&b| b == 0x1b).unwrap_or(rest.len());
+            let (text, used) = match str::from_utf8(&rest[..end]) {
+                Ok(text) => (String::from(text), end),
+                Err(e) if e.error_len().is_none() => {
+                    // A character split across writes, wait for the rest of it
+                    let valid = e.valid_up_to();
+                    (String::from_utf8_lossy(&rest[..valid]).into_owned(), valid)
+                },
+                Err(e) => {
+                    let bad = e.valid_up_to() + e.error_len().unwrap_or(1);
+                    (String::from_utf8_lossy(&rest[..bad]).into_owned(), bad)
+                }
+            };
+            if used == 0 {
+                break;
+            }
+
+            for c in text.chars() {
+                match c {
+                    '\r' => self.cursor.0 = 0,
+                    '\n' => self.cursor.1 = (self.cursor.1 + 1).min(self.height.saturating_sub(1)),
+                    c if c.is_control() => (), // Like a bell, nothing to show
+                    _ => self.put(c)
+                }
+            }
+            start += used;
+        }
+        self.pending.drain(..start);
+    }
+}
+
+impl Write for Grid {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        self.parse();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mark_after_wide_character_at_the_edge() {
+        let mut grid = Grid::new(4, 2);
+        write!(grid, "\x1b[2;4H\u{1f600}\u{fe0f}").unwrap();
+        assert_eq!(grid.cursor(), (4, 1));
+        assert_eq!(grid.cell(3, 1).symbol, "\u{1f600}\u{fe0f}");
+        assert_eq!(grid.row(0), "");
+
+        write!(grid, "\x1b[1;4H\u{1f600}\u{301}").unwrap();
+        assert_eq!(grid.cell(3, 0).symbol, "\u{1f600}\u{301}");
+        assert_eq!(grid.cell(0, 1).symbol, " ");
+    }
+}
@@ -0,0 +1,76 @@
+use crate::editor::Editor;
+use crate::input::Events;
+use crate::grid::Grid;
+use crate::terminal;
+use crate::width;
+use crate::Config;
+use termion::event::{Event, Key};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::io;
+use std::time::Duration;
+
+// Runs the editor without a terminal, for integration tests and fuzzing: a script of
+// events goes in, and the cells a terminal would show come out along with the editor
+
+// Events played back in order, always ready so nothing waits on background jobs
+pub struct Script {
+    events: VecDeque<io::Result<Event>>
+}
+
+impl Script {
+    pub fn new<I>(events: I) -> Self where I : IntoIterator<Item = io::Result<Event>> {
+        Script { events: events.into_iter().collect() }
+    }
+}
+
+impl Events for Script {
+    fn ready(&mut self, _timeout: Duration) -> bool {
+        true
+    }
+
+    fn unread(&mut self, event: io::Result<Event>) {
+        self.events.push_front(event);
+    }
+}
+
+impl Iterator for Script {
+    type Item = io::Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.events.pop_front()
+    }
+}
+
+// Key presses for typing `text`
+pub fn typed(text: &str) -> Vec<io::Result<Event>> {
+    text.chars().map(|c| Ok(Event::Key(Key::Char(c)))).collect()
+}
+
+pub struct Snapshot {
+    pub grid: Grid, // What was on screen once the events ran out or the user quit
+    pub editor: Editor
+}
+
+// Run the editor on a `width` by `height` pretend terminal until `events` run out
+pub fn simulate<I>(config: Config, width: u16, height: u16, events: I) -> Result<Snapshot, Box<dyn Error>>
+    where I : IntoIterator<Item = io::Result<Event>>
+{
+    terminal::fake_size(Some((width, height)));
+    let result = play(config, width, height, events);
+    terminal::fake_size(None);
+    result
+}
+
+fn play<I>(config: Config, width: u16, height: u16, events: I) -> Result<Snapshot, Box<dyn Error>>
+    where I : IntoIterator<Item = io::Result<Event>>
+{
+    width::configure(config.file.string("display", "ambiguous_width"))?;
+    let mut editor = Editor::new(config);
+    let mut grid = Grid::new(width, height);
+    let mut script = Script::new(events);
+
+    crate::drive(&mut editor, &mut script, &mut grid)?;
+
+    Ok(Snapshot { grid, editor })
+}
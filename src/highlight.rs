@@ -0,0 +1,390 @@
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    Normal,
+    Keyword,
+    Type,
+    String,
+    Number,
+    Comment,
+    Heading,
+    Emphasis,
+    Link
+}
+
+// How to highlight a language
+pub struct Syntax {
+    filetypes: &'static [&'static str],
+    keywords: &'static [&'static str],
+    types: &'static [&'static str],
+    capitalized_types: bool, // Treat identifiers starting with a capital as types
+    line_comment: Option<&'static str>,
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    markdown: bool // Highlighted as markup rather than code, see `markdown_line`
+}
+
+const SYNTAXES: &[Syntax] = &[
+    Syntax {
+        filetypes: &["rust"],
+        keywords: &["as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where", "while"],
+        types: &["bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize", "f32", "f64"],
+        capitalized_types: true,
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"'],
+        markdown: false
+    },
+    Syntax {
+        filetypes: &["c", "cpp"],
+        keywords: &["auto", "break", "case", "class", "const", "continue", "default", "delete", "do", "else", "enum", "extern", "false", "for", "goto", "if", "inline", "namespace", "new", "nullptr", "private", "protected", "public", "register", "return", "sizeof", "static", "struct", "switch", "template", "this", "true", "typedef", "typename", "union", "using", "virtual", "volatile", "while", "#include", "#define", "#if", "#ifdef", "#ifndef", "#else", "#endif", "#pragma"],
+        types: &["bool", "char", "double", "float", "int", "long", "short", "signed", "unsigned", "void", "size_t"],
+        capitalized_types: false,
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+        markdown: false
+    },
+    Syntax {
+        filetypes: &["go"],
+        keywords: &["break", "case", "chan", "const", "continue", "default", "defer", "else", "fallthrough", "false", "for", "func", "go", "goto", "if", "import", "interface", "map", "nil", "package", "range", "return", "select", "struct", "switch", "true", "type", "var"],
+        types: &["bool", "byte", "error", "float32", "float64", "int", "int8", "int16", "int32", "int64", "rune", "string", "uint", "uint8", "uint16", "uint32", "uint64"],
+        capitalized_types: false,
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+        markdown: false
+    },
+    Syntax {
+        filetypes: &["java", "javascript", "typescript"],
+        keywords: &["abstract", "async", "await", "break", "case", "catch", "class", "const", "continue", "default", "delete", "do", "else", "export", "extends", "false", "final", "finally", "for", "from", "function", "if", "implements", "import", "in", "instanceof", "interface", "let", "new", "null", "package", "private", "protected", "public", "return", "static", "super", "switch", "this", "throw", "true", "try", "typeof", "undefined", "var", "void", "while", "yield"],
+        types: &["boolean", "byte", "char", "double", "float", "int", "long", "number", "short", "string", "any"],
+        capitalized_types: true,
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+        markdown: false
+    },
+    Syntax {
+        filetypes: &["python"],
+        keywords: &["and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return", "True", "try", "while", "with", "yield", "self"],
+        types: &["int", "float", "str", "bool", "list", "dict", "set", "tuple", "bytes"],
+        capitalized_types: false,
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\''],
+        markdown: false
+    },
+    Syntax {
+        filetypes: &["ruby"],
+        keywords: &["begin", "break", "case", "class", "def", "do", "else", "elsif", "end", "ensure", "false", "for", "if", "in", "module", "next", "nil", "rescue", "retry", "return", "self", "then", "true", "unless", "until", "when", "while", "yield", "require"],
+        types: &[],
+        capitalized_types: true,
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\''],
+        markdown: false
+    },
+    Syntax {
+        filetypes: &["sh", "make", "dockerfile"],
+        keywords: &["case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in", "local", "return", "then", "until", "while", "FROM", "RUN", "CMD", "COPY", "ADD", "ENV", "WORKDIR", "ENTRYPOINT", "EXPOSE"],
+        types: &[],
+        capitalized_types: false,
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\''],
+        markdown: false
+    },
+    Syntax {
+        filetypes: &["lua"],
+        keywords: &["and", "break", "do", "else", "elseif", "end", "false", "for", "function", "if", "in", "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while"],
+        types: &[],
+        capitalized_types: false,
+        line_comment: Some("--"),
+        block_comment: Some(("--[[", "]]")),
+        quotes: &['"', '\''],
+        markdown: false
+    },
+    Syntax {
+        filetypes: &["toml", "yaml"],
+        keywords: &["true", "false", "null"],
+        types: &[],
+        capitalized_types: false,
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\''],
+        markdown: false
+    },
+    Syntax {
+        filetypes: &["json"],
+        keywords: &["true", "false", "null"],
+        types: &[],
+        capitalized_types: false,
+        line_comment: None,
+        block_comment: None,
+        quotes: &['"'],
+        markdown: false
+    },
+    Syntax {
+        filetypes: &["sql"],
+        keywords: &["select", "from", "where", "insert", "into", "values", "update", "set", "delete", "create", "table", "drop", "alter", "and", "or", "not", "null", "join", "left", "right", "inner", "outer", "on", "group", "by", "order", "having", "limit", "as", "SELECT", "FROM", "WHERE", "INSERT", "INTO", "VALUES", "UPDATE", "SET", "DELETE", "CREATE", "TABLE", "DROP", "ALTER", "AND", "OR", "NOT", "NULL", "JOIN", "LEFT", "RIGHT", "INNER", "OUTER", "ON", "GROUP", "BY", "ORDER", "HAVING", "LIMIT", "AS"],
+        types: &["int", "integer", "text", "varchar", "boolean", "INT", "INTEGER", "TEXT", "VARCHAR", "BOOLEAN"],
+        capitalized_types: false,
+        line_comment: Some("--"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['\'', '"'],
+        markdown: false
+    },
+    Syntax {
+        filetypes: &["markdown"],
+        keywords: &[],
+        types: &[],
+        capitalized_types: false,
+        line_comment: None,
+        block_comment: None,
+        quotes: &[],
+        markdown: true
+    }
+];
+
+pub fn syntax(filetype: &str) -> Option<&'static Syntax> {
+    SYNTAXES.iter().find(|s| s.filetypes.contains(&filetype))
+}
+
+// A line's text split into styled byte ranges
+pub type Spans = Vec<(Range<usize>, Style)>;
+
+// What carries over from one line to the next
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct State {
+    in_comment: bool,
+    in_fence: bool // Inside a fenced Markdown code block
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Split `line` into styled byte ranges, updating `state` for the next line
+pub fn highlight_line(syntax: &Syntax, line: &str, state: &mut State) -> Spans {
+    if syntax.markdown {
+        return markdown_line(line, state);
+    }
+
+    let mut spans: Spans = Vec::new();
+    let mut push = |range: Range<usize>, style: Style| {
+        if range.is_empty() {
+            return;
+        }
+        match spans.last_mut() {
+            Some((r, s)) if *s == style && r.end == range.start => r.end = range.end,
+            _ => spans.push((range, style))
+        }
+    };
+
+    let mut i = 0;
+    while i < line.len() {
+        let rest = &line[i..];
+
+        if state.in_comment {
+            let close = syntax.block_comment.map_or("", |(_, close)| close);
+            match rest.find(close) {
+                Some(end) => {
+                    push(i..i + end + close.len(), Style::Comment);
+                    i += end + close.len();
+                    state.in_comment = false;
+                },
+                None => {
+                    push(i..line.len(), Style::Comment);
+                    i = line.len();
+                }
+            }
+            continue;
+        }
+
+        if let Some((open, _)) = syntax.block_comment.filter(|(open, _)| rest.starts_with(open)) {
+            push(i..i + open.len(), Style::Comment);
+            i += open.len();
+            state.in_comment = true;
+            continue;
+        }
+
+        if syntax.line_comment.is_some_and(|c| rest.starts_with(c)) {
+            push(i..line.len(), Style::Comment);
+            break;
+        }
+
+        let c = rest.chars().next().unwrap_or_default();
+        if syntax.quotes.contains(&c) {
+            // Strings end at the matching unescaped quote, or the end of the line
+            let mut end = line.len();
+            let mut escaped = false;
+            for (j, d) in rest.char_indices().skip(1) {
+                if escaped {
+                    escaped = false;
+                } else if d == '\\' {
+                    escaped = true;
+                } else if d == c {
+                    end = i + j + d.len_utf8();
+                    break;
+                }
+            }
+            push(i..end, Style::String);
+            i = end;
+            continue;
+        }
+
+        if is_word(c) || (c == '#' && syntax.keywords.iter().any(|k| k.starts_with('#'))) {
+            let len = rest
+                .char_indices()
+                .skip(1)
+                .find(|&(_, d)| !is_word(d))
+                .map_or(rest.len(), |(j, _)| j);
+            let word = &rest[..len];
+
+            let style = if c.is_ascii_digit() {
+                Style::Number
+            } else if syntax.keywords.contains(&word) {
+                Style::Keyword
+            } else if syntax.types.contains(&word) || (syntax.capitalized_types && c.is_uppercase()) {
+                Style::Type
+            } else {
+                Style::Normal
+            };
+            push(i..i + len, style);
+            i += len;
+            continue;
+        }
+
+        push(i..i + c.len_utf8(), Style::Normal);
+        i += c.len_utf8();
+    }
+
+    spans
+}
+
+// Markdown lines are styled as a whole for headings, quotes and code blocks, otherwise
+// list markers, `code`, *emphasis* and [links](url) are picked out
+fn markdown_line(line: &str, state: &mut State) -> Spans {
+    let trimmed = line.trim_start();
+    let indent = line.len() - trimmed.len();
+    let whole = |style| if line.is_empty() { Vec::new() } else { vec![(0..line.len(), style)] };
+
+    if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+        state.in_fence = !state.in_fence;
+        return whole(Style::String);
+    }
+    if state.in_fence {
+        return whole(Style::String);
+    }
+
+    let hashes = trimmed.chars().take_while(|&c| c == '#').count();
+    if (1..=6).contains(&hashes) && trimmed[hashes..].chars().next().is_none_or(|c| c == ' ') {
+        return whole(Style::Heading);
+    }
+    if trimmed.starts_with('>') {
+        return whole(Style::Comment);
+    }
+
+    let mut spans = Vec::new();
+    let mut i = 0;
+
+    // A list marker like `-`, `*`, `+` or `1.` followed by a space
+    let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+    let marker = if trimmed.starts_with(['-', '*', '+']) {
+        1
+    } else if digits > 0 && trimmed[digits..].starts_with(['.', ')']) {
+        digits + 1
+    } else {
+        0
+    };
+    if marker > 0 && trimmed[marker..].starts_with(' ') {
+        i = indent + marker;
+        spans.push((0..i, Style::Keyword));
+    }
+
+    let mut start = i; // Start of the plain text not yet pushed
+    while i < line.len() {
+        let rest = &line[i..];
+        let c = rest.chars().next().unwrap_or_default();
+
+        let end = match c {
+            '`' => rest[1..].find('`').map(|j| (i + j + 2, Style::String)),
+            '*' | '_' => {
+                let delimiter = if rest[1..].starts_with(c) { &rest[..2] } else { &rest[..1] };
+                let after = &rest[delimiter.len()..];
+                after
+                    .find(delimiter)
+                    .filter(|&j| j > 0 && !after.starts_with(' '))
+                    .map(|j| (i + delimiter.len() * 2 + j, Style::Emphasis))
+            },
+            '[' => rest
+                .find("](")
+                .and_then(|j| rest[j..].find(')').map(|k| (i + j + k + 1, Style::Link))),
+            _ => None
+        };
+
+        match end {
+            Some((end, style)) => {
+                if start < i {
+                    spans.push((start..i, Style::Normal));
+                }
+                spans.push((i..end, style));
+                i = end;
+                start = end;
+            },
+            None => i += c.len_utf8()
+        }
+    }
+    if start < line.len() {
+        spans.push((start..line.len(), Style::Normal));
+    }
+    spans
+}
+
+// Spans of the lines highlighted so far, from the top of the buffer down. Once a line
+// changes, only it and the lines after it are highlighted again, and only as far down as
+// they're shown
+#[derive(Default)]
+pub struct Cache {
+    syntax: Option<&'static Syntax>,
+    revision: usize, // Buffer revision the cached lines are from
+    lines: Vec<(State, Spans)>, // The state each line starts in, and its spans
+    state: State // For the line after the last cached one
+}
+
+impl Cache {
+    // Forget line `row` and the ones after it, after an edit took the buffer to `revision`
+    pub fn edited(&mut self, row: usize, revision: usize) {
+        // If some other change was missed, `update` starts over
+        if self.revision + 1 != revision {
+            return;
+        }
+        self.revision = revision;
+        if let Some(&(state, _)) = self.lines.get(row) {
+            self.state = state;
+            self.lines.truncate(row);
+        }
+    }
+
+    // Highlight the lines up to `end` with `syntax`, getting each from `line`. Any other
+    // change to the buffer since it was at `revision` starts over from the top
+    pub fn update<'a, F>(&mut self, syntax: &'static Syntax, revision: usize, end: usize, line: F)
+        where F : Fn(usize) -> &'a str
+    {
+        let same = self.syntax.is_some_and(|s| std::ptr::eq(s, syntax));
+        if !same || self.revision != revision {
+            *self = Cache { syntax: Some(syntax), revision, ..Cache::default() };
+        }
+        while self.lines.len() < end {
+            let state = self.state;
+            let spans = highlight_line(syntax, line(self.lines.len()), &mut self.state);
+            self.lines.push((state, spans));
+        }
+    }
+
+    pub fn spans(&self, row: usize) -> &[(Range<usize>, Style)] {
+        self.lines.get(row).map_or(&[], |(_, spans)| spans.as_slice())
+    }
+}
@@ -0,0 +1,51 @@
+// The leading spaces and tabs of `line`
+pub fn leading(line: &str) -> &str {
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+// How many columns `indent` spans, with tabs going to the next tab stop
+pub fn width(indent: &str, tab_width: usize) -> usize {
+    let tab_width = tab_width.max(1);
+    indent.chars().fold(0, |column, c| match c {
+        '\t' => column + tab_width - column % tab_width,
+        _ => column + 1
+    })
+}
+
+// Indentation spanning `width` columns, using tabs unless `expand_tabs` is set
+pub fn render(width: usize, tab_width: usize, expand_tabs: bool) -> String {
+    if expand_tabs {
+        return " ".repeat(width);
+    }
+    let tab_width = tab_width.max(1);
+    format!("{}{}", "\t".repeat(width / tab_width), " ".repeat(width % tab_width))
+}
+
+// Shift the lines of `text` so the least indented one starts at column `target`.
+// The first line is pasted after the target indentation, so it only keeps the extra.
+// Blank lines lose their whitespace
+pub fn reindent(text: &str, target: usize, tab_width: usize, expand_tabs: bool) -> String {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let is_blank = |l: &str| l.trim().is_empty();
+
+    let base = lines
+        .iter()
+        .filter(|l| !is_blank(l))
+        .map(|l| width(leading(l), tab_width))
+        .min()
+        .unwrap_or_default();
+
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, l)| {
+            if is_blank(l) {
+                return String::new();
+            }
+            let shifted = width(leading(l), tab_width) - base;
+            let column = if i == 0 { shifted } else { shifted + target };
+            format!("{}{}", render(column, tab_width, expand_tabs), l.trim_start_matches([' ', '\t']))
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
@@ -0,0 +1,94 @@
+use termion::event::{Event, Key};
+use termion::input::TermRead;
+use std::io::{self, stdin};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+// Ask the terminal to mark the start and end of pasted text, so it can be inserted in one go
+pub const BRACKETED_PASTE_ON: &str = "\x1b[?2004h";
+pub const BRACKETED_PASTE_OFF: &str = "\x1b[?2004l";
+pub const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+// Terminal events read on a separate thread, so the editor can wait for input
+// with a timeout and do other work in between
+pub struct Input {
+    rx: Receiver<io::Result<Event>>,
+    pending: Option<io::Result<Event>>
+}
+
+impl Input {
+    pub fn spawn() -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            // Keys come from the terminal even when text is piped to stdin
+            if termion::is_tty(&stdin()) {
+                forward(stdin().events(), &tx);
+            } else if let Ok(tty) = termion::get_tty() {
+                forward(tty.events(), &tx);
+            }
+        });
+
+        Input { rx, pending: None }
+    }
+}
+
+fn forward<I>(events: I, tx: &Sender<io::Result<Event>>) where I : Iterator<Item = io::Result<Event>> {
+    for event in events {
+        if tx.send(event).is_err() {
+            break;
+        }
+    }
+}
+
+// Where the editor's events come from, the terminal or a script of them in tests
+pub trait Events: Iterator<Item = io::Result<Event>> {
+    // Wait up to `timeout` for an event, returns `true` if one is ready or input has ended
+    fn ready(&mut self, timeout: Duration) -> bool;
+
+    // Put back `event` to be read next
+    fn unread(&mut self, event: io::Result<Event>);
+}
+
+impl Events for Input {
+    fn ready(&mut self, timeout: Duration) -> bool {
+        if self.pending.is_some() {
+            return true;
+        }
+
+        match self.rx.recv_timeout(timeout) {
+            Ok(event) => {
+                self.pending = Some(event);
+                true
+            },
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => true
+        }
+    }
+
+    fn unread(&mut self, event: io::Result<Event>) {
+        self.pending = Some(event);
+    }
+}
+
+impl Iterator for Input {
+    type Item = io::Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pending.take().or_else(|| self.rx.recv().ok())
+    }
+}
+
+// The text of a bracketed paste, read up to the marker at its end
+pub fn read_paste<E>(events: &mut E) -> io::Result<String> where E : Events {
+    let mut text = String::new();
+    for event in events {
+        match event? {
+            Event::Key(Key::Char(c)) => text.push(c),
+            Event::Unsupported(bytes) if bytes == PASTE_END => break,
+            _ => ()
+        }
+    }
+    Ok(text)
+}
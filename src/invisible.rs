@@ -0,0 +1,81 @@
+use crate::config::ConfigFile;
+use crate::width;
+use std::borrow::Cow;
+
+// Characters that take up no space or pass for a plain space, which can hide in text.
+// Includes the bidi controls used to make code read differently than it runs
+pub fn is_invisible(c: char) -> bool {
+    matches!(c,
+        '\u{00A0}' | '\u{00AD}' | '\u{034F}' | '\u{061C}' | '\u{115F}' | '\u{1160}'
+        | '\u{17B4}' | '\u{17B5}' | '\u{180E}' | '\u{2000}'..='\u{200F}'
+        | '\u{2028}'..='\u{202F}' | '\u{205F}'..='\u{2064}' | '\u{2066}'..='\u{206F}'
+        | '\u{3000}' | '\u{3164}' | '\u{FEFF}' | '\u{FFA0}' | '\u{FFF9}'..='\u{FFFB}'
+        | '\u{E0000}'..='\u{E007F}'
+    )
+}
+
+pub fn contains_invisible(text: &str) -> bool {
+    text.chars().any(is_invisible)
+}
+
+// `text` with each invisible character spelled out by its code point, like `<200b>`
+pub fn reveal(text: &str) -> Cow<'_, str> {
+    if !contains_invisible(text) {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(text
+        .chars()
+        .map(|c| if is_invisible(c) { format!("<{:04x}>", c as u32) } else { c.to_string() })
+        .collect())
+}
+
+// What spaces, tabs and whitespace at the end of a line are drawn as while showing
+// whitespace, set from `[whitespace]`
+#[derive(Debug, Clone, Copy)]
+pub struct Whitespace {
+    pub space: char,
+    pub tab: char, // Followed by blanks up to the next tab stop
+    pub trail: char
+}
+
+impl Default for Whitespace {
+    fn default() -> Self {
+        Whitespace { space: '·', tab: '→', trail: '•' }
+    }
+}
+
+impl Whitespace {
+    pub fn build(file: &ConfigFile) -> Result<Self, String> {
+        let mut glyphs = Whitespace::default();
+        for (key, glyph) in [
+            ("space", &mut glyphs.space),
+            ("tab", &mut glyphs.tab),
+            ("trail", &mut glyphs.trail)
+        ] {
+            if let Some(s) = file.string("whitespace", key) {
+                let mut chars = s.chars();
+                *glyph = match (chars.next(), chars.next()) {
+                    (Some(c), None) => c,
+                    _ => return Err(format!("Invalid whitespace glyph '{}' for {}, expected one character", s, key))
+                };
+            }
+        }
+        Ok(glyphs)
+    }
+
+    // How `grapheme` is shown at `column` if it's whitespace, `trailing` if nothing but
+    // whitespace follows it on the line
+    pub fn glyph(&self, grapheme: &str, column: usize, tab_width: usize, trailing: bool) -> Option<String> {
+        // Each with an ASCII stand-in for when it would take two cells, like `·` does
+        // where ambiguous characters are wide
+        let (glyph, ascii) = match grapheme {
+            " " | "\t" if trailing => (self.trail, '~'),
+            " " => (self.space, '.'),
+            "\t" => (self.tab, '>'),
+            _ => return None
+        };
+        let width = if grapheme == "\t" { tab_width - column % tab_width } else { 1 };
+        let glyph = if width::char_width(glyph) == Some(1) { glyph } else { ascii };
+        Some(format!("{}{}", glyph, " ".repeat(width - 1)))
+    }
+}
@@ -0,0 +1,474 @@
+use crate::config::{ConfigFile, Value};
+use std::collections::HashMap;
+use termion::event::Key;
+
+// Everything a key can be bound to, by name under `[keys]` in the config file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Chord, // Waits for the next key of a sequence
+    Quit,
+    QuitEach,
+    Suspend,
+    AddCursor,
+    KillLine,
+    KillWholeLine,
+    JoinLines,
+    JoinLinesAsIs,
+    SortLines,
+    UpperCase,
+    LowerCase,
+    TitleCase,
+    ToggleComment,
+    SingleCursor,
+    Undo,
+    Redo,
+    NextBuffer,
+    PreviousBuffer,
+    NewBuffer,
+    Open,
+    Directory,
+    Rename,
+    BufferList,
+    RecentBuffer,
+    SwitchBuffer,
+    ShellCommand,
+    InsertDate,
+    Calculate,
+    IncrementalSearch,
+    Compile,
+    NextError,
+    DiskDiff,
+    Compare,
+    GotoDefinition,
+    JumpBack,
+    Blame,
+    NextConflict,
+    PreviousConflict,
+    ResolveConflict,
+    NextChange,
+    PreviousChange,
+    Reopen,
+    Save,
+    SaveAs,
+    Close,
+    Kill,
+    CloseOthers,
+    GotoLine,
+    ExportHtml,
+    Reformat,
+    Palette,
+    Follow,
+    ReadOnly,
+    Narrow,
+    Widen,
+    Protect,
+    View,
+    AlignColumns,
+    Invisibles,
+    Whitespace,
+    RelativeNumbers,
+    PinHeader,
+    MarkdownTable,
+    NextColumn,
+    PreviousColumn,
+    LineSelect,
+    PasteIndented,
+    ExpandSelection,
+    ShrinkSelection,
+    Count,
+    Describe,
+    StatusFields,
+    SplitBelow,
+    SplitRight,
+    NextPane,
+    ClosePane,
+    OnlyPane,
+    Top,
+    Bottom,
+    Copy,
+    Cut,
+    Paste,
+    Yank,
+    YankPop,
+    Backspace,
+    Delete,
+    Home,
+    End,
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    SelectUp,
+    SelectDown,
+    SelectLeft,
+    SelectRight,
+    Overwrite,
+    Tab,
+    PreviousCell
+}
+
+const COMMANDS: &[(&str, Command)] = &[
+    ("chord", Command::Chord),
+    ("quit", Command::Quit),
+    ("quit-each", Command::QuitEach),
+    ("suspend", Command::Suspend),
+    ("add-cursor", Command::AddCursor),
+    ("kill-line", Command::KillLine),
+    ("kill-whole-line", Command::KillWholeLine),
+    ("join-lines", Command::JoinLines),
+    ("join-lines-as-is", Command::JoinLinesAsIs),
+    ("sort-lines", Command::SortLines),
+    ("upper-case", Command::UpperCase),
+    ("lower-case", Command::LowerCase),
+    ("title-case", Command::TitleCase),
+    ("toggle-comment", Command::ToggleComment),
+    ("single-cursor", Command::SingleCursor),
+    ("undo", Command::Undo),
+    ("redo", Command::Redo),
+    ("next-buffer", Command::NextBuffer),
+    ("previous-buffer", Command::PreviousBuffer),
+    ("new-buffer", Command::NewBuffer),
+    ("open", Command::Open),
+    ("directory", Command::Directory),
+    ("rename", Command::Rename),
+    ("buffer-list", Command::BufferList),
+    ("recent-buffer", Command::RecentBuffer),
+    ("switch-buffer", Command::SwitchBuffer),
+    ("shell-command", Command::ShellCommand),
+    ("insert-date", Command::InsertDate),
+    ("calculate", Command::Calculate),
+    ("incremental-search", Command::IncrementalSearch),
+    ("compile", Command::Compile),
+    ("next-error", Command::NextError),
+    ("disk-diff", Command::DiskDiff),
+    ("compare", Command::Compare),
+    ("goto-definition", Command::GotoDefinition),
+    ("jump-back", Command::JumpBack),
+    ("blame", Command::Blame),
+    ("next-conflict", Command::NextConflict),
+    ("previous-conflict", Command::PreviousConflict),
+    ("resolve-conflict", Command::ResolveConflict),
+    ("next-change", Command::NextChange),
+    ("previous-change", Command::PreviousChange),
+    ("reopen", Command::Reopen),
+    ("save", Command::Save),
+    ("save-as", Command::SaveAs),
+    ("close", Command::Close),
+    ("kill", Command::Kill),
+    ("close-others", Command::CloseOthers),
+    ("goto-line", Command::GotoLine),
+    ("export-html", Command::ExportHtml),
+    ("reformat", Command::Reformat),
+    ("palette", Command::Palette),
+    ("follow", Command::Follow),
+    ("read-only", Command::ReadOnly),
+    ("narrow", Command::Narrow),
+    ("widen", Command::Widen),
+    ("protect", Command::Protect),
+    ("view", Command::View),
+    ("align-columns", Command::AlignColumns),
+    ("invisibles", Command::Invisibles),
+    ("whitespace", Command::Whitespace),
+    ("relative-numbers", Command::RelativeNumbers),
+    ("pin-header", Command::PinHeader),
+    ("markdown-table", Command::MarkdownTable),
+    ("next-column", Command::NextColumn),
+    ("previous-column", Command::PreviousColumn),
+    ("line-select", Command::LineSelect),
+    ("paste-indented", Command::PasteIndented),
+    ("expand-selection", Command::ExpandSelection),
+    ("shrink-selection", Command::ShrinkSelection),
+    ("count", Command::Count),
+    ("describe", Command::Describe),
+    ("status-fields", Command::StatusFields),
+    ("split-below", Command::SplitBelow),
+    ("split-right", Command::SplitRight),
+    ("next-pane", Command::NextPane),
+    ("close-pane", Command::ClosePane),
+    ("only-pane", Command::OnlyPane),
+    ("top", Command::Top),
+    ("bottom", Command::Bottom),
+    ("copy", Command::Copy),
+    ("cut", Command::Cut),
+    ("paste", Command::Paste),
+    ("yank", Command::Yank),
+    ("yank-pop", Command::YankPop),
+    ("backspace", Command::Backspace),
+    ("delete", Command::Delete),
+    ("home", Command::Home),
+    ("end", Command::End),
+    ("up", Command::Up),
+    ("down", Command::Down),
+    ("left", Command::Left),
+    ("right", Command::Right),
+    ("page-up", Command::PageUp),
+    ("page-down", Command::PageDown),
+    ("select-up", Command::SelectUp),
+    ("select-down", Command::SelectDown),
+    ("select-left", Command::SelectLeft),
+    ("select-right", Command::SelectRight),
+    ("overwrite", Command::Overwrite),
+    ("tab", Command::Tab),
+    ("previous-cell", Command::PreviousCell)
+];
+
+// The bindings before the config file changes any
+const DEFAULTS: &[(&str, Command)] = &[
+    ("C-x", Command::Chord),
+    ("C-x q", Command::Quit),
+    ("C-x Q", Command::QuitEach),
+    ("C-z", Command::Suspend),
+    ("C-x z", Command::Undo),
+    ("C-x y", Command::Redo),
+    ("C-x .", Command::NextBuffer),
+    ("C-x ,", Command::PreviousBuffer),
+    ("C-x n", Command::NewBuffer),
+    ("C-x o", Command::Open),
+    ("C-x d", Command::Directory),
+    ("C-x r", Command::Rename),
+    ("C-x b", Command::BufferList),
+    ("C-x Tab", Command::RecentBuffer),
+    ("C-x p", Command::SwitchBuffer),
+    ("C-x !", Command::ShellCommand),
+    ("C-x t", Command::InsertDate),
+    ("C-x e", Command::Calculate),
+    ("C-x f", Command::IncrementalSearch),
+    ("C-x c", Command::Compile),
+    ("C-x `", Command::NextError),
+    ("C-x D", Command::DiskDiff),
+    ("C-x v", Command::Compare),
+    ("C-x j", Command::GotoDefinition),
+    ("C-x J", Command::JumpBack),
+    ("C-x B", Command::Blame),
+    ("C-x >", Command::NextConflict),
+    ("C-x <", Command::PreviousConflict),
+    ("C-x m", Command::ResolveConflict),
+    ("C-x ]", Command::NextChange),
+    ("C-x [", Command::PreviousChange),
+    ("C-x T", Command::Reopen),
+    ("C-x s", Command::Save),
+    ("C-x S", Command::SaveAs),
+    ("C-x w", Command::Close),
+    ("C-x k", Command::Kill),
+    ("C-x O", Command::CloseOthers),
+    ("C-x g", Command::GotoLine),
+    ("C-x H", Command::ExportHtml),
+    ("C-x P", Command::Reformat),
+    ("C-x x", Command::Palette),
+    ("C-x F", Command::Follow),
+    ("C-x R", Command::ReadOnly),
+    ("C-x N", Command::Narrow),
+    ("C-x W", Command::Widen),
+    ("C-x L", Command::Protect),
+    ("C-x V", Command::View),
+    ("C-x A", Command::AlignColumns),
+    ("C-x *", Command::Invisibles),
+    ("C-x _", Command::Whitespace),
+    ("C-x %", Command::RelativeNumbers),
+    ("C-x ^", Command::PinHeader),
+    ("C-x |", Command::MarkdownTable),
+    ("C-x }", Command::NextColumn),
+    ("C-x {", Command::PreviousColumn),
+    ("C-x l", Command::LineSelect),
+    ("C-x M", Command::AddCursor),
+    ("C-x U", Command::UpperCase),
+    ("C-x u", Command::LowerCase),
+    ("C-x ~", Command::TitleCase),
+    ("C-x ;", Command::ToggleComment),
+    ("Esc", Command::SingleCursor),
+    ("C-x I", Command::PasteIndented),
+    ("C-x +", Command::ExpandSelection),
+    ("C-x -", Command::ShrinkSelection),
+    ("C-x #", Command::Count),
+    ("C-x =", Command::Describe),
+    ("C-x i", Command::StatusFields),
+    ("C-x 2", Command::SplitBelow),
+    ("C-x 3", Command::SplitRight),
+    ("C-x a", Command::NextPane),
+    ("C-x 0", Command::ClosePane),
+    ("C-x 1", Command::OnlyPane),
+    ("C-x Up", Command::Top),
+    ("C-x Down", Command::Bottom),
+    ("C-c", Command::Copy),
+    ("C-w", Command::Cut),
+    ("C-p", Command::Paste),
+    ("C-v", Command::Paste),
+    ("C-y", Command::Yank),
+    ("M-y", Command::YankPop),
+    ("C-k", Command::KillLine),
+    ("C-x K", Command::KillWholeLine),
+    ("M-^", Command::JoinLines),
+    ("M-s", Command::SortLines),
+    ("Backspace", Command::Backspace),
+    ("Delete", Command::Delete),
+    ("Home", Command::Home),
+    ("End", Command::End),
+    ("Up", Command::Up),
+    ("Down", Command::Down),
+    ("Left", Command::Left),
+    ("Right", Command::Right),
+    ("PageUp", Command::PageUp),
+    ("PageDown", Command::PageDown),
+    ("C-Up", Command::SelectUp),
+    ("C-Down", Command::SelectDown),
+    ("C-Left", Command::SelectLeft),
+    ("C-Right", Command::SelectRight),
+    ("Insert", Command::Overwrite),
+    ("Tab", Command::Tab),
+    ("S-Tab", Command::PreviousCell)
+];
+
+// Keys with names rather than the character they type
+const NAMES: &[(&str, Key)] = &[
+    ("Tab", Key::Char('\t')),
+    ("Enter", Key::Char('\n')),
+    ("Space", Key::Char(' ')),
+    ("Esc", Key::Esc),
+    ("Backspace", Key::Backspace),
+    ("Delete", Key::Delete),
+    ("Insert", Key::Insert),
+    ("Home", Key::Home),
+    ("End", Key::End),
+    ("PageUp", Key::PageUp),
+    ("PageDown", Key::PageDown),
+    ("Up", Key::Up),
+    ("Down", Key::Down),
+    ("Left", Key::Left),
+    ("Right", Key::Right),
+    ("S-Tab", Key::BackTab),
+    ("S-Up", Key::ShiftUp),
+    ("S-Down", Key::ShiftDown),
+    ("S-Left", Key::ShiftLeft),
+    ("S-Right", Key::ShiftRight),
+    ("M-Up", Key::AltUp),
+    ("M-Down", Key::AltDown),
+    ("M-Left", Key::AltLeft),
+    ("M-Right", Key::AltRight),
+    ("C-Up", Key::CtrlUp),
+    ("C-Down", Key::CtrlDown),
+    ("C-Left", Key::CtrlLeft),
+    ("C-Right", Key::CtrlRight),
+    ("C-Home", Key::CtrlHome),
+    ("C-End", Key::CtrlEnd)
+];
+
+// A key written like `C-x`, `M-y`, `S-Tab`, `PageUp`, `F5` or a single character
+pub fn parse_key(s: &str) -> Option<Key> {
+    if let Some(&(_, key)) = NAMES.iter().find(|(name, _)| *name == s) {
+        return Some(key);
+    }
+
+    let single = |s: &str| {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None
+        }
+    };
+
+    if let Some(c) = s.strip_prefix("C-").and_then(single) {
+        Some(Key::Ctrl(c))
+    } else if let Some(c) = s.strip_prefix("M-").and_then(single) {
+        Some(Key::Alt(c))
+    } else if let Some(n) = s.strip_prefix('F').and_then(|n| n.parse().ok()).filter(|n| (1..=12).contains(n)) {
+        Some(Key::F(n))
+    } else {
+        single(s).map(Key::Char)
+    }
+}
+
+// How `key` is written in the config file
+pub fn key_name(key: Key) -> String {
+    if let Some((name, _)) = NAMES.iter().find(|(_, k)| *k == key) {
+        return String::from(*name);
+    }
+
+    match key {
+        Key::Ctrl(c) => format!("C-{}", c),
+        Key::Alt(c) => format!("M-{}", c),
+        Key::F(n) => format!("F{}", n),
+        Key::Char(c) => c.to_string(),
+        _ => format!("{:?}", key)
+    }
+}
+
+// A sequence of keys separated by spaces, like `C-x q`
+fn parse_sequence(s: &str) -> Option<Vec<Key>> {
+    let keys = s.split_whitespace().map(parse_key).collect::<Option<Vec<_>>>()?;
+    match keys.len() {
+        1 | 2 => Some(keys),
+        _ => None
+    }
+}
+
+// Which command each key or pair of keys runs. The first key of a pair must be bound to
+// `chord`, and keys typing a character that aren't bound just insert it
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<Vec<Key>, Command>
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = DEFAULTS
+            .iter()
+            .map(|&(keys, command)| (parse_sequence(keys).expect("default binding is valid"), command))
+            .collect();
+        Keymap { bindings }
+    }
+}
+
+impl Keymap {
+    // The defaults, changed by `"keys" = "command"` under `[keys]`, where `none` unbinds them
+    pub fn build(file: &ConfigFile) -> Result<Self, String> {
+        let mut keymap = Keymap::default();
+        let mut pairs = Vec::new();
+
+        for (keys, value) in file.table("keys") {
+            let sequence = parse_sequence(keys).ok_or_else(|| format!("Invalid key binding '{}'", keys))?;
+            let name = match value {
+                Value::String(s) => s.as_str(),
+                _ => return Err(format!("Expected a command name for '{}'", keys))
+            };
+
+            if name == "none" {
+                keymap.bindings.remove(&sequence);
+                continue;
+            }
+            let command = COMMANDS
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|&(_, c)| c)
+                .ok_or_else(|| format!("Unknown command '{}' for '{}'", name, keys))?;
+            if sequence.len() == 2 {
+                pairs.push((keys, sequence[0]));
+            }
+            keymap.bindings.insert(sequence, command);
+        }
+
+        // Pairs from the config file could never be typed without their chord key, while
+        // the default ones just go away with it
+        if let Some((keys, prefix)) = pairs.into_iter().find(|&(_, prefix)| keymap.get(prefix) != Some(Command::Chord)) {
+            return Err(format!("'{}' is bound, but '{}' isn't bound to chord", keys, key_name(prefix)));
+        }
+        let prefixes: Vec<Key> = keymap.bindings
+            .iter()
+            .filter(|&(_, &c)| c == Command::Chord)
+            .map(|(k, _)| k[0])
+            .collect();
+        keymap.bindings.retain(|k, _| k.len() == 1 || prefixes.contains(&k[0]));
+
+        Ok(keymap)
+    }
+
+    pub fn get(&self, key: Key) -> Option<Command> {
+        self.bindings.get(&vec![key]).copied()
+    }
+
+    // The command for `key` typed after the chord key `prefix`
+    pub fn chord(&self, prefix: Key, key: Key) -> Option<Command> {
+        self.bindings.get(&vec![prefix, key]).copied()
+    }
+}
@@ -3,32 +3,182 @@ extern crate getopts;
 extern crate unicode_segmentation;
 extern crate unicode_width;
 
+pub mod bidi;
 pub mod buffer;
+pub mod buffers;
+pub mod calc;
+pub mod clipboard;
+pub mod compare;
+pub mod compile;
+pub mod config;
+pub mod conflict;
+pub mod dashboard;
+pub mod depth;
+pub mod diff;
+pub mod editor;
+pub mod elevate;
+pub mod export;
+pub mod filetype;
+pub mod follow;
+pub mod format;
+pub mod git;
+pub mod grid;
+pub mod harness;
+pub mod highlight;
+pub mod indent;
+pub mod input;
+pub mod invisible;
+pub mod keymap;
+pub mod markdown;
+pub mod palette;
+pub mod panes;
+pub mod picker;
+pub mod recent;
+pub mod remote;
 pub mod screen;
+pub mod table;
+pub mod template;
+pub mod terminal;
+pub mod tags;
+pub mod tabs;
+pub mod transform;
+pub mod width;
 
-use crate::screen::Screen;
+use crate::buffer::{Encoding, LineEnding};
+use crate::config::ConfigFile;
+use crate::editor::{calculate, export_html, goto_line, reformat, save, search, search_again, sort_lines, Editor};
+use crate::dashboard::Choice;
+use crate::invisible::Whitespace;
+use crate::input::{Events, Input, BRACKETED_PASTE_OFF, BRACKETED_PASTE_ON, PASTE_START};
+use crate::keymap::{key_name, Command, Keymap};
 use crate::screen::cursor::Direction;
-use screen::Message;
-use termion::event::{Key, Event, MouseEvent};
-use termion::input::{TermRead, MouseTerminal};
-use std::cmp::min;
-use std::io::{stdin, stdout, ErrorKind, Write};
-use std::path::PathBuf;
+use crate::screen::colors::Colors;
+use crate::screen::shape::{self, CursorShapes};
+use crate::terminal::Output;
+use screen::{Message, Screen, StatusSegment};
+use termion::event::{Key, Event, MouseButton, MouseEvent};
+use termion::input::MouseTerminal;
+use std::io::{stdout, Write};
+use std::time::{Duration, Instant};
 use termion::raw::IntoRawMode;
 use std::error::Error;
-use getopts::Options;
+use getopts::{Matches, Options};
 use std::process;
+use std::path::Path;
+
+// How often background jobs are checked for output while waiting for input
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+// How many lines a turn of the mouse wheel scrolls
+const WHEEL_LINES: usize = 3;
+// Presses on the same cell closer together than this count as a double or triple click
+const MULTI_CLICK: Duration = Duration::from_millis(400);
 
 fn print_usage(program: &str, opts: Options) {
-    let brief = format!("Usage: {} [options] [file ...]", program);
+    let brief = format!("Usage: {} [options] [file ...] [-- [options] [file ...] ...]", program);
     println!("{}", opts.usage(&brief));
+    println!("Options apply to the files after them up to the next `--`, and the last ones");
+    println!("to files opened while editing.");
+    println!("A file given as `file:line:column`, `file:line` or after `+line` opens there.");
+    println!("A file named `-` is read from stdin, like `git diff | ted -`.");
+}
+
+// How to open a file, given on the command line
+#[derive(Debug, Clone)]
+pub struct FileOptions {
+    readonly: bool,
+    truncate: bool,
+    modelines: bool,
+    view: bool,
+    encoding: Option<Encoding>, // Overrides for the file's own
+    ending: Option<LineEnding>,
+    tab_width: Option<usize>,
+    position: Option<(usize, usize)> // Zero-based row and column to start the cursor at
+}
+
+impl Default for FileOptions {
+    fn default() -> Self {
+        FileOptions {
+            readonly: false,
+            truncate: false,
+            modelines: true,
+            view: false,
+            encoding: None,
+            ending: None,
+            tab_width: None,
+            position: None
+        }
+    }
+}
+
+impl FileOptions {
+    fn build(matches: &Matches) -> Result<Self, String> {
+        let readonly = matches.opt_present("r");
+        let truncate = matches.opt_present("t");
+        let modelines = !matches.opt_present("no-modelines");
+        let view = matches.opt_present("v");
+
+        if readonly && truncate {
+            return Err("Cannot truncate files in read-only mode".to_string());
+        }
+
+        let encoding = match matches.opt_str("e") {
+            Some(name) => match Encoding::parse(&name) {
+                Some(e) => Some(e),
+                None => return Err(format!("Unsupported encoding '{}', expected UTF-8, UTF-8-BOM, Latin-1, UTF-16LE, UTF-16BE or binary", name))
+            },
+            None => None
+        };
+
+        let mut endings = [("lf", LineEnding::LF), ("crlf", LineEnding::CRLF), ("cr", LineEnding::CR)]
+            .into_iter()
+            .filter(|(flag, _)| matches.opt_present(flag))
+            .map(|(_, ending)| ending);
+        let ending = endings.next();
+        if endings.next().is_some() {
+            return Err("Cannot use more than one of LF, CRLF and CR line endings".to_string());
+        }
+
+        let tab_width = match matches.opt_str("w") {
+            Some(n) => match n.parse() {
+                Ok(n) if n > 0 => Some(n),
+                _ => return Err(format!("Invalid tab width '{}'", n))
+            },
+            None => None
+        };
+
+        Ok(FileOptions { readonly, truncate, modelines, view, encoding, ending, tab_width, position: None })
+    }
+}
+
+// Split a position off the end of `arg`, like `src/lib.rs:120:8` or `notes.txt:3`, unless
+// a file is called that. Lines and columns count from one
+fn split_position(arg: &str) -> (&str, Option<(usize, usize)>) {
+    if Path::new(arg).exists() {
+        return (arg, None);
+    }
+    let number = |s: &str| s.parse::<usize>().ok().filter(|&n| n > 0);
+    if let Some((rest, last)) = arg.rsplit_once(':') {
+        if let (Some((path, line)), Some(column)) = (rest.rsplit_once(':'), number(last)) {
+            if let Some(line) = number(line).filter(|_| !path.is_empty()) {
+                return (path, Some((line - 1, column - 1)));
+            }
+        }
+        if let Some(line) = number(last).filter(|_| !rest.is_empty()) {
+            return (rest, Some((line - 1, 0)));
+        }
+    }
+    (arg, None)
 }
 
 #[derive(Debug)]
 pub struct Config {
-    paths: Vec<String>,
-    readonly: bool,
-    truncate: bool
+    paths: Vec<(String, FileOptions)>,
+    options: FileOptions, // For files opened while editing
+    cursors: CursorShapes,
+    colors: Colors,
+    whitespace: Whitespace, // What spaces and tabs are shown as, while they're shown
+    keymap: Keymap,
+    file: ConfigFile // Settings from the user's config file
 }
 
 impl Config {
@@ -36,275 +186,561 @@ impl Config {
         let mut opts = Options::new();
         opts.optflag("t", "truncate", "Truncate existing file(s)");
         opts.optflag("r", "readonly", "Open file(s) as read-only");
+        opts.optflag("", "no-modelines", "Ignore Vim/Emacs modelines in file(s)");
+        opts.optflag("v", "view", "Open file(s) in view mode, like a pager");
+        opts.optopt("e", "encoding", "Encoding of file(s): UTF-8, UTF-8-BOM, Latin-1, UTF-16LE, UTF-16BE or binary (a hex dump)", "NAME");
+        opts.optflag("", "lf", "Save file(s) with LF line endings");
+        opts.optflag("", "crlf", "Save file(s) with CRLF line endings");
+        opts.optflag("", "cr", "Save file(s) with CR line endings");
+        opts.optopt("w", "tabwidth", "Width of a tab in file(s)", "N");
         opts.optflag("h", "help", "Print this help menu");
 
         let program = &args[0];
-        let matches = opts.parse(&args[1..]);
-
-        if let Err(f) = matches {
-            return Err(f.to_string());
-        }
-        let matches = matches.unwrap();
+        let mut paths = Vec::new();
+        let mut options = FileOptions::default();
 
-        if matches.opt_present("h") {
-            print_usage(program, opts);
-            process::exit(1);
-        }
+        // Each group of files between `--` has its own options, like `-r ref.txt -- notes.txt`
+        for group in args[1..].split(|a| a == "--") {
+            let matches = opts.parse(group).map_err(|f| f.to_string())?;
 
-        let readonly = matches.opt_present("r");
-        let truncate = matches.opt_present("t");
+            if matches.opt_present("h") {
+                print_usage(program, opts);
+                process::exit(1);
+            }
 
-        if readonly && truncate {
-            return Err("Cannot truncate files in read-only mode".to_string());
+            options = FileOptions::build(&matches)?;
+            // A `+N` before a file starts it on line N, as does a `:N` after it
+            let mut line = None;
+            for arg in &matches.free {
+                if let Some(n) = arg.strip_prefix('+').and_then(|n| n.parse::<usize>().ok()) {
+                    line = Some(n.saturating_sub(1));
+                    continue;
+                }
+                let (path, position) = split_position(arg);
+                let position = position.or(line.take().map(|row| (row, 0)));
+                paths.push((String::from(path), FileOptions { position, ..options.clone() }));
+            }
         }
         
+        let file = ConfigFile::load()?;
+        if let Some(s) = file.string("editor", "line_ending").filter(|s| LineEnding::parse(s).is_none()) {
+            return Err(format!("Invalid line_ending '{}', expected lf, crlf or cr", s));
+        }
+
         Ok(Config { 
-            paths: matches.free,
-            readonly,
-            truncate
+            paths,
+            options,
+            cursors: CursorShapes::build(&file)?,
+            colors: Colors::build(&file)?,
+            whitespace: Whitespace::build(&file)?,
+            keymap: Keymap::build(&file)?,
+            file
         })
     }
 }
 
 pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let mut screens: Vec<Screen> = config.paths
-        .iter()
-        .map(|p| Screen::new(p, &config))
-        .collect();
+    width::configure(config.file.string("display", "ambiguous_width"))?;
+    depth::configure(config.file.string("display", "colors"))?;
+    let mut editor = Editor::new(config);
+    terminal::prepare_suspend()?;
+    let mut stdout = MouseTerminal::from(stdout().into_raw_mode().unwrap());
+    let mut events = Input::spawn();
+    write!(stdout, "{}", BRACKETED_PASTE_ON)?;
 
-    if config.paths.is_empty() {
-        screens.push(Screen::new("", &config));
-    }
+    drive(&mut editor, &mut events, &mut stdout)?;
 
-    let stdin = stdin();
-    let mut stdout = MouseTerminal::from(stdout().into_raw_mode().unwrap());
-    let mut index = 0;
-    let mut chord = false;
-    let mut timeout = 0;
+    write!(stdout, "{}{}{}{}", BRACKETED_PASTE_OFF, termion::clear::All, termion::cursor::Goto(1, 1), shape::RESTORE)?;
+
+    Ok(())
+}
 
-    let mut events = stdin.events();
+// Handle `events` until the user quits or they run out, drawing to `stdout`
+pub fn drive<T, E>(editor: &mut Editor, events: &mut E, stdout: &mut T) -> Result<(), Box<dyn Error>>
+    where T : Write
+        , E : Events
+{
+    let mut out = Output::new(stdout);
+    let dashboard = editor.config.paths.is_empty() && editor.config.file.boolean("dashboard", "enabled").unwrap_or(true);
+    let mut chord = None; // The key waiting for the rest of a chord
+    let mut clicks: Option<(u16, u16, Instant, usize)> = None; // Where and when the last press was, and how many in a row
+
+    if dashboard {
+        let files = recent::load();
+        match dashboard::show(events, &mut out, &files, &editor.config.colors)? {
+            Choice::Open(i) => editor.open_instead(&files[i]),
+            Choice::Dismiss(Some(event)) => events.unread(Ok(event)),
+            Choice::Dismiss(None) => ()
+        }
+    }
     loop {
-        let screen = &mut screens[index];
+        editor.draw(&mut out)?;
 
-        if timeout == 0 {
-            screen.clear_message();
-        } 
-        
-        if timeout >= 0 {
-            timeout -= 1;
+        while !events.ready(POLL_INTERVAL) {
+            let stopped = terminal::stop_requested();
+            if stopped {
+                terminal::suspend(&mut out)?;
+            }
+            let polled = editor.poll();
+            if editor.confirm_reload(events, &mut out)? || polled || stopped {
+                editor.draw(&mut out)?;
+            }
         }
 
-        screen.draw(&mut stdout)?;
-        stdout.flush()?;
-
-        if let Some(event) = events.next() {
-            if chord {
-                chord = false;
-                timeout = 0;
-                let mut was_valid = true;
-
-                match event? {
-                    Event::Key(Key::Esc) => continue,
-                    Event::Key(Key::Char(ch)) => {
-                        match ch {
-                            'q' => break,
-                            'z' => screen.undo(),
-                            'y' => screen.redo(),
-                            '.' => index = (index + 1) % screens.len(),
-                            'n' => {
-                                screens.push(Screen::new("", &config));
-                                index = screens.len() - 1;
-                            },
-                            ',' => {
-                                if index == 0 {
-                                    index = screens.len() - 1;
-                                } else {
-                                    index -= 1;
-                                }
-                            },
-                            'o' => {
-                                if let Some(reply) = screen.prompt(&mut events, &mut stdout, "Open file:")? {
-                                    screens.push(Screen::new(&reply, &config));
-                                    index = screens.len() - 1;
-                                }
-                            },
-                            'w' | 's' | 'S' => {
-                                let should_save = 
-                                    ch != 'w' || (
-                                        screen.is_dirty() &&
-                                        screen.confirm_prompt(
-                                            &mut events, 
-                                            &mut stdout, 
-                                            "Save changes (Y/n)", 
-                                            true
-                                        )?
-                                    );
-
-                                let mut wrote: usize = 0;
-                                if should_save {
-                                    let needs_path = screen.path().as_os_str().is_empty() || ch == 'S';
-                                    let mut path = PathBuf::from(screen.path());
-
-                                    if needs_path {
-                                        let reply = screen
-                                            .prompt(
-                                                &mut events,
-                                                &mut stdout,
-                                                "Save as:"
-                                            )?
-                                            .map(PathBuf::from);
-                                        if reply.is_none() { continue; }
-                                        path = reply.unwrap();
-                                    }
-
-                                    // Try normally first...
-                                    let result = if needs_path {
-                                        screen.save_as(&path, false)
-                                    } else {
-                                        screen.save( false)
-                                    };
-
-                                    if let Err(e) = result {
-                                        // ...if it fails...
-                                        match e.kind() {
-                                            ErrorKind::Other | ErrorKind::AlreadyExists => {
-                                                // ...ask user if they want to overwrite
-                                                let overwrite = screen.confirm_prompt(
-                                                    &mut events, 
-                                                    &mut stdout,
-                                                    "Overwrite (y/N)?",
-                                                    false
-                                                )?;
-
-                                                if overwrite {
-                                                    let result = if needs_path {
-                                                        screen.save_as(&path, true)
-                                                    } else {
-                                                        screen.save( true)
-                                                    };
-                                                    
-                                                    if let Err(e) = result {
-                                                        // don't crash if we still can't write save
-                                                        screen.set_message(Message::Error(e.to_string()));
-                                                        timeout = 5;
-                                                        continue;
-                                                    } else {
-                                                        wrote = result.unwrap();
-                                                    }
-                                                }
-                                            },
-                                            _ => {
-                                                // ...show error and stop
-                                                screen.set_message(Message::Error(e.to_string()));
-                                                timeout = 5;
-                                                continue;
-                                            }
-                                        }
-                                    } else {
-                                        wrote = result.unwrap();
-                                    }
-                                }
-
-                                if ch == 'w' {
-                                    screens.remove(index);
-                                    if screens.is_empty() {
-                                        screens.push(Screen::new("", &config));
-                                    }
-                                    index = min(screens.len() - 1, index);
-                                }
-
-                                if should_save {
-                                    let m = format!("Wrote {} bytes", wrote);
-                                    screens[index].set_message(Message::Info(m));
-                                    timeout = 1;
-                                }
-                            },
-                            'p' => {
-                                if let Some(reply) = screen.prompt(&mut events, &mut stdout, "Switch to buffer:")? {
-                                    // Look for a buffer whose file name includes `reply` somewhere:
-                                    let found = screens
-                                        .iter()
-                                        .enumerate()
-                                        .find(|(_, s)| {
-                                            s.path()
-                                                .file_name()
-                                                .and_then(|o| o.to_str())
-                                                .map_or(
-                                                    false, 
-                                                    |n| n.starts_with(&reply)
-                                                )
-                                        })
-                                        .map(|i| i.0);
-
-                                    if let Some(i) = found {
-                                        index = i;
-                                    } else {
-                                        let m = format!("Buffer '{reply}' not found");
-                                        screens[index].set_message(Message::Warning(m));
-                                        timeout = 3;
-                                    }
-                                }
-                            },
-                            _ => was_valid = false
+        let (width, _) = terminal::size()?;
+        let tabs = editor.has_tabs();
+        let index = editor.index;
+        let screen = &mut editor.screens[index];
+
+        let event = match events.next() {
+            Some(event) => event?,
+            None => break
+        };
+
+        let key = match event {
+            Event::Key(key) => key,
+            _ if chord.take().is_some() => {
+                editor.current().set_message(Message::Warning(String::from("Unknown chord")));
+                continue;
+            },
+            Event::Unsupported(bytes) if bytes == PASTE_START => {
+                // Pasted text goes in as one edit, undone in one go
+                let text = input::read_paste(events)?;
+                if !text.is_empty() {
+                    screen.insert_str(&text);
+                }
+                continue;
+            },
+            Event::Mouse(MouseEvent::Press(button, x, y)) => {
+                let (x, y) = (x - 1, y - 1);
+
+                if tabs && y == 0 {
+                    // Clicked on the tab bar
+                    if let Some(i) = tabs::tab_at(&editor.screens, index, width, x) {
+                        match button {
+                            MouseButton::Left => editor.switch(i),
+                            MouseButton::Middle => editor.close(i, events, &mut out)?,
+                            _ => ()
                         }
                     }
-                    Event::Key(Key::Up) => screen.top(),
-                    Event::Key(Key::Down) => screen.bottom(),
-                    _ => was_valid = false
-                }
-
-                if !was_valid {
-                    screens[index].set_message(Message::Warning(String::from("Unknown chord")));
-                    timeout = 3;
-                }
-            } else {
-                match event? {
-                    Event::Key(Key::Char(ch)) => {
-                        if screen.overwrite {
-                            screen.overwrite(ch);
-                        } else {
-                            screen.insert(ch)
-                        }
-                    },
-                    Event::Key(Key::Insert) => {
-                        screen.overwrite = !screen.overwrite;
-                    },
-                    Event::Key(Key::Ctrl(ch)) => {
-                        if ch == 'x' && !chord {
-                            chord = true;
-                            timeout = -1;
-                            let m = String::from("Waiting for C-x chord (Esc to cancel)");
+                    continue;
+                }
+
+                // Clicking (or scrolling) another pane focuses it first
+                match editor.pane_at(x, y) {
+                    Some(i) => editor.focus(i),
+                    None => continue
+                }
+                let screen = &mut editor.screens[editor.index];
+                if button == MouseButton::WheelUp || button == MouseButton::WheelDown {
+                    screen.scroll(button == MouseButton::WheelDown, WHEEL_LINES);
+                } else if screen.is_status_row(y) {
+                    match screen.status_segment_at(x, &editor.fields) {
+                        Some(StatusSegment::Position) => goto_line(screen, events, &mut out)?,
+                        Some(StatusSegment::Ending) => screen.cycle_line_ending(),
+                        Some(StatusSegment::Name) => {
+                            let location = screen.location();
+                            let m = location
+                                .canonicalize()
+                                .unwrap_or(location)
+                                .display()
+                                .to_string();
                             screen.set_message(Message::Info(m));
+                        },
+                        _ => ()
+                    }
+                } else {
+                    screen.set_cursor(x, y);
+                    if button == MouseButton::Left {
+                        let count = match clicks {
+                            Some((cx, cy, at, n)) if (cx, cy) == (x, y) && at.elapsed() < MULTI_CLICK => n % 3 + 1,
+                            _ => 1
+                        };
+                        clicks = Some((x, y, Instant::now(), count));
+                        match count {
+                            2 => screen.select_word(),
+                            3 => screen.select_line(),
+                            _ => ()
                         }
+                    }
+                }
+                continue;
+            },
+            _ => continue
+        };
+
+        let command = if let Some(prefix) = chord.take() {
+            // Done waiting for the rest of the chord
+            screen.clear_message();
+            match editor.config.keymap.chord(prefix, key) {
+                Some(command) => command,
+                None if key == Key::Esc => continue,
+                None => {
+                    editor.current().set_message(Message::Warning(String::from("Unknown chord")));
+                    continue;
+                }
+            }
+        } else if let (Key::Char(ch), true) = (key, screen.is_viewing()) {
+            match ch {
+                ' ' => screen.page(true),
+                'b' => screen.page(false),
+                'g' => screen.top(),
+                'G' => screen.bottom(),
+                '/' => search(screen, events, &mut out)?,
+                'n' | 'N' => search_again(screen, ch == 'n'),
+                'q' => {
+                    if editor.screens.len() > 1 {
+                        editor.close(index, events, &mut out)?;
+                    } else if editor.confirm_quit(events, &mut out)? {
+                        break;
+                    }
+                },
+                _ => ()
+            }
+            continue;
+        } else {
+            match (editor.config.keymap.get(key), key) {
+                (Some(command), _) => command,
+                (None, Key::Char(ch)) => {
+                    if screen.overwrite {
+                        screen.at_each_cursor(|s| s.overwrite(ch));
+                    } else {
+                        screen.at_each_cursor(|s| s.insert(ch));
+                    }
+                    continue;
+                },
+                (None, _) => continue
+            }
+        };
+
+        match command {
+            Command::Chord => {
+                chord = Some(key);
+                let m = format!("Waiting for {} chord (Esc to cancel)", key_name(key));
+                screen.set_message(Message::Info(m));
+            },
+            Command::Quit => {
+                if editor.confirm_quit(events, &mut out)? {
+                    break;
+                }
+            },
+            Command::QuitEach => {
+                if editor.quit_each(events, &mut out)? {
+                    break;
+                }
+            },
+            Command::Suspend => terminal::suspend(&mut out)?,
+            Command::AddCursor => {
+                if screen.add_cursor() {
+                    let m = format!("{} cursors", screen.cursor_count());
+                    screen.set_message(Message::Info(m));
+                } else {
+                    let m = String::from("Nowhere else to add a cursor");
+                    screen.set_message(Message::Warning(m));
+                }
+            },
+            Command::SingleCursor => {
+                screen.single_cursor();
+            },
+            Command::Undo => screen.undo(),
+            Command::Redo => screen.redo(),
+            Command::NextBuffer => editor.switch((index + 1) % editor.screens.len()),
+            Command::NewBuffer => editor.open(""),
+            Command::PreviousBuffer => {
+                if index == 0 {
+                    editor.switch(editor.screens.len() - 1);
+                } else {
+                    editor.switch(index - 1);
+                }
+            },
+            Command::Open => {
+                if let Some(reply) = screen.prompt(events, &mut out, "Open file:")? {
+                    if reply.is_empty() {
+                        editor.open("");
+                    } else {
+                        let path = screen.resolve(&reply);
+                        editor.open(&path.to_string_lossy());
+                    }
+                }
+            },
+            Command::Directory => {
+                let prompt = format!("Working directory ({}):", screen.directory().display());
+                if let Some(reply) = screen.prompt(events, &mut out, &prompt)? {
+                    match screen.set_directory(reply.trim()) {
+                        Ok(()) => {
+                            let m = format!("Working directory is {}", screen.directory().display());
+                            screen.set_message(Message::Info(m));
+                        },
+                        Err(e) => screen.set_message(Message::Error(e.to_string()))
+                    }
+                }
+            },
+            Command::Rename => {
+                if let Some(reply) = screen.prompt(events, &mut out, "Rename buffer:")? {
+                    screen.rename(reply.trim());
+                }
+            },
+            Command::BufferList => editor.buffer_list(events, &mut out)?,
+            Command::RecentBuffer => {
+                if !editor.switch_recent() {
+                    let m = String::from("No other buffer");
+                    editor.current().set_message(Message::Warning(m));
+                }
+            },
+            Command::ShellCommand => editor.shell_command(events, &mut out)?,
+            Command::InsertDate => editor.insert_date(events, &mut out)?,
+            Command::Calculate => calculate(screen, events, &mut out)?,
+            Command::IncrementalSearch => editor.incremental_search(events, &mut out)?,
+            Command::Compile => editor.compile(events, &mut out)?,
+            Command::NextError => editor.next_error(),
+            Command::DiskDiff => editor.disk_diff(),
+            Command::Compare => editor.compare(events, &mut out)?,
+            Command::GotoDefinition => editor.goto_definition(events, &mut out)?,
+            Command::JumpBack => {
+                if !editor.jump_back() {
+                    let m = String::from("No jumps to go back to");
+                    editor.current().set_message(Message::Warning(m));
+                }
+            },
+            Command::Blame => screen.blame(),
+            Command::NextConflict | Command::PreviousConflict => {
+                if !screen.goto_conflict(command == Command::NextConflict) {
+                    screen.set_message(Message::Warning(String::from("No more conflicts")));
+                }
+            },
+            Command::ResolveConflict => {
+                let prompt = "Keep (o)urs, (t)heirs, or (b)oth?";
+                if let Some(reply) = screen.prompt(events, &mut out, prompt)? {
+                    let (ours, theirs) = match reply.trim() {
+                        "o" => (true, false),
+                        "t" => (false, true),
+                        "b" => (true, true),
+                        _ => continue
+                    };
+                    if !screen.resolve_conflict(ours, theirs) {
+                        screen.set_message(Message::Warning(String::from("Not in a merge conflict")));
+                    }
+                }
+            },
+            Command::NextChange | Command::PreviousChange => {
+                if !screen.goto_hunk(command == Command::NextChange) {
+                    screen.set_message(Message::Warning(String::from("No more changes")));
+                }
+            },
+            Command::Reopen => {
+                if !editor.reopen() {
+                    let m = String::from("No closed buffers to reopen");
+                    editor.current().set_message(Message::Warning(m));
+                }
+            },
+            Command::Save | Command::SaveAs => {
+                if let Some(saved) = save(screen, events, &mut out, command == Command::SaveAs)? {
+                    screen.set_message(saved.message());
+                }
+            },
+            Command::Close => editor.close(index, events, &mut out)?,
+            Command::Kill => editor.kill(index, events, &mut out)?,
+            Command::CloseOthers => editor.close_others(events, &mut out)?,
+            Command::GotoLine => goto_line(screen, events, &mut out)?,
+            Command::ExportHtml => export_html(screen, events, &mut out)?,
+            Command::Reformat => reformat(screen, events, &mut out)?,
+            Command::Palette => palette::run(screen, events, &mut out)?,
+            Command::Follow => {
+                match screen.toggle_follow() {
+                    Ok(true) => screen.set_message(Message::Info(String::from("Following file (C-x F to stop)"))),
+                    Ok(false) => screen.set_message(Message::Info(String::from("Stopped following file"))),
+                    Err(e) => screen.set_message(Message::Warning(e))
+                }
+            },
+            Command::ReadOnly => {
+                match screen.toggle_readonly() {
+                    Ok(true) => screen.set_message(Message::Info(String::from("Buffer is read-only"))),
+                    Ok(false) => screen.set_message(Message::Info(String::from("Buffer is editable"))),
+                    Err(e) => screen.set_message(Message::Warning(e))
+                }
+            },
+            Command::Narrow => {
+                if screen.narrow() {
+                    screen.set_message(Message::Info(String::from("Narrowed to the selected lines (C-x W to widen)")));
+                } else {
+                    screen.set_message(Message::Warning(String::from("Select the lines to narrow to")));
+                }
+            },
+            Command::Widen => {
+                if screen.widen() {
+                    screen.set_message(Message::Info(String::from("Showing the whole buffer")));
+                } else {
+                    screen.set_message(Message::Warning(String::from("Buffer isn't narrowed")));
+                }
+            },
+            Command::Protect => {
+                if screen.protect() {
+                    screen.set_message(Message::Info(String::from("Selected text protected")));
+                } else if screen.unprotect() {
+                    screen.set_message(Message::Info(String::from("Text unprotected")));
+                } else {
+                    screen.set_message(Message::Warning(String::from("Select text to protect")));
+                }
+            },
+            Command::View => {
+                let m = if screen.toggle_view() { "View mode on" } else { "View mode off" };
+                screen.set_message(Message::Info(String::from(m)));
+            },
+            Command::AlignColumns => {
+                let m = if screen.toggle_table() { "Columns aligned" } else { "Columns not aligned" };
+                screen.set_message(Message::Info(String::from(m)));
+            },
+            Command::Invisibles => {
+                let m = if screen.toggle_invisibles() { "Showing invisible characters" } else { "Hiding invisible characters" };
+                screen.set_message(Message::Info(String::from(m)));
+            },
+            Command::Whitespace => {
+                let m = if screen.toggle_whitespace() { "Showing whitespace" } else { "Hiding whitespace" };
+                screen.set_message(Message::Info(String::from(m)));
+            },
+            Command::RelativeNumbers => {
+                let m = if screen.toggle_relative_numbers() { "Relative line numbers" } else { "Absolute line numbers" };
+                screen.set_message(Message::Info(String::from(m)));
+            },
+            Command::PinHeader => {
+                match screen.toggle_header() {
+                    Some(true) => screen.set_message(Message::Info(String::from("Header row pinned"))),
+                    Some(false) => screen.set_message(Message::Info(String::from("Header row unpinned"))),
+                    None => screen.set_message(Message::Warning(String::from("Columns aren't aligned")))
+                }
+            },
+            Command::MarkdownTable => {
+                let prompt = "Table: (a)lign, insert (r)ow, insert (c)olumn, (d)elete column:";
+                if let Some(reply) = screen.prompt(events, &mut out, prompt)? {
+                    let done = match reply.trim() {
+                        "a" => screen.align_table(),
+                        "r" => screen.insert_table_row(),
+                        "c" => screen.insert_table_column(),
+                        "d" => screen.remove_table_column(),
+                        _ => continue
+                    };
+                    if !done {
+                        screen.set_message(Message::Warning(String::from("Not in a Markdown table")));
+                    }
+                }
+            },
+            Command::NextColumn | Command::PreviousColumn => {
+                if !screen.goto_column(command == Command::NextColumn) {
+                    screen.set_message(Message::Warning(String::from("No more columns")));
+                }
+            },
+            Command::LineSelect => {
+                let m = if screen.toggle_line_select() {
+                    "Selecting lines (C-x l to stop)"
+                } else {
+                    "Stopped selecting lines"
+                };
+                screen.set_message(Message::Info(String::from(m)));
+            },
+            Command::PasteIndented => editor.paste(true),
+            Command::ExpandSelection => {
+                if !screen.expand_selection() {
+                    let m = String::from("Selection can't grow any further");
+                    screen.set_message(Message::Warning(m));
+                }
+            },
+            Command::ShrinkSelection => {
+                if !screen.shrink_selection() {
+                    let m = String::from("No expansion to undo");
+                    screen.set_message(Message::Warning(m));
+                }
+            },
+            Command::Count => {
+                let m = screen.count();
+                screen.set_message(Message::Info(m));
+            },
+            Command::Describe => {
+                let m = screen.describe_cursor();
+                screen.set_message(Message::Info(m));
+            },
+            Command::StatusFields => {
+                let reply = screen.prompt(
+                    events,
+                    &mut out,
+                    "Toggle status field (e)ncoding, (s)ize, (w)ords:"
+                )?;
+                match reply.and_then(|r| r.chars().next()) {
+                    Some('e') => editor.fields.encoding = !editor.fields.encoding,
+                    Some('s') => editor.fields.size = !editor.fields.size,
+                    Some('w') => editor.fields.words = !editor.fields.words,
+                    Some(_) => {
+                        let m = String::from("Unknown status field");
+                        screen.set_message(Message::Warning(m));
                     },
-                    Event::Key(Key::Backspace) => screen.backspace(),
-                    Event::Key(Key::Delete) => screen.delete(),
-                    Event::Key(Key::Home) => screen.home(),
-                    Event::Key(Key::End) => screen.end(),
-                    Event::Key(Key::Up) => screen.move_cursor(Direction::Up),
-                    Event::Key(Key::Down) => screen.move_cursor(Direction::Down),
-                    Event::Key(Key::Left) => screen.move_cursor(Direction::Left),
-                    Event::Key(Key::Right) => screen.move_cursor(Direction::Right),
-                    Event::Key(Key::CtrlUp) => screen.select(Direction::Up),
-                    Event::Key(Key::CtrlDown) => screen.select(Direction::Down),
-                    Event::Key(Key::CtrlLeft) => screen.select(Direction::Left),
-                    Event::Key(Key::CtrlRight) => screen.select(Direction::Right),
-                    Event::Mouse(me) => {
-                        match me {
-                            MouseEvent::Press(_, x, y) => 
-                            screen.set_cursor((x - 1) as usize, (y - 1) as usize),
-                            _ => (),
-                        }
-                    },
-                    _ => ()
+                    None => ()
                 }
+            },
+            Command::SwitchBuffer => {
+                if let Some(reply) = screen.prompt(events, &mut out, "Switch to buffer:")? {
+                    if let Some(i) = editor.find(&reply) {
+                        editor.switch(i);
+                    } else {
+                        let m = format!("Buffer '{reply}' not found");
+                        editor.current().set_message(Message::Warning(m));
+                    }
+                }
+            },
+            Command::SplitBelow => editor.split(false),
+            Command::SplitRight => editor.split(true),
+            Command::NextPane => editor.next_pane(),
+            Command::ClosePane => editor.close_pane(),
+            Command::OnlyPane => editor.only_pane(),
+            Command::Top => screen.top(),
+            Command::Bottom => screen.bottom(),
+            Command::Copy => editor.copy(false),
+            Command::Cut => editor.copy(true),
+            Command::Paste => editor.paste(false),
+            Command::Yank => screen.yank(),
+            Command::YankPop => screen.yank_pop(),
+            Command::KillLine => screen.kill_line(),
+            Command::KillWholeLine => screen.kill_whole_line(),
+            Command::SortLines => sort_lines(screen, events, &mut out)?,
+            Command::ToggleComment => {
+                if !screen.toggle_comment() {
+                    screen.set_message(Message::Warning(String::from("No line comments for this filetype")));
+                }
+            },
+            Command::UpperCase | Command::LowerCase | Command::TitleCase => {
+                let changed = match command {
+                    Command::UpperCase => screen.change_text(str::to_uppercase),
+                    Command::LowerCase => screen.change_text(str::to_lowercase),
+                    _ => screen.change_text(transform::title_case)
+                };
+                if !changed {
+                    screen.set_message(Message::Warning(String::from("No selection or word to change")));
+                }
+            },
+            Command::JoinLines | Command::JoinLinesAsIs => {
+                if !screen.join_lines(command == Command::JoinLines) {
+                    screen.set_message(Message::Warning(String::from("No line to join")));
+                }
+            },
+            Command::Backspace => screen.at_each_cursor(Screen::backspace),
+            Command::Delete => screen.at_each_cursor(Screen::delete),
+            Command::Home => screen.home(),
+            Command::End => screen.end(),
+            Command::Up => screen.move_cursor(Direction::Up),
+            Command::Down => screen.move_cursor(Direction::Down),
+            Command::Left => screen.move_cursor(Direction::Left),
+            Command::Right => screen.move_cursor(Direction::Right),
+            Command::PageUp => screen.page(false),
+            Command::PageDown => screen.page(true),
+            Command::SelectUp => screen.select(Direction::Up),
+            Command::SelectDown => screen.select(Direction::Down),
+            Command::SelectLeft => screen.select(Direction::Left),
+            Command::SelectRight => screen.select(Direction::Right),
+            Command::Overwrite => screen.overwrite = !screen.overwrite,
+            Command::Tab => screen.at_each_cursor(Screen::tab),
+            Command::PreviousCell => {
+                screen.next_cell(false);
             }
         }
-
-        assert!(index < screens.len(), "screen index out-of-range");
     }
 
-    write!(stdout, "{}{}{}", termion::clear::All, termion::cursor::Goto(1, 1), termion::cursor::BlinkingBar)?;
-
     Ok(())
 }
\ No newline at end of file
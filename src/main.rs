@@ -6,7 +6,7 @@ fn main() {
     let args: Vec<String> = env::args().collect();
 
     let config = Config::build(&args).unwrap_or_else(|err| {
-        eprintln!("Error parsing arguments: {err}");
+        eprintln!("Error: {err}");
         process::exit(1);
     });
 
@@ -0,0 +1,205 @@
+use crate::width::DisplayWidth;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Default,
+    Left,
+    Center,
+    Right
+}
+
+// A `|`-delimited Markdown table, split into cells
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub indent: String,
+    pub rows: Vec<Vec<String>>,
+    pub aligns: Option<Vec<Align>> // Set if the first row is a header, followed by a separator
+}
+
+pub fn is_row(line: &str) -> bool {
+    line.trim_start().starts_with('|')
+}
+
+// The rows of the table containing `row`, if it's in one
+pub fn find(lines: &[&str], row: usize) -> Option<Range<usize>> {
+    if !is_row(lines.get(row)?) {
+        return None;
+    }
+    let start = (0..row).rev().take_while(|&r| is_row(lines[r])).last().unwrap_or(row);
+    let end = (row..lines.len()).find(|&r| !is_row(lines[r])).unwrap_or(lines.len());
+    Some(start..end)
+}
+
+// Byte offsets of the unescaped pipes in `line`
+fn pipes(line: &str) -> Vec<usize> {
+    let mut pipes = Vec::new();
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '|' {
+            pipes.push(i);
+        }
+    }
+    pipes
+}
+
+fn cells(line: &str) -> Vec<String> {
+    let pipes = pipes(line);
+    let mut cells: Vec<String> = pipes
+        .windows(2)
+        .map(|w| String::from(line[w[0] + 1..w[1]].trim()))
+        .collect();
+
+    // The closing pipe is optional
+    if let Some(&last) = pipes.last() {
+        let rest = line[last + 1..].trim();
+        if !rest.is_empty() {
+            cells.push(String::from(rest));
+        }
+    }
+    cells
+}
+
+fn parse_align(cell: &str) -> Option<Align> {
+    let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+    if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+        return None;
+    }
+    Some(match (cell.starts_with(':'), cell.ends_with(':')) {
+        (true, true) => Align::Center,
+        (true, false) => Align::Left,
+        (false, true) => Align::Right,
+        (false, false) => Align::Default
+    })
+}
+
+// Which cell the byte `byte` of `line` is in
+pub fn cell_at(line: &str, byte: usize) -> usize {
+    pipes(line).iter().filter(|&&p| p < byte).count().saturating_sub(1)
+}
+
+// Where the text of `cell` starts in a formatted `line`
+pub fn cell_start(line: &str, cell: usize) -> usize {
+    pipes(line).get(cell).map_or(line.len(), |p| (p + 2).min(line.len()))
+}
+
+impl Table {
+    pub fn parse(lines: &[&str]) -> Self {
+        let indent = lines
+            .first()
+            .map_or("", |l| &l[..l.len() - l.trim_start().len()]);
+        let mut rows: Vec<Vec<String>> = lines.iter().map(|l| cells(l)).collect();
+
+        let aligns = rows
+            .get(1)
+            .and_then(|r| r.iter().map(|c| parse_align(c)).collect::<Option<Vec<Align>>>())
+            .filter(|a| !a.is_empty());
+        if aligns.is_some() {
+            rows.remove(1);
+        }
+
+        let mut table = Table { indent: String::from(indent), rows, aligns };
+        table.pad();
+        table
+    }
+
+    pub fn columns(&self) -> usize {
+        self.rows.iter().map(Vec::len).max().unwrap_or_default()
+    }
+
+    // Give every row the same number of cells
+    fn pad(&mut self) {
+        let columns = self.columns().max(1);
+        for row in self.rows.iter_mut() {
+            row.resize(columns, String::new());
+        }
+        if let Some(aligns) = self.aligns.as_mut() {
+            aligns.resize(columns, Align::Default);
+        }
+    }
+
+    // The line a row is shown on, skipping the separator
+    pub fn line_of(&self, row: usize) -> usize {
+        if self.aligns.is_some() && row > 0 { row + 1 } else { row }
+    }
+
+    // The row shown on `line`, the separator counts as the header
+    pub fn row_of(&self, line: usize) -> usize {
+        if self.aligns.is_some() && line > 0 { line - 1 } else { line }
+    }
+
+    pub fn insert_row(&mut self, at: usize) {
+        let columns = self.columns();
+        self.rows.insert(at.min(self.rows.len()), vec![String::new(); columns]);
+    }
+
+    pub fn insert_column(&mut self, at: usize) {
+        for row in self.rows.iter_mut() {
+            row.insert(at.min(row.len()), String::new());
+        }
+        if let Some(aligns) = self.aligns.as_mut() {
+            aligns.insert(at.min(aligns.len()), Align::Default);
+        }
+    }
+
+    pub fn remove_column(&mut self, at: usize) {
+        if self.columns() <= 1 {
+            return;
+        }
+        for row in self.rows.iter_mut() {
+            row.remove(at.min(row.len() - 1));
+        }
+        if let Some(aligns) = self.aligns.as_mut() {
+            aligns.remove(at.min(aligns.len() - 1));
+        }
+    }
+
+    // Format the table with each column padded to its widest cell
+    pub fn render(&self) -> Vec<String> {
+        let columns = self.columns();
+        let widths: Vec<usize> = (0..columns)
+            .map(|c| self.rows.iter().map(|r| r[c].display_width()).max().unwrap_or_default().max(3))
+            .collect();
+
+        let render_row = |cells: Vec<String>| format!("{}| {} |", self.indent, cells.join(" | "));
+
+        let mut lines: Vec<String> = self.rows
+            .iter()
+            .map(|row| {
+                let cells = row
+                    .iter()
+                    .enumerate()
+                    .map(|(c, text)| {
+                        let pad = widths[c] - text.display_width();
+                        let align = self.aligns.as_ref().map_or(Align::Default, |a| a[c]);
+                        match align {
+                            Align::Right => format!("{}{}", " ".repeat(pad), text),
+                            Align::Center => format!("{}{}{}", " ".repeat(pad / 2), text, " ".repeat(pad - pad / 2)),
+                            _ => format!("{}{}", text, " ".repeat(pad))
+                        }
+                    })
+                    .collect();
+                render_row(cells)
+            })
+            .collect();
+
+        if let Some(aligns) = &self.aligns {
+            let cells = aligns
+                .iter()
+                .zip(widths.iter())
+                .map(|(align, &width)| match align {
+                    Align::Default => "-".repeat(width),
+                    Align::Left => format!(":{}", "-".repeat(width - 1)),
+                    Align::Center => format!(":{}:", "-".repeat(width - 2)),
+                    Align::Right => format!("{}:", "-".repeat(width - 1))
+                })
+                .collect();
+            lines.insert(1.min(lines.len()), render_row(cells));
+        }
+        lines
+    }
+}
@@ -0,0 +1,62 @@
+use crate::screen::{Message, Screen};
+use crate::{format, picker, transform};
+use termion::event::Event;
+use std::io::{self, Write};
+
+#[derive(Debug, Clone, Copy)]
+enum Command {
+    Base64Encode,
+    Base64Decode,
+    UrlEncode,
+    UrlDecode,
+    HexDump,
+    HexUndump,
+    PrettyJson,
+    MinifyJson,
+    PrettyXml,
+    MinifyXml
+}
+
+const COMMANDS: &[(&str, Command)] = &[
+    ("Base64 encode", Command::Base64Encode),
+    ("Base64 decode", Command::Base64Decode),
+    ("URL encode", Command::UrlEncode),
+    ("URL decode", Command::UrlDecode),
+    ("Hex dump", Command::HexDump),
+    ("Hex undump", Command::HexUndump),
+    ("Pretty-print JSON", Command::PrettyJson),
+    ("Minify JSON", Command::MinifyJson),
+    ("Pretty-print XML", Command::PrettyXml),
+    ("Minify XML", Command::MinifyXml)
+];
+
+fn execute(screen: &mut Screen, command: Command) -> Result<(), String> {
+    let indent = screen.indent_unit();
+    match command {
+        Command::Base64Encode => screen.reformat(transform::base64_encode),
+        Command::Base64Decode => screen.reformat(transform::base64_decode),
+        Command::UrlEncode => screen.reformat(transform::url_encode),
+        Command::UrlDecode => screen.reformat(transform::url_decode),
+        Command::HexDump => screen.reformat(transform::hex_dump),
+        Command::HexUndump => screen.reformat(transform::hex_undump),
+        Command::PrettyJson => screen.reformat(|s| format::json(s, Some(&indent))),
+        Command::MinifyJson => screen.reformat(|s| format::json(s, None)),
+        Command::PrettyXml => screen.reformat(|s| format::xml(s, Some(&indent))),
+        Command::MinifyXml => screen.reformat(|s| format::xml(s, None))
+    }
+}
+
+// Let the user find a command by name and run it on `screen`,
+// transforms apply to the selection or the whole buffer
+pub fn run<T, I>(screen: &mut Screen, events: &mut I, out: &mut T) -> io::Result<()>
+    where T : Write
+        , I : Iterator<Item = io::Result<Event>>
+{
+    let names: Vec<String> = COMMANDS.iter().map(|(name, _)| String::from(*name)).collect();
+    if let Some(i) = picker::filter(events, out, "Command", &names, screen.colors())? {
+        if let Err(e) = execute(screen, COMMANDS[i].1) {
+            screen.set_message(Message::Warning(e));
+        }
+    }
+    Ok(())
+}
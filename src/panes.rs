@@ -0,0 +1,189 @@
+use crate::screen::{Rect, View};
+use std::mem;
+
+// Panes smaller than this can't be split
+const MIN_WIDTH: u16 = 20;
+const MIN_HEIGHT: u16 = 4;
+
+// A part of the terminal showing a screen. Only the focused pane's screen is scrolled
+// and moved around directly, the others keep where they were here
+#[derive(Clone)]
+pub struct Pane {
+    pub screen: usize,
+    pub view: Option<View> // Unset to show the screen as it is
+}
+
+// How the terminal is divided between panes, splitting in two as often as needed
+enum Layout {
+    Pane(Pane),
+    Split { vertical: bool, first: Box<Layout>, second: Box<Layout> } // Vertical splits are side by side
+}
+
+impl Layout {
+    fn count(&self) -> usize {
+        match self {
+            Layout::Pane(_) => 1,
+            Layout::Split { first, second, .. } => first.count() + second.count()
+        }
+    }
+
+    fn panes<'a>(&'a self, panes: &mut Vec<&'a Pane>) {
+        match self {
+            Layout::Pane(pane) => panes.push(pane),
+            Layout::Split { first, second, .. } => {
+                first.panes(panes);
+                second.panes(panes);
+            }
+        }
+    }
+
+    fn panes_mut<'a>(&'a mut self, panes: &mut Vec<&'a mut Pane>) {
+        match self {
+            Layout::Pane(pane) => panes.push(pane),
+            Layout::Split { first, second, .. } => {
+                first.panes_mut(panes);
+                second.panes_mut(panes);
+            }
+        }
+    }
+
+    // Divide `area` between the panes, with a column between ones side by side
+    fn place(&self, area: Rect, areas: &mut Vec<Rect>, separators: &mut Vec<Rect>) {
+        match self {
+            Layout::Pane(_) => areas.push(area),
+            Layout::Split { vertical: true, first, second } => {
+                let left = area.width.saturating_sub(1) / 2;
+                first.place(Rect { width: left, ..area }, areas, separators);
+                separators.push(Rect { x: area.x + left, width: 1, ..area });
+                let right = Rect { x: area.x + left + 1, width: area.width.saturating_sub(left + 1), ..area };
+                second.place(right, areas, separators);
+            },
+            Layout::Split { vertical: false, first, second } => {
+                let top = area.height / 2;
+                first.place(Rect { height: top, ..area }, areas, separators);
+                second.place(Rect { y: area.y + top, height: area.height - top, ..area }, areas, separators);
+            }
+        }
+    }
+
+    // Replace pane `target`, counting panes in order with `seen`, by a split of two copies
+    fn split(self, target: usize, seen: &mut usize, vertical: bool) -> Layout {
+        match self {
+            Layout::Pane(pane) => {
+                *seen += 1;
+                if *seen - 1 == target {
+                    let first = Box::new(Layout::Pane(pane.clone()));
+                    Layout::Split { vertical, first, second: Box::new(Layout::Pane(pane)) }
+                } else {
+                    Layout::Pane(pane)
+                }
+            },
+            Layout::Split { vertical: v, first, second } => {
+                let first = Box::new(first.split(target, seen, vertical));
+                let second = Box::new(second.split(target, seen, vertical));
+                Layout::Split { vertical: v, first, second }
+            }
+        }
+    }
+
+    // Leave out pane `target`, the other side of its split takes its place
+    fn without(self, target: usize, seen: &mut usize) -> Option<Layout> {
+        match self {
+            Layout::Pane(pane) => {
+                *seen += 1;
+                if *seen - 1 == target { None } else { Some(Layout::Pane(pane)) }
+            },
+            Layout::Split { vertical, first, second } => {
+                match (first.without(target, seen), second.without(target, seen)) {
+                    (Some(first), Some(second)) => {
+                        Some(Layout::Split { vertical, first: Box::new(first), second: Box::new(second) })
+                    },
+                    (Some(layout), None) | (None, Some(layout)) => Some(layout),
+                    (None, None) => None
+                }
+            }
+        }
+    }
+}
+
+// The panes on screen and which one has focus, counted from the top left
+pub struct Panes {
+    layout: Layout,
+    pub focus: usize
+}
+
+impl Panes {
+    pub fn new(screen: usize) -> Self {
+        Panes { layout: Layout::Pane(Pane { screen, view: None }), focus: 0 }
+    }
+
+    pub fn count(&self) -> usize {
+        self.layout.count()
+    }
+
+    pub fn get(&self, index: usize) -> &Pane {
+        let mut panes = Vec::new();
+        self.layout.panes(&mut panes);
+        panes[index]
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> &mut Pane {
+        let mut panes = Vec::new();
+        self.layout.panes_mut(&mut panes);
+        panes.swap_remove(index)
+    }
+
+    pub fn focused(&mut self) -> &mut Pane {
+        self.get_mut(self.focus)
+    }
+
+    // Each pane's part of `area` in order, and the columns between them
+    pub fn areas(&self, area: Rect) -> (Vec<Rect>, Vec<Rect>) {
+        let (mut areas, mut separators) = (Vec::new(), Vec::new());
+        self.layout.place(area, &mut areas, &mut separators);
+        (areas, separators)
+    }
+
+    // Split the focused pane, which takes up `area`, keeping focus on the top or left half.
+    // Returns `false` if it's too small, side by side panes need a column between them too
+    pub fn split(&mut self, area: Rect, vertical: bool) -> bool {
+        let fits = if vertical { area.width > MIN_WIDTH * 2 } else { area.height >= MIN_HEIGHT * 2 };
+        if !fits {
+            return false;
+        }
+        let layout = mem::replace(&mut self.layout, Layout::Pane(Pane { screen: 0, view: None }));
+        self.layout = layout.split(self.focus, &mut 0, vertical);
+        true
+    }
+
+    // Close the focused pane, returns `false` if it's the only one
+    pub fn close(&mut self) -> bool {
+        if self.count() == 1 {
+            return false;
+        }
+        let layout = mem::replace(&mut self.layout, Layout::Pane(Pane { screen: 0, view: None }));
+        self.layout = layout.without(self.focus, &mut 0).expect("another pane is left");
+        self.focus = self.focus.min(self.count() - 1);
+        true
+    }
+
+    // Close all but the focused pane
+    pub fn only(&mut self) {
+        let pane = self.focused().clone();
+        *self = Panes { layout: Layout::Pane(pane), focus: 0 };
+    }
+
+    // Screen `removed` was closed, so panes showing it show `replacement` as it is instead,
+    // and ones showing later screens follow them down
+    pub fn remove_screen(&mut self, removed: usize, replacement: usize) {
+        let mut panes = Vec::new();
+        self.layout.panes_mut(&mut panes);
+        for pane in panes {
+            if pane.screen == removed {
+                *pane = Pane { screen: replacement, view: None };
+            } else if pane.screen > removed {
+                pane.screen -= 1;
+            }
+        }
+    }
+}
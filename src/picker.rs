@@ -0,0 +1,144 @@
+use crate::screen::colors::Colors;
+use crate::screen::truncate;
+use termion::event::{Event, Key};
+use crate::terminal;
+use crate::width::DisplayWidth;
+use termion as t;
+use std::cmp::min;
+use std::io::{self, Write};
+
+
+fn draw<W>(out: &mut W, title: &str, items: &[&String], selected: usize, colors: &Colors) -> io::Result<()>
+    where W : Write
+{
+    let (width, height) = terminal::size()?;
+    let width = width as usize;
+    let rows = height as usize - 2;
+
+    let header = |out: &mut W, y: u16, text: &str| {
+        let text = truncate(text, width - 1);
+        let pad = width - 1 - text.display_width();
+        write!(out, "{}{}{} {}{:pad$}{}{}",
+            t::cursor::Goto(1, y),
+            t::color::Bg(colors.status_bg),
+            t::color::Fg(colors.status_fg),
+            text,
+            "",
+            t::color::Bg(t::color::Reset),
+            t::color::Fg(t::color::Reset)
+        )
+    };
+
+    write!(out, "{}", t::clear::All)?;
+    header(out, 1, title)?;
+
+    // Scroll so that the selected item is visible
+    let first = (selected + 1).saturating_sub(rows);
+    for (i, item) in items.iter().enumerate().skip(first).take(rows) {
+        let text = truncate(item, width - 1);
+        let pad = width - 1 - text.display_width();
+        write!(out, "{}", t::cursor::Goto(1, (i - first + 2) as u16))?;
+        if i == selected {
+            write!(out, "{}{} {}{:pad$}{}{}",
+                t::color::Bg(colors.selection_bg),
+                t::color::Fg(colors.selection_fg),
+                text,
+                "",
+                t::color::Bg(t::color::Reset),
+                t::color::Fg(t::color::Reset)
+            )?;
+        } else {
+            write!(out, " {}", text)?;
+        }
+    }
+
+    header(out, height, "Enter: choose, Esc: cancel")?;
+    out.flush()
+}
+
+// Let the user narrow down `items` by typing, then choose one. Returns its index in `items`
+// or `None` if they cancelled
+pub fn filter<T, I>(events: &mut I, out: &mut T, title: &str, items: &[String], colors: &Colors)
+    -> io::Result<Option<usize>>
+    where T : Write
+        , I : Iterator<Item = io::Result<Event>>
+{
+    let mut query = String::new();
+    let mut selected = 0;
+    let mut chosen = None;
+    write!(out, "{}", t::cursor::Hide)?;
+    loop {
+        let needle = query.to_lowercase();
+        let matches: Vec<usize> = (0..items.len())
+            .filter(|&i| items[i].to_lowercase().contains(&needle))
+            .collect();
+        selected = min(selected, matches.len().saturating_sub(1));
+
+        let visible: Vec<&String> = matches.iter().map(|&i| &items[i]).collect();
+        draw(out, &format!("{}: {}", title, query), &visible, selected, colors)?;
+
+        let event = match events.next() {
+            Some(event) => event?,
+            None => break
+        };
+
+        match event {
+            Event::Key(Key::Up) => selected = selected.saturating_sub(1),
+            Event::Key(Key::Down) => selected += 1,
+            Event::Key(Key::Char('\n')) => {
+                chosen = matches.get(selected).copied();
+                break;
+            },
+            Event::Key(Key::Char(c)) => {
+                query.push(c);
+                selected = 0;
+            },
+            Event::Key(Key::Backspace) => {
+                query.pop();
+            },
+            Event::Key(Key::Esc) => break,
+            _ => ()
+        }
+    }
+
+    write!(out, "{}", t::cursor::Show)?;
+    Ok(chosen)
+}
+
+// Let the user choose one of `items` from a full-screen list, returns its index
+// or `None` if they cancelled
+pub fn pick<T, I>(events: &mut I, out: &mut T, title: &str, items: &[String], colors: &Colors)
+    -> io::Result<Option<usize>>
+    where T : Write
+        , I : Iterator<Item = io::Result<Event>>
+{
+    if items.is_empty() {
+        return Ok(None);
+    }
+
+    let mut selected = 0;
+    let mut chosen = None;
+    write!(out, "{}", t::cursor::Hide)?;
+    loop {
+        draw(out, title, &items.iter().collect::<Vec<&String>>(), selected, colors)?;
+
+        let event = match events.next() {
+            Some(event) => event?,
+            None => break
+        };
+
+        match event {
+            Event::Key(Key::Up) => selected = selected.saturating_sub(1),
+            Event::Key(Key::Down) => selected = min(selected + 1, items.len() - 1),
+            Event::Key(Key::Char('\n')) => {
+                chosen = Some(selected);
+                break;
+            },
+            Event::Key(Key::Esc) | Event::Key(Key::Char('q')) => break,
+            _ => ()
+        }
+    }
+
+    write!(out, "{}", t::cursor::Show)?;
+    Ok(chosen)
+}
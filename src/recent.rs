@@ -0,0 +1,40 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// How many files are remembered
+const LIMIT: usize = 9;
+
+// Where recently opened files are listed, most recent first
+pub fn path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_STATE_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".local").join("state")))?;
+    Some(base.join("ted").join("recent"))
+}
+
+pub fn load() -> Vec<String> {
+    path()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .map(|s| s.lines().filter(|l| !l.is_empty()).map(String::from).collect())
+        .unwrap_or_default()
+}
+
+// Move `location` to the top of the list. It's only a convenience, so errors are ignored
+pub fn record(location: &str) {
+    let path = match path() {
+        Some(p) => p,
+        None => return
+    };
+
+    let mut files = load();
+    files.retain(|f| f != location);
+    files.insert(0, String::from(location));
+    files.truncate(LIMIT);
+
+    if let Some(directory) = path.parent() {
+        let _ = fs::create_dir_all(directory);
+    }
+    let _ = fs::write(path, files.join("\n") + "\n");
+}
@@ -0,0 +1,114 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+// A file on another machine, edited through a local copy that's transferred with sftp
+pub struct Remote {
+    pub host: String, // Including the user, like `user@host`
+    pub path: String,
+    pub local: PathBuf, // The local copy
+    pub connected: bool // Whether the last transfer worked
+}
+
+// Split `user@host:/path` (or `host:/path`) into host and path
+pub fn parse(spec: &str) -> Option<(&str, &str)> {
+    let (host, path) = spec.split_once(':')?;
+    let valid = !host.is_empty()
+        && !path.is_empty()
+        && !host.contains('/')
+        && (host.contains('@') || path.starts_with('/'));
+    if valid { Some((host, path)) } else { None }
+}
+
+// Quote `s` for an sftp batch command
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Run sftp commands against `host` without prompting, since the terminal is ours
+fn sftp(host: &str, commands: &str) -> io::Result<()> {
+    let mut child = Command::new("sftp")
+        .args(["-q", "-o", "BatchMode=yes", "-b", "-", host])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| io::Error::new(e.kind(), format!("Couldn't run sftp: {}", e)))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(commands.as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let reason = match stderr.lines().find(|l| !l.trim().is_empty()) {
+        Some(l) => format!("{}: {}", host, l.trim()),
+        None => format!("{}: sftp failed ({})", host, output.status)
+    };
+    // Not `Other`, which saving takes to mean the file changed on disk
+    Err(io::Error::new(io::ErrorKind::NotConnected, reason))
+}
+
+impl Remote {
+    // Pick where the local copy of `spec` goes, keeping the file name for filetype detection
+    pub fn new(spec: &str) -> Option<Self> {
+        let (host, path) = parse(spec)?;
+        let name = Path::new(path).file_name().map_or("file", |n| n.to_str().unwrap_or("file"));
+        let directory = std::env::temp_dir().join(format!(
+            "ted-{}-{}",
+            process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        Some(Remote {
+            host: String::from(host),
+            path: String::from(path),
+            local: directory.join(name),
+            connected: false
+        })
+    }
+
+    pub fn spec(&self) -> String {
+        format!("{}:{}", self.host, self.path)
+    }
+
+    // Download the file, a missing one is left to be created on save
+    pub fn fetch(&mut self) -> io::Result<()> {
+        if let Some(directory) = self.local.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let local = self.local.to_string_lossy();
+
+        // A `-` prefix lets the batch carry on when the file doesn't exist
+        let commands = format!("-get {} {}\n", quote(&self.path), quote(&local));
+        self.connected = false;
+        sftp(&self.host, &commands)?;
+        self.connected = true;
+        Ok(())
+    }
+
+    // Upload the local copy
+    pub fn store(&mut self) -> io::Result<()> {
+        let local = self.local.to_string_lossy();
+        let commands = format!("put {} {}\n", quote(&local), quote(&self.path));
+        let result = sftp(&self.host, &commands);
+        self.connected = result.is_ok();
+        result
+    }
+}
+
+impl Drop for Remote {
+    fn drop(&mut self) {
+        if let Some(directory) = self.local.parent() {
+            let _ = fs::remove_dir_all(directory);
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use crate::config::{ConfigFile, Value};
+use crate::highlight::Style;
+use termion::color::Color;
+use std::{fmt, fs, io};
+
+// Themes that come with the editor, a theme file with the same name takes their place
+const THEMES: &[(&str, &str)] = &[
+    ("dark", ""),
+    ("light", r##"
+        [colors]
+        line_bg = "#e5e5e6"
+        line_fg = "#383a42"
+        gutter_fg = "#a0a1a7"
+        status_bg = "#c9c9cc"
+        status_fg = "#383a42"
+        selection_bg = "#4078f2"
+        selection_fg = "#fafafa"
+        match_bg = "#f0d58c"
+        whitespace_fg = "#c2c2c3"
+        keyword_fg = "#a626a4"
+        type_fg = "#c18401"
+        string_fg = "#50a14f"
+        number_fg = "#986801"
+        comment_fg = "#a0a1a7"
+        heading_fg = "#4078f2"
+        emphasis_fg = "#e45649"
+        link_fg = "#0184bc"
+        overflow_bg = "#4078f2"
+        overlong_bg = "#f5c6c6"
+        overlong_fg = "#e45649"
+        protected_bg = "#d7d7ef"
+        invisible_fg = "#a626a4"
+        added_fg = "#50a14f"
+        modified_fg = "#c18401"
+        deleted_fg = "#e45649"
+        blame_fg = "#a0a1a7"
+        conflict_marker_bg = "#f2c4c4"
+        conflict_ours_bg = "#d4ecd6"
+        conflict_base_bg = "#dcdcdc"
+        conflict_theirs_bg = "#d3def5"
+        separator_fg = "#c9c9cc"
+        tab_bg = "#dcdcdd"
+        tab_fg = "#696c77"
+        section_fg = "#c18401"
+        diff_added_bg = "#d4ecd6"
+        diff_removed_bg = "#f2d0d0"
+        diff_changed_bg = "#f3e7c0"
+        diff_filler_bg = "#e5e5e6"
+        diff_number_fg = "#a0a1a7"
+        warning_bg = "#c18401"
+        warning_fg = "#fafafa"
+        error_bg = "#e45649"
+        error_fg = "#fafafa"
+    "##)
+];
+
+// A color from the config file, either `"#rrggbb"` or a terminal color number from 0 to 255
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Paint {
+    Ansi(u8),
+    Rgb(u8, u8, u8)
+}
+
+impl Paint {
+    pub fn parse(value: &Value) -> Option<Self> {
+        match value {
+            Value::Integer(n) => u8::try_from(*n).ok().map(Paint::Ansi),
+            Value::String(s) => {
+                let hex = s.strip_prefix('#').filter(|h| h.len() == 6 && h.is_ascii())?;
+                let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+                Some(Paint::Rgb(channel(0)?, channel(2)?, channel(4)?))
+            },
+            Value::Boolean(_) => None
+        }
+    }
+}
+
+// Written out here rather than through termion's colors, which leave themselves out while
+// `NO_COLOR` is set. Whether the terminal gets them is up to `depth`
+impl Color for Paint {
+    fn write_fg(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Paint::Ansi(n) => write!(f, "\x1b[38;5;{}m", n),
+            Paint::Rgb(r, g, b) => write!(f, "\x1b[38;2;{};{};{}m", r, g, b)
+        }
+    }
+
+    fn write_bg(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Paint::Ansi(n) => write!(f, "\x1b[48;5;{}m", n),
+            Paint::Rgb(r, g, b) => write!(f, "\x1b[48;2;{};{};{}m", r, g, b)
+        }
+    }
+}
+
+// Colors of the screen's current line, status line, highlights, markers and syntax, and
+// of the tabs, lists and views around it, set under `[colors]`
+#[derive(Debug, Clone, Copy)]
+pub struct Colors {
+    pub line_bg: Paint,
+    pub line_fg: Paint,
+    pub gutter_fg: Paint, // Numbers of the other lines
+    pub status_bg: Paint,
+    pub status_fg: Paint,
+    pub selection_bg: Paint,
+    pub selection_fg: Paint,
+    pub match_bg: Paint,
+    pub whitespace_fg: Paint, // Glyphs standing in for spaces and tabs
+    pub keyword_fg: Paint,
+    pub type_fg: Paint,
+    pub string_fg: Paint,
+    pub number_fg: Paint,
+    pub comment_fg: Paint,
+    pub heading_fg: Paint,
+    pub emphasis_fg: Paint,
+    pub link_fg: Paint,
+    pub overflow_bg: Paint, // Where a line goes on past the screen's edge
+    pub overlong_bg: Paint, // Text past the line length limit
+    pub overlong_fg: Paint, // Numbers of lines that go past it
+    pub protected_bg: Paint, // Text that can't be edited
+    pub invisible_fg: Paint, // Invisible characters shown as escapes
+    pub added_fg: Paint, // Git markers beside the line numbers
+    pub modified_fg: Paint,
+    pub deleted_fg: Paint,
+    pub blame_fg: Paint,
+    pub conflict_marker_bg: Paint, // Regions of merge conflicts
+    pub conflict_ours_bg: Paint,
+    pub conflict_base_bg: Paint,
+    pub conflict_theirs_bg: Paint,
+    pub separator_fg: Paint, // Line between panes side by side
+    pub tab_bg: Paint, // Other tabs, the current one has the status line's colors
+    pub tab_fg: Paint,
+    pub section_fg: Paint, // Headings on the dashboard
+    pub diff_added_bg: Paint, // Lines of the comparison view
+    pub diff_removed_bg: Paint,
+    pub diff_changed_bg: Paint,
+    pub diff_filler_bg: Paint, // Across from lines the other side doesn't have
+    pub diff_number_fg: Paint,
+    pub warning_bg: Paint, // Messages in the status line
+    pub warning_fg: Paint,
+    pub error_bg: Paint,
+    pub error_fg: Paint
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Colors {
+            line_bg: Paint::Rgb(39, 39, 39),
+            line_fg: Paint::Ansi(15),
+            gutter_fg: Paint::Rgb(39, 39, 39),
+            status_bg: Paint::Rgb(84, 84, 84),
+            status_fg: Paint::Ansi(7),
+            selection_bg: Paint::Rgb(184, 184, 184),
+            selection_fg: Paint::Rgb(34, 34, 34),
+            match_bg: Paint::Rgb(110, 90, 30),
+            whitespace_fg: Paint::Rgb(80, 80, 80),
+            keyword_fg: Paint::Rgb(198, 120, 221),
+            type_fg: Paint::Rgb(229, 192, 123),
+            string_fg: Paint::Rgb(152, 195, 121),
+            number_fg: Paint::Rgb(209, 154, 102),
+            comment_fg: Paint::Rgb(127, 132, 142),
+            heading_fg: Paint::Rgb(97, 175, 239),
+            emphasis_fg: Paint::Rgb(224, 108, 117),
+            link_fg: Paint::Rgb(86, 182, 194),
+            overflow_bg: Paint::Ansi(4),
+            overlong_bg: Paint::Rgb(95, 30, 30),
+            overlong_fg: Paint::Rgb(220, 80, 80),
+            protected_bg: Paint::Rgb(45, 45, 70),
+            invisible_fg: Paint::Rgb(230, 110, 200),
+            added_fg: Paint::Rgb(80, 200, 120),
+            modified_fg: Paint::Rgb(220, 180, 60),
+            deleted_fg: Paint::Rgb(220, 80, 80),
+            blame_fg: Paint::Rgb(120, 120, 120),
+            conflict_marker_bg: Paint::Rgb(90, 40, 40),
+            conflict_ours_bg: Paint::Rgb(30, 60, 40),
+            conflict_base_bg: Paint::Rgb(50, 50, 50),
+            conflict_theirs_bg: Paint::Rgb(30, 45, 75),
+            separator_fg: Paint::Rgb(84, 84, 84),
+            tab_bg: Paint::Rgb(39, 39, 39),
+            tab_fg: Paint::Rgb(150, 150, 150),
+            section_fg: Paint::Rgb(220, 180, 60),
+            diff_added_bg: Paint::Rgb(30, 60, 40),
+            diff_removed_bg: Paint::Rgb(70, 35, 35),
+            diff_changed_bg: Paint::Rgb(70, 60, 25),
+            diff_filler_bg: Paint::Rgb(39, 39, 39),
+            diff_number_fg: Paint::Rgb(120, 120, 120),
+            warning_bg: Paint::Rgb(230, 150, 0),
+            warning_fg: Paint::Ansi(7),
+            error_bg: Paint::Rgb(200, 0, 0),
+            error_fg: Paint::Ansi(7)
+        }
+    }
+}
+
+impl Colors {
+    // The theme named by `theme` under `[display]`, then any colors set under `[colors]`
+    pub fn build(file: &ConfigFile) -> Result<Self, String> {
+        let mut colors = Colors::default();
+        if let Some(name) = file.string("display", "theme") {
+            colors.apply(&Colors::theme(name)?)?;
+        }
+        colors.apply(file)?;
+        Ok(colors)
+    }
+
+    // A theme's `[colors]`, from `themes/<name>.toml` beside the config file or else
+    // built in
+    fn theme(name: &str) -> Result<ConfigFile, String> {
+        let path = ConfigFile::path()
+            .and_then(|p| Some(p.parent()?.join("themes").join(format!("{}.toml", name))));
+        if let Some(path) = path {
+            match fs::read_to_string(&path) {
+                Ok(s) => return ConfigFile::parse(&s).map_err(|e| format!("{}:{}", path.display(), e)),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+                Err(e) => return Err(format!("{}: {}", path.display(), e))
+            }
+        }
+
+        match THEMES.iter().find(|(n, _)| *n == name) {
+            Some((_, text)) => ConfigFile::parse(text),
+            None => Err(format!("Unknown theme '{}'", name))
+        }
+    }
+
+    fn apply(&mut self, file: &ConfigFile) -> Result<(), String> {
+        for (key, paint) in [
+            ("line_bg", &mut self.line_bg),
+            ("line_fg", &mut self.line_fg),
+            ("gutter_fg", &mut self.gutter_fg),
+            ("status_bg", &mut self.status_bg),
+            ("status_fg", &mut self.status_fg),
+            ("selection_bg", &mut self.selection_bg),
+            ("selection_fg", &mut self.selection_fg),
+            ("match_bg", &mut self.match_bg),
+            ("whitespace_fg", &mut self.whitespace_fg),
+            ("keyword_fg", &mut self.keyword_fg),
+            ("type_fg", &mut self.type_fg),
+            ("string_fg", &mut self.string_fg),
+            ("number_fg", &mut self.number_fg),
+            ("comment_fg", &mut self.comment_fg),
+            ("heading_fg", &mut self.heading_fg),
+            ("emphasis_fg", &mut self.emphasis_fg),
+            ("link_fg", &mut self.link_fg),
+            ("overflow_bg", &mut self.overflow_bg),
+            ("overlong_bg", &mut self.overlong_bg),
+            ("overlong_fg", &mut self.overlong_fg),
+            ("protected_bg", &mut self.protected_bg),
+            ("invisible_fg", &mut self.invisible_fg),
+            ("added_fg", &mut self.added_fg),
+            ("modified_fg", &mut self.modified_fg),
+            ("deleted_fg", &mut self.deleted_fg),
+            ("blame_fg", &mut self.blame_fg),
+            ("conflict_marker_bg", &mut self.conflict_marker_bg),
+            ("conflict_ours_bg", &mut self.conflict_ours_bg),
+            ("conflict_base_bg", &mut self.conflict_base_bg),
+            ("conflict_theirs_bg", &mut self.conflict_theirs_bg),
+            ("separator_fg", &mut self.separator_fg),
+            ("tab_bg", &mut self.tab_bg),
+            ("tab_fg", &mut self.tab_fg),
+            ("section_fg", &mut self.section_fg),
+            ("diff_added_bg", &mut self.diff_added_bg),
+            ("diff_removed_bg", &mut self.diff_removed_bg),
+            ("diff_changed_bg", &mut self.diff_changed_bg),
+            ("diff_filler_bg", &mut self.diff_filler_bg),
+            ("diff_number_fg", &mut self.diff_number_fg),
+            ("warning_bg", &mut self.warning_bg),
+            ("warning_fg", &mut self.warning_fg),
+            ("error_bg", &mut self.error_bg),
+            ("error_fg", &mut self.error_fg)
+        ] {
+            if let Some(value) = file.get("colors", key) {
+                *paint = Paint::parse(value).ok_or_else(|| {
+                    format!("Invalid color for {}, expected \"#rrggbb\" or a number from 0 to 255", key)
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    // The text color for `style`, normal text keeps the line's own
+    pub fn style(&self, style: Style) -> Option<Paint> {
+        match style {
+            Style::Normal => None,
+            Style::Keyword => Some(self.keyword_fg),
+            Style::Type => Some(self.type_fg),
+            Style::String => Some(self.string_fg),
+            Style::Number => Some(self.number_fg),
+            Style::Comment => Some(self.comment_fg),
+            Style::Heading => Some(self.heading_fg),
+            Style::Emphasis => Some(self.emphasis_fg),
+            Style::Link => Some(self.link_fg)
+        }
+    }
+}
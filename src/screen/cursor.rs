@@ -1,9 +1,10 @@
-use crate::buffer::Buffer;
+use crate::buffer::{Buffer, Point};
 use crate::buffer::line::{Line, ColumnIndex};
 use unicode_segmentation::GraphemeCursor;
-use unicode_width::UnicodeWidthStr;
+use crate::width::DisplayWidth;
 use std::cmp::min;
 
+#[derive(Clone, Copy)]
 pub enum Direction {
     Up,
     Down,
@@ -21,6 +22,12 @@ pub struct Cursor {
     desired_column: usize // Column index (actual)
 }
 
+impl Default for Cursor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Cursor {
     pub fn new() -> Self {
         Cursor {
@@ -35,7 +42,7 @@ impl Cursor {
 
     pub fn from(buf: &Buffer, x: usize, y: usize) -> Self {
         let line = buf.line(y).expect("No such line");
-        let index = Cursor::find_column(line, x);
+        let index = Cursor::find_column(line, x, buf.settings.tab_width);
         let offset = Cursor::offset(y, buf) + index.byte;
         Cursor {
             row: y,
@@ -47,7 +54,32 @@ impl Cursor {
         }
     }
 
-    fn find<'a, T>(line: &'a Line, f: T) -> ColumnIndex<'a>
+    // Cursor at byte offset `byte` of line `y`, snapped to a grapheme boundary
+    pub fn from_byte(buf: &Buffer, byte: usize, y: usize) -> Self {
+        let line = buf.line(y).expect("No such line");
+        let tab_width = buf.settings.tab_width;
+        let index = if byte >= line.text.len() {
+            Cursor::get_last_index(line, tab_width)
+        } else {
+            Cursor::find(line, tab_width, |i| i.byte + i.grapheme.len() > byte)
+        };
+        let offset = Cursor::offset(y, buf) + index.byte;
+        Cursor {
+            row: y,
+            column: index.column,
+            byte: index.byte,
+            index: index.index,
+            offset,
+            desired_column: index.column
+        }
+    }
+
+    // Where the cursor is, as a point in the buffer
+    pub fn point(&self) -> Point {
+        Point { x: self.byte, y: self.row }
+    }
+
+    fn find<'a, T>(line: &'a Line, tab_width: usize, f: T) -> ColumnIndex<'a>
         where T : Fn(&ColumnIndex) -> bool 
     {
         let mut previous = ColumnIndex {
@@ -58,38 +90,38 @@ impl Cursor {
             grapheme: ""
         };
 
-        for i in line.column_indices() {
+        for i in line.column_indices(tab_width) {
             if f(&i) {
                 return i;
             }
             previous = i;
         }
 
-        return previous;
+        previous
     }
     
-    fn get_last_index(line: &Line) -> ColumnIndex {
+    fn get_last_index(line: &Line, tab_width: usize) -> ColumnIndex<'_> {
         ColumnIndex {
             byte: line.text.len(),
             width: 0,
-            column: line.width,
+            column: line.width(tab_width),
             index: line.size,
             grapheme: ""
         }
     }
 
-    fn find_column(line: &Line, column: usize) -> ColumnIndex {
-        if column >= line.width {
-            return Cursor::get_last_index(line);
+    fn find_column(line: &Line, column: usize, tab_width: usize) -> ColumnIndex<'_> {
+        if column >= line.width(tab_width) {
+            return Cursor::get_last_index(line, tab_width);
         }
-        Cursor::find(line, |i| i.column <= column && column < i.column + i.width)
+        Cursor::find(line, tab_width, |i| i.column <= column && column < i.column + i.width)
     }
 
-    fn find_index(line: &Line, index: usize) -> ColumnIndex {
+    fn find_index(line: &Line, index: usize, tab_width: usize) -> ColumnIndex<'_> {
         if index >= line.size {
-            return Cursor::get_last_index(line);
+            return Cursor::get_last_index(line, tab_width);
         }
-        Cursor::find(line, |i| i.index == index)
+        Cursor::find(line, tab_width, |i| i.index == index)
     }
 
     fn check_bounds(&self, buf: &Buffer) {
@@ -97,7 +129,7 @@ impl Cursor {
         assert!(self.row < line_count, "Row out-of-bounds");
 
         let line = buf.line(self.row).unwrap();
-        assert!(self.column <= line.width, "Column out-of-bounds");
+        assert!(self.column <= line.width(buf.settings.tab_width), "Column out-of-bounds");
         assert!(self.byte <= line.text.len(), "Offset out-of-bounds");
         assert!(self.index <= line.size, "Index out-of-bounds");
     }
@@ -116,7 +148,7 @@ impl Cursor {
                     self.row -= steps;
 
                     let line = buf.line(self.row).unwrap();
-                    let index = Cursor::find_column(line, self.desired_column);
+                    let index = Cursor::find_column(line, self.desired_column, buf.settings.tab_width);
                     self.column = index.column;
                     self.byte = index.byte;
                     self.index = index.index;
@@ -130,13 +162,13 @@ impl Cursor {
                     let line = buf.line(self.row).unwrap();
                     self.byte = line.text.len();
                     self.index = line.size;
-                    self.column = line.width;
+                    self.column = line.width(buf.settings.tab_width);
                 } else {
                     // Go down `steps` lines
                     self.row += steps;
 
                     let line = buf.line(self.row).unwrap();
-                    let index = Cursor::find_column(line, self.desired_column);
+                    let index = Cursor::find_column(line, self.desired_column, buf.settings.tab_width);
                     self.column = index.column;
                     self.byte = index.byte;
                     self.index = index.index;
@@ -150,7 +182,7 @@ impl Cursor {
                     self.index -= take;
                     remain -= take;
 
-                    if self.index <= 0 && remain > 0 {
+                    if self.index == 0 && remain > 0 {
                         if self.row == 0 {
                             break;
                         } else {
@@ -163,7 +195,7 @@ impl Cursor {
                 }
 
                 let line = buf.line(self.row).unwrap();
-                let index = Cursor::find_index(line, self.index);
+                let index = Cursor::find_index(line, self.index, buf.settings.tab_width);
                 self.column = index.column;
                 self.byte = index.byte;
                 self.desired_column = index.column;
@@ -190,7 +222,7 @@ impl Cursor {
                 }
 
                 let line = buf.line(self.row).unwrap();
-                let index = Cursor::find_index(line, self.index);
+                let index = Cursor::find_index(line, self.index, buf.settings.tab_width);
                 self.column = index.column;
                 self.byte = index.byte;
                 self.desired_column = index.column;
@@ -212,9 +244,14 @@ impl Cursor {
                         // Step left by one character
                         let s = &line.text[previous..self.byte];
                         self.offset -= self.byte - previous;
-                        self.column -= s.width_cjk();
                         self.byte = previous;
                         self.index -= 1;
+                        if s == "\t" {
+                            // How far back the tab stop was depends on what's before it
+                            self.column = Cursor::find_index(line, self.index, buf.settings.tab_width).column;
+                        } else {
+                            self.column -= s.display_width();
+                        }
                         self.desired_column = self.column;
                     },
                     Ok(None) => {
@@ -239,7 +276,12 @@ impl Cursor {
                         // Step right by one character
                         let s = &line.text[self.byte..next];
                         self.offset += next - self.byte;
-                        self.column += s.width_cjk();
+                        if s == "\t" {
+                            let tab_width = buf.settings.tab_width.max(1);
+                            self.column += tab_width - self.column % tab_width;
+                        } else {
+                            self.column += s.display_width();
+                        }
                         self.byte = next;
                         self.index += 1;
                         self.desired_column = self.column;
@@ -273,7 +315,7 @@ impl Cursor {
 
     pub fn end(&mut self, buf: &Buffer) {
         let line = buf.line(self.row).unwrap();
-        self.column = line.width;
+        self.column = line.width(buf.settings.tab_width);
         self.byte = line.text.len();
         self.index = line.size;
         self.offset = Cursor::offset(self.row, buf) + self.byte;
@@ -291,7 +333,6 @@ impl Cursor {
     }
 
     fn offset(row: usize, buf: &Buffer) -> usize {
-        buf.lines().iter().take(row)
-            .fold(0, |acc, i| acc + i.text.len())
+        buf.offset(row)
     }
 }
\ No newline at end of file
@@ -1,23 +1,47 @@
+pub mod colors;
 pub mod cursor;
+pub mod selection;
+pub mod shape;
 
 use cursor::{Cursor, Direction};
+use selection::Selection;
+use colors::{Colors, Paint};
+use shape::CursorShapes;
 use termion::event::{Event, Key};
-use unicode_width::UnicodeWidthStr;
-use crate::buffer::{Buffer, Edit, Point};
-use crate::Config;
+use unicode_segmentation::UnicodeSegmentation;
+use crate::terminal;
+use crate::width::{self, DisplayWidth};
+use crate::bidi;
+use crate::buffer::{Buffer, Change, Edit, Point};
+use crate::conflict::{self, Conflict, Region};
+use crate::diff;
+use crate::follow::{Follow, Update};
+use crate::git::{Blame, BlameLine, Gutter, Marker};
+use crate::highlight::{self, Cache};
+use crate::indent;
+use crate::invisible::{self, Whitespace};
+use crate::markdown::{self, Table as MarkdownTable};
+use crate::remote::Remote;
+use crate::table::{self, Table};
+use crate::template;
+use crate::transform;
+use crate::{Config, FileOptions};
 use termion as t;
 use std::io::{self, Write};
 use std::cmp::{max, min};
+use std::borrow::Cow;
 use std::ops::Range;
-use std::path::Path;
+use std::iter;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use std::mem;
 
-const LINE_BG: t::color::Rgb = t::color::Rgb(39, 39, 39);
-const LINE_FG: t::color::LightWhite = t::color::LightWhite;
-const STATUS_BG: t::color::Rgb = t::color::Rgb(84, 84, 84);
-const STATUS_FG: t::color::White = t::color::White;
-const OVERFLOW_BG: t::color::Blue = t::color::Blue;
-const HIGHLIGHT_BG: t::color::Rgb = t::color::Rgb(184, 184, 184);
-const HIGHLIGHT_FG: t::color::Rgb = t::color::Rgb(34, 34, 34);
+// How many pieces of killed text are kept for yanking
+const KILL_RING_SIZE: usize = 30;
+
+const READONLY: &str = "Buffer is read-only (C-x R to allow edits)";
 
 pub fn intersects(lhs: &Range<usize>, rhs: &Range<usize>) -> bool {
     !(lhs.end < rhs.start || rhs.end < lhs.start)
@@ -33,6 +57,40 @@ pub fn intersection(lhs: &Range<usize>, rhs: &Range<usize>) -> Option<Range<usiz
     }
 }
 
+// Region of the terminal a screen is drawn into, zero-based and including the status line
+#[derive(Clone, Copy)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16
+}
+
+impl Rect {
+    pub fn terminal() -> Self {
+        let (width, height) = terminal::size()
+            .expect("Failed to get terminal size");
+        Rect { x: 0, y: 0, width, height }
+    }
+}
+
+// Where a screen is scrolled to and its cursor, kept for panes that aren't focused
+#[derive(Clone)]
+pub struct View {
+    origin: Point,
+    cursor: Cursor,
+    revision: usize // Buffer revision the cursor was placed in
+}
+
+// Trailing whitespace stripped for a save, to put back if the save doesn't go ahead
+pub struct Strip {
+    pub lines: usize, // How many lines changed
+    first: usize, // Where its steps start on the undo stack
+    redo: Vec<(Cursor, Edit, usize)>, // What could be redone before
+    cursor: Cursor,
+    dirty: bool
+}
+
 pub enum Message {
     Info(String),
     Warning(String),
@@ -47,174 +105,810 @@ impl Message {
             Message::Error(s) => s
         }
     }
+
+    // How long the message stays visible for
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(match self {
+            Message::Info(_) => 3,
+            Message::Warning(_) => 5,
+            Message::Error(_) => 8
+        })
+    }
     
-    fn set_color(&self, out: &mut impl Write) -> io::Result<()> {
+    fn set_color(&self, out: &mut impl Write, colors: &Colors) -> io::Result<()> {
         match self {
             Message::Info(_) =>
                 write!(out, "{}{}", 
-                    t::color::Bg(STATUS_BG),
-                    t::color::Fg(STATUS_FG)
+                    t::color::Bg(colors.status_bg),
+                    t::color::Fg(colors.status_fg)
                 ),
             Message::Warning(_) => 
                 write!(out, "{}{}", 
-                    t::color::Bg(t::color::Rgb(230, 150, 0)),
-                    t::color::Fg(t::color::White)
+                    t::color::Bg(colors.warning_bg),
+                    t::color::Fg(colors.warning_fg)
                 ),
             Message::Error(_) => 
                 write!(out, "{}{}",
-                    t::color::Bg(t::color::Rgb(200, 0, 0)),
-                    t::color::Fg(t::color::White)
+                    t::color::Bg(colors.error_bg),
+                    t::color::Fg(colors.error_fg)
                 )
         }
     }
 }
 
+// Text just yanked from the kill ring, which a yank-pop replaces with an older entry
+struct Yank {
+    index: usize, // Into the kill ring, counting back from the most recent
+    start: Point,
+    end: Point,
+    revision: usize // The buffer's revision right after, a yank-pop must follow directly
+}
+
+// An incremental search being typed, and where it started so it can be cancelled
+struct Incremental {
+    pattern: String,
+    found: bool,
+    cursor: Cursor,
+    selection: Option<Selection>,
+    origin: Point,
+    search: Option<String> // The last search before this one
+}
+
+// Optional fields shown in the right-hand side of the status line
+#[derive(Clone, Copy)]
+pub struct StatusFields {
+    pub encoding: bool,
+    pub size: bool,
+    pub words: bool
+}
+
+impl Default for StatusFields {
+    fn default() -> Self {
+        StatusFields { encoding: true, size: true, words: false }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum StatusSegment {
+    Name,
+    Mode,
+    Position,
+    Words,
+    Size,
+    Filetype,
+    Encoding,
+    Ending,
+    Remote
+}
+
+// Cut `s` down to at most `width` columns
+pub fn truncate(s: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (i, g) in s.grapheme_indices(true) {
+        used += g.display_width();
+        if used > width {
+            return &s[..i];
+        }
+    }
+    s
+}
+
+// Widen `range` of `text` to whole graphemes
+fn snap_to_graphemes(text: &str, range: Range<usize>) -> Range<usize> {
+    let mut boundaries = text
+        .grapheme_indices(true)
+        .map(|(i, _)| i)
+        .chain(iter::once(text.len()));
+    let start = boundaries.clone().take_while(|&i| i <= range.start).last().unwrap_or(0);
+    let end = boundaries.find(|&i| i >= range.end).unwrap_or(text.len());
+    start..end.max(start)
+}
+
+// Where a match of `pattern` starting at byte `i` of `text` ends, if there is one. Case is
+// ignored unless `pattern` has capitals
+fn match_at(text: &str, i: usize, pattern: &str) -> Option<usize> {
+    let ignore_case = !pattern.chars().any(char::is_uppercase);
+    let mut chars = text[i..].char_indices();
+    for p in pattern.chars() {
+        match chars.next() {
+            Some((_, c)) if ignore_case && c.to_lowercase().eq(p.to_lowercase()) => (),
+            Some((_, c)) if c == p => (),
+            _ => return None
+        }
+    }
+    Some(chars.next().map_or(text.len(), |(j, _)| i + j))
+}
+
+// Print the columns of `text` from `x` to `x + width`, returns how many were printed
+fn draw_aligned<W>(out: &mut W, text: &str, x: usize, width: usize) -> io::Result<usize>
+    where W : Write
+{
+    let mut column = 0;
+    let mut printed = 0;
+    for grapheme in text.graphemes(true) {
+        let end = column + grapheme.display_width();
+        if end > x + width {
+            break;
+        }
+        if column >= x {
+            write!(out, "{}", grapheme)?;
+            printed += end - column;
+        } else if end > x {
+            // Partially scrolled out of view on the left
+            write!(out, "{:1$}", "", end - x)?;
+            printed += end - x;
+        }
+        column = end;
+    }
+    Ok(printed)
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
 pub struct Screen {
     buffer: Buffer,
     origin: Point, // Top-left edge of the viewport, in rows and columns
+    area: Rect,
     cursor: Cursor,
     pub overwrite: bool,
+    name: Option<String>, // Display name set by the user, instead of the file name
+    directory: PathBuf, // Relative paths in prompts are resolved against this
     message: Option<Message>,
-    undo_stack: Vec<(Cursor, Edit)>,
-    redo_stack: Vec<(Cursor, Edit)>,
-    selection: Option<(Cursor, Cursor)>
+    expires: Instant, // When the message stops being shown
+    undo_stack: Vec<(Cursor, Edit, usize)>, // Edits of the same group are undone together
+    redo_stack: Vec<(Cursor, Edit, usize)>,
+    undo_group: usize,
+    typing: Option<(char, Point, Instant)>, // Last character typed or deleted, where the cursor went and when
+    undo_idle: Duration, // Typing after a pause this long starts a new undo group
+    selection: Option<Selection>,
+    gutter: Option<Gutter>, // Changes against the git index
+    blame: Blame,
+    inline_blame: bool, // Always show the current line's blame after it
+    show_blame: bool, // Show the current line's blame as a message once it's ready
+    conflicts: Vec<Conflict>,
+    conflicts_revision: Option<usize>, // Buffer revision `conflicts` were found in
+    conflicts_stale: Option<Range<usize>>, // Rows edited since, to look through again
+    table: Option<Table>, // Set when delimiter-separated columns are shown aligned
+    follow: Option<Follow>, // Set while reading what's appended to the file
+    view: bool, // Keys scroll instead of editing, like a pager
+    search: Option<String>, // The last text searched for
+    incremental: Option<Incremental>,
+    kill_ring: Vec<String>, // Text cut from this screen, most recent last
+    yank: Option<Yank>,
+    line_anchor: Option<usize>, // Row the selection started on, while selecting whole lines
+    extra_cursors: Vec<Point>, // More places edits are made at, besides the cursor
+    cursor_pattern: Option<String>, // The text extra cursors were added at occurrences of
+    expansions: Vec<Option<Selection>>, // Selections before each expansion, then the current one
+    remote: Option<Remote>, // Set when editing a local copy of a file on another machine
+    bidi: bool, // Show right-to-left text in visual order
+    invisibles: bool, // Spell out invisible characters
+    whitespace: bool, // Show spaces and tabs as glyphs
+    glyphs: Whitespace,
+    syntax: bool, // Color the text by its filetype
+    highlights: Cache,
+    cursors: CursorShapes,
+    colors: Colors,
+    line_numbers: bool,
+    relative_numbers: bool, // Number lines by their distance from the cursor's
+    scroll_margin: usize, // Rows kept visible above and below the cursor
+    narrow: Option<Range<usize>>, // Rows that are shown and editable, if narrowed to some
+    protected: Vec<Selection> // Text that can't be edited, kept in place like the selection
 }
 
 impl Screen {
+    // Open `path` with the options for files opened while editing
     pub fn new(path: &str, config: &Config) -> Self {
+        Screen::open(path, &config.options, config)
+    }
+
+    pub fn open(path: &str, options: &FileOptions, config: &Config) -> Self {
         let mut message: Option<Message> = None;
-        let buffer = Buffer::build(path, &config)
+
+        // `-` is the text piped to stdin, in a buffer without a file
+        let stdin = path == "-";
+        let path = if stdin { "" } else { path };
+
+        // `user@host:/path` is fetched to a local copy, unless a local file has that name
+        let mut remote = if Path::new(path).exists() { None } else { Remote::new(path) };
+        if let Some(remote) = remote.as_mut() {
+            if let Err(e) = remote.fetch() {
+                message = Some(Message::Error(e.to_string()));
+            }
+        }
+        let spec = path;
+        let local = remote.as_ref().map(|r| r.local.to_string_lossy().into_owned());
+        let path = local.as_deref().unwrap_or(path);
+
+        let built = if stdin { Buffer::stdin(options, config) } else { Buffer::build(path, options, config) };
+        let unread = built.is_err() && !stdin;
+        let mut buffer = built
             .unwrap_or_else(|e| {
                 message = Some(Message::Error(e.to_string()));
-                Buffer::new(path, &config)
+                Buffer::new(path, options, config)
             });
 
-        Screen {
+        // Don't let an empty buffer overwrite a file that couldn't be read or fetched
+        if unread || remote.as_ref().is_some_and(|r| !r.connected) {
+            buffer.set_readonly(true);
+        }
+
+        // Default to the file's directory, or where the editor was started
+        let cwd = env::current_dir().unwrap_or_default();
+        let directory = match remote {
+            Some(_) => cwd,
+            None => Path::new(spec)
+                .parent()
+                .map_or(cwd.clone(), |p| cwd.join(p))
+        };
+
+        let local = !path.is_empty() && remote.is_none();
+        let gutter = if local && config.file.boolean("git", "gutter").unwrap_or(true) {
+            Gutter::load(Path::new(path))
+        } else {
+            None
+        };
+
+        let table = match buffer.settings.filetype.as_deref() {
+            Some("csv") | Some("tsv") if config.file.boolean("csv", "align").unwrap_or(false) => {
+                let first = buffer.line(0).map_or("", |l| l.text.as_str());
+                Some(Table::new(table::delimiter(buffer.settings.filetype.as_deref(), first)))
+            },
+            _ => None
+        };
+
+        // New files start from a template, as an edit that can be undone
+        let template = if !path.is_empty() && buffer.size().is_none() && !buffer.is_readonly()
+            && config.file.boolean("templates", "enabled").unwrap_or(true)
+        {
+            template::find(Path::new(spec))
+        } else {
+            None
+        };
+
+        let mut screen = Screen {
             buffer,
             origin: Point::new(),
+            area: Rect::terminal(),
             cursor: Cursor::new(),
             overwrite: false,
+            name: None,
+            directory,
+            expires: Instant::now() + message.as_ref().map_or(Duration::ZERO, |m| m.timeout()),
             message,
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
-            selection: None
+            undo_group: 0,
+            typing: None,
+            undo_idle: Duration::from_millis(config.file.integer("editor", "undo_idle").map_or(1000, |n| n.max(0) as u64)),
+            selection: None,
+            gutter,
+            blame: Blame::default(),
+            inline_blame: local && config.file.boolean("git", "blame").unwrap_or(false),
+            show_blame: false,
+            conflicts: Vec::new(),
+            conflicts_revision: None,
+            conflicts_stale: None,
+            table,
+            follow: None,
+            view: options.view,
+            search: None,
+            incremental: None,
+            kill_ring: Vec::new(),
+            yank: None,
+            line_anchor: None,
+            extra_cursors: Vec::new(),
+            cursor_pattern: None,
+            expansions: Vec::new(),
+            remote,
+            bidi: config.file.boolean("display", "bidi").unwrap_or(false),
+            invisibles: config.file.boolean("display", "invisibles").unwrap_or(false),
+            whitespace: config.file.boolean("display", "whitespace").unwrap_or(false),
+            glyphs: config.whitespace,
+            syntax: config.file.boolean("display", "syntax").unwrap_or(true),
+            highlights: Cache::default(),
+            cursors: config.cursors,
+            colors: config.colors,
+            line_numbers: config.file.boolean("display", "line_numbers").unwrap_or(true),
+            relative_numbers: config.file.boolean("display", "relative_line_numbers").unwrap_or(false),
+            scroll_margin: config.file.integer("editor", "scroll_margin").map_or(0, |n| n.max(0) as usize),
+            narrow: None,
+            protected: Vec::new()
+        };
+
+        if let Some(warning) = screen.endings_warning().filter(|_| screen.message.is_none()) {
+            screen.set_message(warning);
+        }
+        if let Some((name, text)) = template {
+            screen.insert_str(&text);
+            if screen.message.is_none() {
+                screen.set_message(Message::Info(format!("New file from template '{}'", name)));
+            }
+        }
+        if let Some((row, column)) = options.position {
+            screen.goto(row, column);
         }
+        screen
     }
     
-    fn draw_selection<W>(&self, out: &mut W, row: usize, offset: usize, range: Range<usize>) 
+    // A read-only screen not backed by a file, showing `text`
+    pub fn scratch(name: &str, text: &str, directory: &Path, config: &Config) -> Self {
+        let mut screen = Screen::new("", config);
+        screen.buffer = Buffer::scratch(text, config);
+        screen.name = Some(String::from(name));
+        screen.directory = directory.to_path_buf();
+        screen
+    }
+
+    pub fn append_line(&mut self, s: &str) {
+        self.buffer.append_line(s);
+        self.highlights.edited(self.buffer.line_count() - 1, self.buffer.revision());
+    }
+
+    // Highlight the lines down to `end` for their filetype, if it has a syntax
+    fn update_highlights(&mut self, end: usize) {
+        let syntax = match self.filetype().filter(|_| self.syntax).and_then(highlight::syntax) {
+            Some(s) => s,
+            None => return
+        };
+        let buffer = &self.buffer;
+        self.highlights.update(syntax, buffer.revision(), end, |row| &buffer.line(row).expect("row out-of-bounds").text);
+    }
+
+    // Colored spans of line `row`, as far as it's been highlighted
+    fn syntax_colors(&self, row: usize) -> Vec<(Range<usize>, Paint)> {
+        if !self.syntax || self.filetype().and_then(highlight::syntax).is_none() {
+            return Vec::new();
+        }
+        self.highlights
+            .spans(row)
+            .iter()
+            .filter_map(|(r, style)| Some((r.clone(), self.colors.style(*style)?)))
+            .collect()
+    }
+
+    // Go back to the text color of line `row`, leaving the background as is
+    fn reset_foreground<W>(&self, out: &mut W, row: usize) -> io::Result<()> where W : Write {
+        if self.cursor.row == row {
+            write!(out, "{}", t::color::Fg(self.colors.line_fg))
+        } else {
+            write!(out, "{}", t::color::Fg(t::color::Reset))
+        }
+    }
+
+    // Print the bytes `range` of line `row`, coloring its syntax and highlighting what's
+    // selected, what's past the maximum column and what's protected
+    fn draw_selection<W>(&self, out: &mut W, row: usize, range: Range<usize>) 
         -> io::Result<()> where W : Write
     {
         let line = self.buffer.line(row).expect("row out-of-bounds");
+        let tab_width = self.buffer.settings.tab_width;
+        let selected = self.selection
+            .as_ref()
+            .and_then(|s| s.columns(row, line.text.len()))
+            .map(|s| snap_to_graphemes(&line.text, s))
+            .and_then(|s| intersection(&range, &s));
+        let overlong = self.overlong_byte(row)
+            .map(|b| b.clamp(range.start, range.end))
+            .filter(|&b| b < range.end);
+        let protected: Vec<Range<usize>> = self.protected
+            .iter()
+            .filter_map(|p| p.columns(row, line.text.len()))
+            .filter_map(|p| intersection(&range, &p))
+            .collect();
+        let matched: Vec<Range<usize>> = self.match_ranges(row)
+            .iter()
+            .filter_map(|m| intersection(&range, m))
+            .collect();
+        let colored: Vec<(Range<usize>, Paint)> = self.syntax_colors(row)
+            .into_iter()
+            .filter_map(|(r, paint)| Some((intersection(&range, &r)?, paint)))
+            .collect();
+
+        // Only syntax colors leave the background alone, for conflicting regions
+        let highlighted = selected.is_some() || overlong.is_some() || !protected.is_empty() || !matched.is_empty();
+        if !highlighted && colored.is_empty() {
+            return write!(out, "{}", line.expand_tabs(range, tab_width));
+        }
 
-        if let Some((left, right)) = &self.selection {
-            let lhs = (range.start + offset)..(range.end + offset);
-            let rhs = left.offset..right.offset;
-            if let Some(int) = intersection(&lhs, &rhs) {
-                let start=  int.start - offset;
-                let end = int.end - offset;
-                let last = line.text.len();
-                let points = [0, start, end, last];
-                let current_line = self.cursor.row == row;
-                
-                // Print sections:
-                // [0, start) normal text
-                // [start, end) highlighted text
-                // [end, last) normal text
-                for (i, &p) in points.iter().enumerate() {
-                    let highlight = i == 1;
-                    let n = *points.get(i + 1).unwrap_or(&last);
-
-                    if n - p > 0 {
-                        if highlight {
-                            write!(out, "{}{}", t::color::Fg(HIGHLIGHT_FG), t::color::Bg(HIGHLIGHT_BG))?;
-                        } else if current_line {
-                            write!(out, "{}{}", t::color::Fg(LINE_FG), t::color::Bg(LINE_BG))?;
-                        } else {
-                            write!(out, "{}{}",t::color::Fg(t::color::Reset), t::color::Bg(t::color::Reset))?;
-                        }
-                        write!(out, "{}", &line.text[p..n])?;
-                    }
+        let mut points = vec![range.start, range.end];
+        points.extend(selected.iter().flat_map(|s| [s.start, s.end]));
+        points.extend(overlong);
+        points.extend(protected.iter().flat_map(|p| [p.start, p.end]));
+        points.extend(matched.iter().flat_map(|m| [m.start, m.end]));
+        points.extend(colored.iter().flat_map(|(c, _)| [c.start, c.end]));
+        points.sort_unstable();
+        points.dedup();
+
+        for w in points.windows(2) {
+            if selected.as_ref().is_some_and(|s| s.contains(&w[0])) {
+                write!(out, "{}{}", t::color::Fg(self.colors.selection_fg), t::color::Bg(self.colors.selection_bg))?;
+            } else {
+                if highlighted {
+                    self.reset_colors(out, row)?;
+                }
+                if matched.iter().any(|m| m.contains(&w[0])) {
+                    write!(out, "{}", t::color::Bg(self.colors.match_bg))?;
+                } else if overlong.is_some_and(|b| w[0] >= b) {
+                    write!(out, "{}", t::color::Bg(self.colors.overlong_bg))?;
+                } else if protected.iter().any(|p| p.contains(&w[0])) {
+                    write!(out, "{}", t::color::Bg(self.colors.protected_bg))?;
                 }
+                match colored.iter().find(|(c, _)| c.contains(&w[0])) {
+                    Some((_, paint)) => write!(out, "{}", t::color::Fg(*paint))?,
+                    None => self.reset_foreground(out, row)?
+                }
+            }
+            write!(out, "{}", line.expand_tabs(w[0]..w[1], tab_width))?;
+        }
+
+        // Set colors once again for the rest of the row
+        if highlighted {
+            self.reset_colors(out, row)
+        } else {
+            self.reset_foreground(out, row)
+        }
+    }
+
+    fn is_protected(&self, row: usize, byte: usize) -> bool {
+        let len = self.buffer.line(row).map_or(0, |l| l.text.len());
+        self.protected
+            .iter()
+            .filter_map(|p| p.columns(row, len))
+            .any(|p| p.contains(&byte))
+    }
+
+    // Where line `row` goes past the maximum column, if it does
+    fn overlong_byte(&self, row: usize) -> Option<usize> {
+        let max = self.buffer.settings.max_column?;
+        self.buffer
+            .line(row)?
+            .column_indices(self.buffer.settings.tab_width)
+            .find(|c| c.column >= max)
+            .map(|c| c.byte)
+    }
+
+    fn is_selected(&self, row: usize, byte: usize) -> bool {
+        let line = self.buffer.line(row).expect("row out-of-bounds");
+        self.selection
+            .as_ref()
+            .and_then(|s| s.columns(row, line.text.len()))
+            .map(|s| snap_to_graphemes(&line.text, s))
+            .is_some_and(|s| s.contains(&byte))
+    }
+
+    // Fill the `space` visible cells of a wide character cut off by the edge of the
+    // viewport with `marker`, highlighted if the character is selected
+    fn draw_overflow<W>(&self, out: &mut W, row: usize, byte: usize, marker: char, space: usize)
+        -> io::Result<()> where W : Write
+    {
+        if self.is_selected(row, byte) {
+            write!(out, "{}{}", t::color::Fg(self.colors.selection_fg), t::color::Bg(self.colors.selection_bg))?;
+        } else {
+            write!(out, "{}", t::color::Bg(self.colors.overflow_bg))?;
+        }
+        write!(out, "{}", marker.to_string().repeat(space))?;
+        self.reset_colors(out, row)
+    }
+
+    // Go back to the colors of line `row`
+    fn reset_colors<W>(&self, out: &mut W, row: usize) -> io::Result<()> where W : Write {
+        if self.cursor.row == row {
+            write!(out, "{}{}", t::color::Fg(self.colors.line_fg), t::color::Bg(self.colors.line_bg))
+        } else {
+            write!(out, "{}{}", t::color::Fg(t::color::Reset), t::color::Bg(t::color::Reset))
+        }
+    }
 
-                // Set colors once again in case last section was zero length
-                if current_line {
-                    write!(out, "{}{}", t::color::Fg(LINE_FG), t::color::Bg(LINE_BG))?;
+    // Print the graphemes of line `row` in `order`, from column `x` to `x + width`.
+    // Returns how many columns were printed
+    fn draw_layout<W>(&self, out: &mut W, row: usize, order: &[Range<usize>], x: usize, width: usize)
+        -> io::Result<usize> where W : Write
+    {
+        let text = &self.buffer.line(row).expect("row out-of-bounds").text;
+        let overlong = self.overlong_byte(row);
+        let matched = self.match_ranges(row);
+        let colored = self.syntax_colors(row);
+        let tab_width = self.buffer.settings.tab_width.max(1);
+        let trail = text.trim_end_matches([' ', '\t']).len(); // Where trailing whitespace starts
+        let mut column = 0;
+        let mut printed = 0;
+        for range in order {
+            let glyph = match self.whitespace {
+                true => self.glyphs.glyph(&text[range.clone()], column, tab_width, range.start >= trail),
+                false => None
+            };
+            let whitespace = glyph.is_some();
+            let grapheme = match glyph {
+                Some(glyph) => Cow::Owned(glyph),
+                None => self.shown(&text[range.clone()], column)
+            };
+            let end = column + grapheme.display_width();
+            if end > x + width {
+                if x <= column && column < x + width {
+                    // Partially visible at the right edge
+                    self.draw_overflow(out, row, range.start, '>', x + width - column)?;
+                    printed += x + width - column;
+                }
+                break;
+            }
+            if column >= x {
+                let revealed = self.invisibles && invisible::contains_invisible(&text[range.clone()]);
+                let background = if matched.iter().any(|m| m.contains(&range.start)) {
+                    Some(self.colors.match_bg)
+                } else if overlong.is_some_and(|b| range.start >= b) {
+                    Some(self.colors.overlong_bg)
+                } else if self.is_protected(row, range.start) {
+                    Some(self.colors.protected_bg)
                 } else {
-                    write!(out, "{}{}", t::color::Fg(t::color::Reset), t::color::Bg(t::color::Reset))?;
+                    None
+                };
+                let foreground = match whitespace {
+                    true => Some(self.colors.whitespace_fg),
+                    false => colored.iter().find(|(c, _)| c.contains(&range.start)).map(|&(_, paint)| paint)
+                };
+                if self.is_selected(row, range.start) {
+                    write!(out, "{}{}{}", t::color::Fg(self.colors.selection_fg), t::color::Bg(self.colors.selection_bg), grapheme)?;
+                    self.reset_colors(out, row)?;
+                } else if revealed || background.is_some() {
+                    if revealed {
+                        write!(out, "{}", t::color::Fg(self.colors.invisible_fg))?;
+                    } else if let Some(foreground) = foreground {
+                        write!(out, "{}", t::color::Fg(foreground))?;
+                    }
+                    if let Some(background) = background {
+                        write!(out, "{}", t::color::Bg(background))?;
+                    }
+                    write!(out, "{}", grapheme)?;
+                    self.reset_colors(out, row)?;
+                } else if let Some(foreground) = foreground {
+                    write!(out, "{}{}", t::color::Fg(foreground), grapheme)?;
+                    self.reset_foreground(out, row)?;
+                } else {
+                    write!(out, "{}", grapheme)?;
                 }
+                printed += end - column;
+            } else if end > x {
+                // Partially scrolled out of view on the left
+                self.draw_overflow(out, row, range.start, '<', end - x)?;
+                printed += end - x;
+            }
+            column = end;
+        }
+        Ok(printed)
+    }
+
+    // How `grapheme` is shown at `column`, which differs for tabs, control characters and
+    // when spelling out invisible characters
+    fn shown<'a>(&self, grapheme: &'a str, column: usize) -> Cow<'a, str> {
+        if grapheme == "\t" {
+            let tab_width = self.buffer.settings.tab_width.max(1);
+            Cow::Owned(" ".repeat(tab_width - column % tab_width))
+        } else if self.invisibles && invisible::contains_invisible(grapheme) {
+            invisible::reveal(grapheme)
+        } else {
+            width::reveal_controls(grapheme)
+        }
+    }
+
+    // The graphemes of line `row` in the order they're shown, if that or their width
+    // differs from the text as is
+    fn layout(&self, row: usize) -> Option<Vec<Range<usize>>> {
+        if self.table.is_some() {
+            return None;
+        }
+        let text = &self.buffer.line(row)?.text;
+        let order = if self.bidi { bidi::visual_order(text) } else { None };
+        let whitespace = self.whitespace && text.contains([' ', '\t']);
+        if order.is_none() && !whitespace && !(self.invisibles && invisible::contains_invisible(text)) {
+            return None;
+        }
+        Some(order.unwrap_or_else(|| {
+            text.grapheme_indices(true).map(|(i, g)| i..i + g.len()).collect()
+        }))
+    }
+
+    // The column the grapheme starting at `byte` is shown at in `order`, or the end of the line
+    fn layout_column(&self, row: usize, order: &[Range<usize>], byte: usize) -> usize {
+        let text = &self.buffer.line(row).expect("row out-of-bounds").text;
+        let mut column = 0;
+        for range in order {
+            if range.start == byte {
+                return column;
+            }
+            column += self.shown(&text[range.clone()], column).display_width();
+        }
+        column
+    }
 
-                return Ok(())
+    // The start of the grapheme shown at `column` in `order`, or the end of the line
+    fn layout_byte(&self, row: usize, order: &[Range<usize>], column: usize) -> usize {
+        let text = &self.buffer.line(row).expect("row out-of-bounds").text;
+        let mut end = 0;
+        for range in order {
+            end += self.shown(&text[range.clone()], end).display_width();
+            if column < end {
+                return range.start;
             }
         }
+        text.len()
+    }
+
+    pub fn toggle_invisibles(&mut self) -> bool {
+        self.invisibles = !self.invisibles;
+        self.invisibles
+    }
+
+    pub fn toggle_whitespace(&mut self) -> bool {
+        self.whitespace = !self.whitespace;
+        self.whitespace
+    }
+
+    // Number lines by how far they are from the cursor's, which shows its own number
+    pub fn toggle_relative_numbers(&mut self) -> bool {
+        self.relative_numbers = !self.relative_numbers;
+        self.relative_numbers
+    }
+
+    pub fn resize(&mut self, area: Rect) {
+        self.area = area;
+    }
+
+    pub fn view(&self) -> View {
+        View { origin: self.origin, cursor: self.cursor.clone(), revision: self.buffer.revision() }
+    }
 
-        return write!(out, "{}", &line.text[range]);
+    pub fn set_view(&mut self, view: View) {
+        self.origin = view.origin;
+        self.cursor = if view.revision == self.buffer.revision() {
+            view.cursor
+        } else {
+            // The text may have changed under the cursor since
+            let row = min(view.cursor.row, self.buffer.line_count() - 1);
+            Cursor::from(&self.buffer, view.cursor.column, row)
+        };
+        self.clamp_cursor();
     }
 
-    pub fn draw<T>(&mut self, out: &mut T) -> io::Result<()> where T : Write {
+    // Draw into the screen's area, which is assumed to have been cleared
+    pub fn draw<T>(&mut self, out: &mut T, fields: &StatusFields) -> io::Result<()> where T : Write {
+        if let Some(table) = self.table.as_mut() {
+            table.update(&self.buffer);
+        }
         self.update_viewport();
+        if let Some(gutter) = self.gutter.as_mut() {
+            gutter.update(&self.buffer);
+        }
+        if self.inline_blame {
+            self.blame.request(&self.buffer);
+        }
+        self.update_conflicts();
         let number_width = self.line_number_width();
         let (width, height) = self.get_viewport_size();
+        let (left, top) = (self.area.x + 1, self.area.y + 1);
+        self.update_highlights(min(self.origin.y + height, self.rows().end));
 
-        write!(out, "{}", t::clear::All)?;
-
-        let lines = self.buffer.lines()
-            .iter()
-            .skip(self.origin.y)
+        // A pinned header row covers the top line once scrolled past it
+        let pinned = self.is_header_pinned();
+        let rows = (self.origin.y..self.rows().end)
             .take(height)
-            .enumerate();
+            .map(|y| if pinned && y == self.origin.y { 0 } else { y });
 
-        let mut offset = 0;
-        for (i, line) in lines {
+        for (i, y) in rows.enumerate() {
+            let line = self.buffer.line(y).unwrap();
             let x = self.origin.x;
-            let y = self.origin.y + i;
 
             // Setup colors:
             if self.cursor.row == y {
-                write!(out, "{}{}", t::color::Bg(LINE_BG), t::color::Fg(LINE_FG))?;
+                write!(out, "{}{}", t::color::Bg(self.colors.line_bg), t::color::Fg(self.colors.line_fg))?;
             } else {
-                write!(out, "{}", t::color::Fg(LINE_BG))?;
+                write!(out, "{}", t::color::Fg(self.colors.gutter_fg))?;
             }
 
-            // Print line number:
-            let position = t::cursor::Goto(1, (i + 1) as u16);
-            write!(out, "{}{:>number_width$} ", position, y + 1)?;
+            // Print line number, followed by any git marker:
+            // Lines past the maximum column get a red number, in case that's scrolled out of view
+            let position = t::cursor::Goto(left, top + i as u16);
+            let number = if self.relative_numbers && y != self.cursor.row { y.abs_diff(self.cursor.row) } else { y + 1 };
+            if !self.line_numbers {
+                write!(out, "{}", position)?;
+            } else if self.overlong_byte(y).is_some() {
+                write!(out, "{}{}{:>number_width$}", position, t::color::Fg(self.colors.overlong_fg), number)?;
+                if self.cursor.row == y {
+                    write!(out, "{}", t::color::Fg(self.colors.line_fg))?;
+                } else {
+                    write!(out, "{}", t::color::Fg(self.colors.gutter_fg))?;
+                }
+            } else {
+                write!(out, "{}{:>number_width$}", position, number)?;
+            }
+            match self.gutter.as_ref().and_then(|g| g.marker(y)) {
+                Some(marker) => {
+                    match marker {
+                        Marker::Added => write!(out, "{}+", t::color::Fg(self.colors.added_fg))?,
+                        Marker::Modified => write!(out, "{}~", t::color::Fg(self.colors.modified_fg))?,
+                        Marker::Deleted => write!(out, "{}-", t::color::Fg(self.colors.deleted_fg))?
+                    }
+                    if self.cursor.row == y {
+                        write!(out, "{}", t::color::Fg(self.colors.line_fg))?;
+                    }
+                },
+                None => write!(out, " ")?
+            }
+
+            // Conflicting regions are highlighted, except on the cursor's line
+            let region = self.conflicts
+                .iter()
+                .find(|c| c.contains(y))
+                .and_then(|c| c.region(y));
 
             if self.cursor.row != y {
                 write!(out, "{}{}", t::color::Fg(t::color::Reset), t::color::Bg(t::color::Reset))?;
+                match region {
+                    Some(Region::Marker) => write!(out, "{}", t::color::Bg(self.colors.conflict_marker_bg))?,
+                    Some(Region::Ours) => write!(out, "{}", t::color::Bg(self.colors.conflict_ours_bg))?,
+                    Some(Region::Base) => write!(out, "{}", t::color::Bg(self.colors.conflict_base_bg))?,
+                    Some(Region::Theirs) => write!(out, "{}", t::color::Bg(self.colors.conflict_theirs_bg))?,
+                    None => ()
+                }
             }
 
-            let mut iter = line.column_indices();
-            let printed = match iter.find(|c| c.column <= x && x < c.column + c.width)
-            {
-                None => 0, // Line is not visible in viewport
-                Some(start) => {
-                    let mut first = start.byte;
-                    if start.column < x {
-                        // First character is partially visible, pad the start
-                        let space = (start.column + start.width) - x;
-                        write!(out, "{}{:<<space$}{}", t::color::Bg(OVERFLOW_BG), "<", t::color::Bg(t::color::Reset))?;
-                        first += start.grapheme.len();
-                    }
+            let printed = if let Some(table) = &self.table {
+                if pinned && i == 0 {
+                    write!(out, "{}", t::style::Underline)?;
+                    let printed = draw_aligned(out, &table.render(&line.text), x, width)?;
+                    write!(out, "{}", t::style::NoUnderline)?;
+                    printed
+                } else {
+                    draw_aligned(out, &table.render(&line.text), x, width)?
+                }
+            } else if let Some(order) = self.layout(y) {
+                self.draw_layout(out, y, &order, x, width)?
+            } else {
+                let mut iter = line.column_indices(self.buffer.settings.tab_width);
+                match iter.find(|c| c.column <= x && x < c.column + c.width)
+                {
+                    None => 0, // Line is not visible in viewport
+                    Some(start) => {
+                        let mut first = start.byte;
+                        if start.column < x {
+                            // First character is partially visible, pad the start
+                            let space = (start.column + start.width) - x;
+                            self.draw_overflow(out, y, start.byte, '<', space)?;
+                            first += start.grapheme.len();
+                        }
 
-                    match iter.find(|c| c.column <= x + width && x + width < c.column + c.width)
-                    {
-                        Some(end) => {
-                            if end.column + end.width > x + width {
-                                // Last character is partially visible, pad the end
-                                let space = (x + width) - end.column;
+                        match iter.find(|c| c.column <= x + width && x + width < c.column + c.width)
+                        {
+                            Some(end) => {
+                                if end.column + end.width > x + width {
+                                    // Last character is partially visible, pad the end
+                                    let space = (x + width) - end.column;
                                 
-                                self.draw_selection(out, y, offset, first..end.byte)?; // Print all but last character
-                                write!(out, "{}{:>>space$}{}", t::color::Bg(OVERFLOW_BG), ">", t::color::Bg(t::color::Reset))?; // Print padding
-                            } else {
-                                // Last character is visible, print the whole line
-                                self.draw_selection(out, y, offset, first..end.byte)?;
+                                    self.draw_selection(out, y, first..end.byte)?; // Print all but last character
+                                    self.draw_overflow(out, y, end.byte, '>', space)?; // Print padding
+                                } else {
+                                    // Last character is visible, print the whole line
+                                    self.draw_selection(out, y, first..end.byte)?;
+                                }
+                                end.column - start.column
+                            },
+                            None => {
+                                // Line doesn't collide with right edge, print it whole
+                                self.draw_selection(out, y, first..line.text.len())?;
+                                line.width(self.buffer.settings.tab_width) - start.column
                             }
-                            end.column - start.column
-                        },
-                        None => {
-                            // Line doesn't collide with right edge, print it whole
-                            self.draw_selection(out, y, offset, first..line.text.len())?;
-                            line.width - start.column
                         }
                     }
                 }
@@ -222,80 +916,219 @@ impl Screen {
 
             // Finish coloring the rest of the row:
             if self.cursor.row == y {
-                let remaining = width - printed;
+                let mut remaining = width - printed;
+                if let Some(blame) = self.inline_blame_line() {
+                    let suffix = format!("    {}", blame.describe());
+                    let suffix = truncate(&suffix, remaining);
+                    write!(out, "{}{}{}", t::color::Fg(self.colors.blame_fg), suffix, t::color::Fg(self.colors.line_fg))?;
+                    remaining -= suffix.display_width();
+                }
                 write!(out, "{:remaining$}{}{}", "", t::color::Bg(t::color::Reset), t::color::Fg(t::color::Reset))?;
+            } else if region.is_some() {
+                let remaining = width - printed;
+                write!(out, "{:remaining$}{}", "", t::color::Bg(t::color::Reset))?;
             }
-
-            offset += line.text.len();
         }
 
-        // Draw status line:
-        let (width, height) = t::terminal_size().unwrap();
-        write!(out, "{}", t::cursor::Goto(1, height))?;
+        // Draw extra cursors as selected cells over the text:
+        for at in &self.extra_cursors {
+            let cursor = Cursor::from_byte(&self.buffer, at.x, at.y);
+            let text = &self.buffer.line(at.y).expect("row out-of-bounds").text;
+            let grapheme = text[at.x..].graphemes(true).next().filter(|&g| g != "\t").unwrap_or(" ");
+            let shown = self.shown(grapheme, cursor.column);
+            let rows = self.origin.y..self.origin.y + height;
+            let columns = self.origin.x..(self.origin.x + width).saturating_sub(shown.display_width().max(1) - 1);
+            if !rows.contains(&at.y) || !columns.contains(&cursor.column) {
+                continue;
+            }
+            let x = (cursor.column - self.origin.x + number_width) as u16 + left + 1;
+            let y = (at.y - self.origin.y) as u16 + top;
+            let colors = (t::color::Fg(self.colors.selection_fg), t::color::Bg(self.colors.selection_bg));
+            write!(out, "{}{}{}{}", t::cursor::Goto(x, y), colors.0, colors.1, shown)?;
+        }
 
-        if let Some(m) = &self.message {
-            let s = m.content();
-            let pad = width as usize - 1;
-            m.set_color(out)?;
+        // Draw status line, cut to the width of the screen's area in case it's a pane:
+        let width = self.area.width as usize;
+        write!(out, "{}", t::cursor::Goto(left, top + self.area.height - 1))?;
+
+        if let Some(search) = &self.incremental {
+            let prompt = if search.found { "I-search:" } else { "Failing I-search:" };
+            let text = format!("{} {}", prompt, search.pattern);
+            let text = truncate(&text, width.saturating_sub(2));
+            let pad = width.saturating_sub(1);
+            write!(out, "{}{} {:<pad$}", t::color::Bg(self.colors.status_bg), t::color::Fg(self.colors.status_fg), text)?;
+        } else if let Some(m) = &self.message {
+            let s = truncate(m.content(), width.saturating_sub(2));
+            let pad = width.saturating_sub(1);
+            m.set_color(out, &self.colors)?;
             write!(out, " {:<pad$}", s)?;
         } else {
-            write!(out, "{}{}", t::color::Bg(STATUS_BG), t::color::Fg(STATUS_FG))?;
-
-            let path = self.buffer.path()
-                .file_name()
-                .map_or(
-                    "[new buffer]", 
-                    |i| i.to_str().expect("path is not valid unicode")
-                );
-            let rhs = format!("{} ({}, {}) {}", 
-                if self.overwrite { "INS" } else { "" },
-                self.cursor.row + 1, 
-                self.cursor.column + 1, 
-                self.buffer.line_ending()
-            );
-            let pad = width as usize - path.width_cjk() - 3;
-            write!(out, " {} {:>pad$} ", path, rhs)?;
+            write!(out, "{}{}", t::color::Bg(self.colors.status_bg), t::color::Fg(self.colors.status_fg))?;
+
+            let name = self.display_name();
+            let rhs = self.status_segments(fields)
+                .into_iter()
+                .map(|(_, s)| s)
+                .collect::<Vec<String>>()
+                .join(" ");
+            let pad = width.saturating_sub(name.display_width() + 3);
+            let status = format!(" {} {:>pad$} ", name, rhs);
+            write!(out, "{}", truncate(&status, width))?;
         }
 
         write!(out, "{}{}", t::color::Bg(t::color::Reset), t::color::Fg(t::color::Reset))?;
 
         // Draw cursor:
-        let x = (self.cursor.column - self.origin.x + number_width) as u16 + 2;
-        let y = (self.cursor.row - self.origin.y) as u16 + 1;
+        let x = (self.display_column() - self.origin.x + number_width) as u16 + left + 1;
+        let y = (self.cursor.row - self.origin.y) as u16 + top;
         let position = t::cursor::Goto(x, y);
-        if self.overwrite {
-            write!(out, "{}", t::cursor::BlinkingBlock)?;
-        } else {
-            write!(out, "{}", t::cursor::BlinkingBar)?;
-        }
-        write!(out, "{}", position)?;
+        let shape = if self.overwrite { self.cursors.overwrite } else { self.cursors.insert };
+        write!(out, "{}{}", self.cursors.style(shape), position)?;
 
         Ok(())
     }
     
+    pub fn display_name(&self) -> &str {
+        if let Some(name) = &self.name {
+            return name;
+        }
+
+        self.buffer.path()
+            .file_name()
+            .map_or(
+                "[new buffer]", 
+                |i| i.to_str().expect("path is not valid unicode")
+            )
+    }
+
+    // Set a custom display name, or go back to the file name if empty
+    pub fn rename(&mut self, name: &str) {
+        self.name = if name.is_empty() { None } else { Some(String::from(name)) };
+    }
+
+    // Segments making up the right-hand side of the status line, in order
+    fn status_segments(&self, fields: &StatusFields) -> Vec<(StatusSegment, String)> {
+        let mut segments = Vec::new();
+
+        if self.is_readonly() {
+            segments.push((StatusSegment::Mode, String::from("[RO]")));
+        }
+        if self.overwrite {
+            segments.push((StatusSegment::Mode, String::from("INS")));
+        }
+        if self.follow.is_some() {
+            segments.push((StatusSegment::Mode, String::from("FOLLOW")));
+        }
+        if self.view {
+            segments.push((StatusSegment::Mode, String::from("VIEW")));
+        }
+        if self.line_anchor.is_some() {
+            segments.push((StatusSegment::Mode, String::from("LINE")));
+        }
+        if let Some(narrow) = &self.narrow {
+            segments.push((StatusSegment::Mode, format!("NARROW {}-{}", narrow.start + 1, narrow.end)));
+        }
+        segments.push((
+            StatusSegment::Position,
+            format!("({}, {})", self.cursor.row + 1, self.cursor.column + 1)
+        ));
+        if fields.words {
+            segments.push((StatusSegment::Words, format!("{}w", self.buffer.word_count())));
+        }
+        if fields.size {
+            if let Some(size) = self.buffer.size() {
+                segments.push((StatusSegment::Size, format_size(size)));
+            }
+        }
+        if let Some(filetype) = &self.buffer.settings.filetype {
+            segments.push((StatusSegment::Filetype, filetype.clone()));
+        }
+        if fields.encoding {
+            segments.push((StatusSegment::Encoding, self.buffer.encoding().to_string()));
+        }
+        segments.push((StatusSegment::Ending, self.buffer.line_ending().to_string()));
+        if let Some(remote) = &self.remote {
+            let state = if remote.connected { "" } else { " (disconnected)" };
+            segments.push((StatusSegment::Remote, format!("{}{}", remote.host, state)));
+        }
+
+        segments
+    }
+
+    // Is the zero-based terminal row `y` this screen's status line?
+    pub fn is_status_row(&self, y: u16) -> bool {
+        y == self.area.y + self.area.height - 1
+    }
+
+    // Find the status line segment under the zero-based terminal column `x`
+    pub fn status_segment_at(&self, x: u16, fields: &StatusFields) -> Option<StatusSegment> {
+        if self.message.is_some() || x < self.area.x {
+            return None;
+        }
+        let x = (x - self.area.x) as usize;
+
+        let name_width = self.display_name().display_width();
+        if (1..1 + name_width).contains(&x) {
+            return Some(StatusSegment::Name);
+        }
+
+        // Right-hand segments are right-aligned and end one column before the edge
+        let segments = self.status_segments(fields);
+        let total = segments.iter().map(|(_, s)| s.display_width() + 1).sum::<usize>();
+        let mut start = (self.area.width as usize).checked_sub(total)?;
+
+        for (segment, s) in segments {
+            let end = start + s.display_width();
+            if (start..end).contains(&x) {
+                return Some(segment);
+            }
+            start = end + 1;
+        }
+
+        None
+    }
+
     pub fn prompt<T, I>(&self, events: &mut I, out: &mut T, prompt: &str) 
         -> io::Result<Option<String>>
         where T : Write
             , I : Iterator<Item = io::Result<Event>>
+    {
+        self.read_prompt(events, out, prompt, false)
+    }
+
+    // Like `prompt` but the reply is masked, for passwords
+    pub fn secret_prompt<T, I>(&self, events: &mut I, out: &mut T, prompt: &str) 
+        -> io::Result<Option<String>>
+        where T : Write
+            , I : Iterator<Item = io::Result<Event>>
+    {
+        self.read_prompt(events, out, prompt, true)
+    }
+
+    fn read_prompt<T, I>(&self, events: &mut I, out: &mut T, prompt: &str, secret: bool) 
+        -> io::Result<Option<String>>
+        where T : Write
+            , I : Iterator<Item = io::Result<Event>>
     {
         let mut buffer = String::new();
-        let prompt_width = prompt.width_cjk();
-        write!(out, "{}", t::cursor::BlinkingUnderline)?;
+        write!(out, "{}", self.cursors.style(self.cursors.prompt))?;
 
         loop {
-            let (width, height) = t::terminal_size().unwrap();
-            let pad = width as usize - prompt_width - 3;
-            let end = prompt_width + buffer.width_cjk() + 3;
+            let (width, height) = (self.area.width as usize, self.area.y + self.area.height);
+            let shown = if secret { "*".repeat(buffer.chars().count()) } else { buffer.clone() };
+            let text = format!("{} {}", prompt, shown);
+            let text = truncate(&text, width.saturating_sub(2));
+            let pad = width.saturating_sub(1);
+            let end = text.display_width() + 2;
             
-            write!(out, "{}{}{} {} {:<pad$} {}{}{}",
-                t::cursor::Goto(1, height),
-                t::color::Bg(STATUS_BG),
-                t::color::Fg(STATUS_FG),
-                prompt,
-                buffer,
+            write!(out, "{}{}{} {:<pad$}{}{}{}",
+                t::cursor::Goto(self.area.x + 1, height),
+                t::color::Bg(self.colors.status_bg),
+                t::color::Fg(self.colors.status_fg),
+                text,
                 t::color::Bg(t::color::Reset),
                 t::color::Fg(t::color::Reset),
-                t::cursor::Goto(end as u16, height)
+                t::cursor::Goto(self.area.x + end as u16, height)
             )?;
             out.flush()?;
 
@@ -311,6 +1144,8 @@ impl Screen {
                     Event::Key(Key::Backspace) => { buffer.pop(); },
                     _ => continue
                 }
+            } else {
+                break;
             }
         }
 
@@ -326,12 +1161,16 @@ impl Screen {
             .and_then(|i| i
                 .chars()
                 .next()
-                .map(|c| c.to_ascii_lowercase() == 'y')
+                .map(|c| c.eq_ignore_ascii_case(&'y'))
             )
             .unwrap_or(default))
     }
 
     fn line_number_width(&self) -> usize {
+        if !self.line_numbers {
+            return 0;
+        }
+
         // `ilog10` may panic if length = 0, but this should never be true,
         // `as usize` may panic if `usize` isn't big enough to contain a `u32`,
         // but even if we compute the number of digits using strings, we can
@@ -342,8 +1181,7 @@ impl Screen {
     }
 
     fn get_viewport_size(&self) -> (usize, usize) {
-        let (width, height) = t::terminal_size()
-            .expect("Failed to get terminal size");
+        let Rect { width, height, .. } = self.area;
 
         // `+1` is for the space between numbers and text
         let number_width = self.line_number_width() + 1;
@@ -352,30 +1190,36 @@ impl Screen {
     }
 
     fn update_viewport(&mut self) {
+        self.clamp_cursor();
         let (mut origin_x, mut origin_y) = self.origin.as_tuple();
         let (width, height) = self.get_viewport_size();
         let cursor_y = self.cursor.row;
-        let cursor_x = self.cursor.column;
-
-        if cursor_y >= origin_y && (cursor_y - origin_y) >= height {
-            // Move `top` down to keep cursor visible
-            origin_y = cursor_y - height + 1;
-        } else if cursor_y < origin_y {
-            // Move `top` up to the cursor
-            origin_y = cursor_y;
+        let margin = min(self.scroll_margin, height.saturating_sub(1) / 2);
+
+        if cursor_y + margin >= origin_y + height {
+            // Move `top` down to keep cursor visible, with `margin` rows below it
+            origin_y = cursor_y + margin + 1 - height;
+        } else if cursor_y < origin_y + margin {
+            // Move `top` up to the cursor, leaving `margin` rows above it
+            origin_y = cursor_y.saturating_sub(margin);
+        }
+        // Don't show rows above the narrowed ones
+        origin_y = max(origin_y, self.rows().start);
+        if self.is_header_pinned_at(origin_y) && cursor_y == origin_y {
+            // Don't let the pinned header cover the cursor
+            origin_y -= 1;
         }
 
         let padding = 4;
         let padded_width = if width >= padding { width - padding } else { width };
-        let line = self.buffer.line(cursor_y).unwrap();
-        let column = min(cursor_x, line.width);
+        let column = self.display_column();
 
         if column >= origin_x && (column - origin_x) >= padded_width {
             // Move `left` right to keep cursor visible (w/ padding)
             origin_x = column - padded_width + 1;
         } else if column <= origin_x + padding {
             // Move `left` left to padded position (or clip to zero)
-            origin_x = if column >= padding { column - padding } else { 0 };
+            origin_x = column.saturating_sub(padding);
         }
 
         // Assert: cursor is visible
@@ -388,43 +1232,1069 @@ impl Screen {
 
     pub fn move_cursor(&mut self, direction: Direction) {
         self.cursor.step_cursor(&self.buffer, direction);
-        self.deselect();
+        self.clamp_cursor();
+        self.move_extra_cursors(|cursor, buffer| cursor.step_cursor(buffer, direction));
+        if self.line_anchor.is_some() {
+            self.select_lines();
+        } else {
+            self.deselect();
+        }
     }
 
-    pub fn set_cursor(&mut self, x: usize,  y: usize) {
-        let x = x - self.line_number_width() + self.origin.x;
+    // Move the cursor to the zero-based terminal position (`x`, `y`)
+    pub fn set_cursor(&mut self, x: u16, y: u16) {
+        let x = x.saturating_sub(self.area.x) as usize;
+        let y = y.saturating_sub(self.area.y) as usize;
+        let x = x.saturating_sub(self.line_number_width() + 1) + self.origin.x;
 
         let line_count = self.buffer.line_count();
         assert_ne!(line_count, 0, "Buffer is empty!");
 
-        let y = min(y + self.origin.y, line_count - 1);
+        let y = if y == 0 && self.is_header_pinned() { 0 } else { min(y + self.origin.y, line_count - 1) };
 
-        self.cursor = Cursor::from(&self.buffer, x, y);
+        self.cursor = match (&self.table, self.layout(y)) {
+            (Some(table), _) => {
+                let byte = table.byte_at(&self.buffer.line(y).unwrap().text, x);
+                Cursor::from_byte(&self.buffer, byte, y)
+            },
+            (None, Some(order)) => Cursor::from_byte(&self.buffer, self.layout_byte(y, &order, x), y),
+            (None, None) => Cursor::from(&self.buffer, x, y)
+        };
         self.deselect();
+        self.single_cursor();
     }
 
-    fn push_undo(&mut self, item: (Cursor, Edit)) {
-        self.redo_stack.clear();
-        self.undo_stack.push(item);
+    // The cursor's column on screen, before scrolling
+    fn display_column(&self) -> usize {
+        let line = self.buffer.line(self.cursor.row).unwrap();
+        match (&self.table, self.layout(self.cursor.row)) {
+            (Some(table), _) => table.column_at(&line.text, self.cursor.byte),
+            (None, Some(order)) => self.layout_column(self.cursor.row, &order, self.cursor.byte),
+            (None, None) => min(self.cursor.column, line.width(self.buffer.settings.tab_width))
+        }
     }
 
-    pub fn insert(&mut self, ch: char) {
-        let edit = if let Some((l, r)) = &self.selection {
-            self.cursor = l.clone();
-            let start = Point { x: l.byte, y: l.row };
-            let end = Point { x: r.byte, y: r.row };
-            Edit::Replace(start, end, ch.to_string())
-        } else {
-            let pt = Point { x: self.cursor.byte, y: self.cursor.row };
-            Edit::Insert(ch, pt)
-        };
+    fn is_header_pinned_at(&self, top: usize) -> bool {
+        top > 0 && self.table.as_ref().is_some_and(|t| t.header)
+    }
 
-        if let Some(undo) = self.buffer.execute(&edit) {
-            let before = self.cursor.clone();
-            self.cursor.step_cursor(&self.buffer, Direction::Right);
-            self.push_undo((before, undo));
-        }
+    fn is_header_pinned(&self) -> bool {
+        self.is_header_pinned_at(self.origin.y)
+    }
+
+    fn delimiter(&self) -> char {
+        match &self.table {
+            Some(table) => table.delimiter,
+            None => table::delimiter(self.filetype(), &self.buffer.line(0).unwrap().text)
+        }
+    }
+
+    // Switch between aligned columns and the raw text, returns whether columns are now aligned
+    pub fn toggle_table(&mut self) -> bool {
+        self.table = match self.table {
+            Some(_) => None,
+            None => Some(Table::new(self.delimiter()))
+        };
+        self.table.is_some()
+    }
+
+    // Pin (or unpin) the first line as a header row, returns `None` if columns aren't aligned
+    pub fn toggle_header(&mut self) -> Option<bool> {
+        let table = self.table.as_mut()?;
+        table.header = !table.header;
+        Some(table.header)
+    }
+
+    // Move to the start of the next (or previous) field, returns `false` if there's none
+    pub fn goto_column(&mut self, forward: bool) -> bool {
+        let row = self.cursor.row;
+        let byte = self.cursor.byte;
+        let fields = table::fields(&self.buffer.line(row).unwrap().text, self.delimiter());
+
+        let target = if forward {
+            fields.iter().find(|f| f.start > byte)
+        } else {
+            fields.iter().rev().find(|f| f.start < byte)
+        };
+
+        match target {
+            Some(field) => {
+                self.cursor = Cursor::from_byte(&self.buffer, field.start, row);
+                self.deselect();
+                true
+            },
+            None => false
+        }
+    }
+
+    // The identifier (letters, digits and underscores) under or just before the cursor
+    pub fn word_at_cursor(&self) -> Option<&str> {
+        let text = &self.buffer.line(self.cursor.row)?.text;
+        self.word_range_at_cursor().map(|r| &text[r])
+    }
+
+    // Where the word the cursor is in or next to is in its line
+    fn word_range_at_cursor(&self) -> Option<Range<usize>> {
+        let text = &self.buffer.line(self.cursor.row)?.text;
+        let byte = self.cursor.byte;
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+        let start = text[..byte]
+            .char_indices()
+            .rev()
+            .take_while(|&(_, c)| is_word(c))
+            .last()
+            .map_or(byte, |(i, _)| i);
+        let end = text[byte..]
+            .char_indices()
+            .find(|&(_, c)| !is_word(c))
+            .map_or(text.len(), |(i, _)| byte + i);
+
+        Some(start..end).filter(|r| !r.is_empty())
+    }
+
+    // Replace the selection, or else the word at the cursor, with `change` of it as one
+    // edit. Returns `false` if there's neither
+    pub fn change_text<F>(&mut self, change: F) -> bool where F : FnOnce(&str) -> String {
+        let (start, end) = match (self.selection, self.word_range_at_cursor()) {
+            (Some(s), _) => (s.start, s.end),
+            (None, Some(r)) => (Point { x: r.start, y: self.cursor.row }, Point { x: r.end, y: self.cursor.row }),
+            (None, None) => return false
+        };
+        let text = self.buffer.slice(&start, &end);
+        let changed = change(&text);
+        if changed == text {
+            return true;
+        }
+
+        let before = self.cursor.clone();
+        let selected = self.selection.is_some();
+        if let Some(undo) = self.execute(&Edit::Replace(start, end, changed)) {
+            let end = match &undo {
+                Edit::Replace(_, end, _) => *end,
+                _ => start
+            };
+            self.push_undo((before.clone(), undo));
+            if selected {
+                self.set_selection(Selection::new(start, end));
+            } else {
+                self.goto_byte(before.row, before.byte);
+            }
+        }
+        true
+    }
+
+    // Zero-based row and column of the cursor
+    pub fn cursor_position(&self) -> (usize, usize) {
+        (self.cursor.row, self.cursor.column)
+    }
+
+    fn inline_blame_line(&self) -> Option<&BlameLine> {
+        if self.inline_blame && self.blame.is_current(&self.buffer) {
+            self.blame.line(self.cursor.row)
+        } else {
+            None
+        }
+    }
+
+    // Show who last changed the current line, once git has been asked
+    pub fn blame(&mut self) {
+        if self.path().as_os_str().is_empty() {
+            self.set_message(Message::Warning(String::from("Buffer has no file to blame")));
+            return;
+        }
+
+        self.blame.request(&self.buffer);
+        if self.blame.is_current(&self.buffer) {
+            self.describe_blame();
+        } else {
+            self.show_blame = true;
+        }
+    }
+
+    fn describe_blame(&mut self) {
+        let m = match self.blame.line(self.cursor.row) {
+            Some(line) => Message::Info(line.describe()),
+            None if self.blame.is_available() => Message::Info(String::from("No blame for this line")),
+            None => Message::Warning(String::from("File isn't tracked by git"))
+        };
+        self.set_message(m);
+    }
+
+    // Check on background work, returns `true` if the screen needs redrawing
+    pub fn poll(&mut self) -> bool {
+        let loaded = self.poll_loading();
+        let followed = self.poll_follow();
+        self.poll_blame() || followed || loaded
+    }
+
+    // Show what's been read of a large file so far, and how much
+    fn poll_loading(&mut self) -> bool {
+        match self.buffer.load_more() {
+            Ok(false) => false,
+            Ok(true) => {
+                let m = match self.buffer.loading_percent() {
+                    Some(percent) => format!("Loading… {}%", percent),
+                    None => format!("Loaded {} lines", self.buffer.line_count())
+                };
+                self.set_message(Message::Info(m));
+                true
+            },
+            Err(e) => {
+                self.set_message(Message::Error(format!("Stopped reading the file: {}", e)));
+                true
+            }
+        }
+    }
+
+    fn poll_blame(&mut self) -> bool {
+        if !self.blame.poll() {
+            return false;
+        }
+
+        // The buffer may have changed while git was running
+        if !self.blame.is_current(&self.buffer) && (self.show_blame || self.inline_blame) {
+            self.blame.request(&self.buffer);
+            return false;
+        }
+
+        if self.show_blame {
+            self.show_blame = false;
+            self.describe_blame();
+        }
+        true
+    }
+
+    // Start or stop reading what's appended to the file, like `tail -f`,
+    // returns whether it's now being followed
+    pub fn toggle_follow(&mut self) -> Result<bool, String> {
+        if let Some(follow) = self.follow.take() {
+            self.buffer.set_readonly(follow.readonly);
+            return Ok(false);
+        }
+
+        if self.path().as_os_str().is_empty() {
+            return Err(String::from("Buffer has no file to follow"));
+        }
+        if self.is_dirty() {
+            return Err(String::from("Save changes before following the file"));
+        }
+
+        let follow = Follow::start(self.path(), self.is_readonly()).map_err(|e| e.to_string())?;
+        self.follow = Some(follow);
+        self.buffer.set_readonly(true);
+
+        // The file may be truncated and read again, which old edits can't be undone across
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.goto(self.buffer.line_count() - 1, 0);
+        Ok(true)
+    }
+
+    pub fn is_following(&self) -> bool {
+        self.follow.is_some()
+    }
+
+    fn poll_follow(&mut self) -> bool {
+        let follow = match self.follow.as_mut() {
+            Some(f) => f,
+            None => return false
+        };
+
+        match follow.read(self.buffer.path()) {
+            Ok(None) => return false,
+            Ok(Some(Update::Truncated)) => {
+                follow.restart();
+                self.buffer.clear();
+            },
+            Ok(Some(Update::Appended(text))) => {
+                self.buffer.append(&text, follow.new_line);
+                follow.new_line = true;
+            },
+            Err(e) => {
+                self.buffer.set_readonly(follow.readonly);
+                self.follow = None;
+                self.set_message(Message::Error(format!("Stopped following: {}", e)));
+            }
+        }
+
+        // Keep the end of the file in view
+        self.goto(self.buffer.line_count() - 1, 0);
+        true
+    }
+
+    // Move the conflicts below an edit with the text, leaving the rows it touched to be
+    // looked through again
+    fn conflicts_edited(&mut self, change: &Change) {
+        // If some other change was missed, `update_conflicts` starts over
+        if self.conflicts_revision.is_none_or(|r| r + 1 != self.buffer.revision()) {
+            return;
+        }
+        self.conflicts_revision = Some(self.buffer.revision());
+
+        let (first, inserted) = (change.start.y, change.inserted.y);
+        for c in self.conflicts.iter_mut().filter(|c| c.end >= first) {
+            c.start = change.map_row(c.start);
+            c.base = c.base.map(|b| change.map_row(b));
+            c.separator = change.map_row(c.separator);
+            c.end = change.map_row(c.end);
+        }
+        self.conflicts_stale = Some(match self.conflicts_stale.take() {
+            Some(s) => change.map_row(s.start).min(first)..change.map_row(s.end).max(inserted + 1),
+            None => first..inserted + 1
+        });
+    }
+
+    fn update_conflicts(&mut self) {
+        let stale = self.conflicts_stale.take();
+        if self.conflicts_revision != Some(self.buffer.revision()) {
+            self.conflicts_revision = Some(self.buffer.revision());
+            self.conflicts = conflict::find(self.buffer.lines().iter().map(|l| l.text.as_str()));
+        } else if let Some(stale) = stale {
+            self.conflicts = conflict::refind(&self.conflicts, stale, |row| self.buffer.line(row).map(|l| l.text.as_str()));
+        }
+    }
+
+    // Move to the next (or previous) merge conflict, returns `false` if there's none
+    pub fn goto_conflict(&mut self, forward: bool) -> bool {
+        self.update_conflicts();
+        let row = self.cursor.row;
+        let target = if forward {
+            self.conflicts.iter().find(|c| c.start > row)
+        } else {
+            self.conflicts.iter().rev().find(|c| c.start < row)
+        };
+
+        match target.map(|c| c.start) {
+            Some(r) => {
+                self.goto(r, 0);
+                true
+            },
+            None => false
+        }
+    }
+
+    // Replace the conflict under the cursor with our side, theirs, or both (ours first)
+    // as a single edit, returns `false` if the cursor isn't in a conflict
+    pub fn resolve_conflict(&mut self, ours: bool, theirs: bool) -> bool {
+        self.update_conflicts();
+        let row = self.cursor.row;
+        let conflict = match self.conflicts.iter().find(|c| c.contains(row)) {
+            Some(c) => c.clone(),
+            None => return false
+        };
+
+        let mut rows: Vec<usize> = Vec::new();
+        if ours {
+            rows.extend(conflict.ours());
+        }
+        if theirs {
+            rows.extend(conflict.theirs());
+        }
+        let mut text: Vec<&str> = rows
+            .into_iter()
+            .filter_map(|r| self.buffer.line(r))
+            .map(|l| l.text.as_str())
+            .collect();
+
+        // Take the line break after the closing marker too, unless it's the last line
+        let start = Point { x: 0, y: conflict.start };
+        let end = if conflict.end + 1 < self.buffer.line_count() {
+            if !text.is_empty() {
+                text.push("");
+            }
+            Point { x: 0, y: conflict.end + 1 }
+        } else {
+            let len = self.buffer.line(conflict.end).map_or(0, |l| l.text.len());
+            Point { x: len, y: conflict.end }
+        };
+
+        let text = text.join("\n");
+        let before = self.cursor.clone();
+        if let Some(undo) = self.execute(&Edit::Replace(start, end, text)) {
+            self.push_undo((before, undo));
+        }
         self.deselect();
+        self.goto(conflict.start, 0);
+        true
+    }
+
+    // One level of indentation, as the buffer's settings would insert it
+    pub fn indent_unit(&self) -> String {
+        let settings = &self.buffer.settings;
+        if settings.expand_tabs {
+            " ".repeat(settings.tab_width.max(1))
+        } else {
+            String::from("\t")
+        }
+    }
+
+    // Insert `text` shifted to line up with the indentation before the cursor
+    pub fn insert_indented(&mut self, text: &str) {
+        let line = &self.buffer.line(self.cursor.row).expect("row out-of-bounds").text;
+        let settings = &self.buffer.settings;
+        let target = indent::width(indent::leading(&line[..self.cursor.byte]), settings.tab_width);
+        let text = indent::reindent(text, target, settings.tab_width, settings.expand_tabs);
+        self.insert_str(&text);
+    }
+
+    // Replace the selection (or the whole buffer) with the result of `format` as a single
+    // edit, keeping the cursor on the same line where possible
+    pub fn reformat<F>(&mut self, format: F) -> Result<(), String>
+        where F : FnOnce(&str) -> Result<String, String>
+    {
+        let (start, end) = match &self.selection {
+            Some(selection) => (selection.start, selection.end),
+            None => {
+                let last = self.buffer.line_count() - 1;
+                let len = self.buffer.line(last).map_or(0, |l| l.text.len());
+                (Point::default(), Point { x: len, y: last })
+            }
+        };
+
+        let text = self.buffer.slice(&start, &end);
+        let formatted = format(&text)?;
+        if formatted == text {
+            return Ok(());
+        }
+
+        let before = self.cursor.clone();
+        let row = before.row;
+        if let Some(undo) = self.execute(&Edit::Replace(start, end, formatted)) {
+            self.push_undo((before, undo));
+        }
+        self.goto(row, 0);
+        Ok(())
+    }
+
+    // Change the Markdown table under the cursor with `edit`, then realign it as a single edit.
+    // `edit` is given the cursor's row and cell, and may move it. Returns `false` if the
+    // cursor isn't in a table
+    fn edit_table<F>(&mut self, edit: F) -> bool
+        where F : FnOnce(&mut MarkdownTable, &mut (usize, usize))
+    {
+        let lines = self.lines();
+        let range = match markdown::find(&lines, self.cursor.row) {
+            Some(r) => r,
+            None => return false
+        };
+
+        let mut table = MarkdownTable::parse(&lines[range.clone()]);
+        let mut position = (
+            table.row_of(self.cursor.row - range.start),
+            markdown::cell_at(lines[self.cursor.row], self.cursor.byte)
+        );
+        edit(&mut table, &mut position);
+
+        let old = lines[range.clone()].join("\n");
+        let text = table.render().join("\n");
+        let start = Point { x: 0, y: range.start };
+        let end = Point { x: lines[range.end - 1].len(), y: range.end - 1 };
+
+        if text != old {
+            let before = self.cursor.clone();
+            if let Some(undo) = self.execute(&Edit::Replace(start, end, text)) {
+                self.push_undo((before, undo));
+            }
+        }
+
+        let row = position.0.min(table.rows.len() - 1);
+        let cell = position.1.min(table.columns() - 1);
+        let y = range.start + table.line_of(row);
+        let byte = markdown::cell_start(&self.buffer.line(y).unwrap().text, cell);
+        self.cursor = Cursor::from_byte(&self.buffer, byte, y);
+        self.deselect();
+        true
+    }
+
+    pub fn align_table(&mut self) -> bool {
+        self.edit_table(|_, _| ())
+    }
+
+    // Move to the next (or previous) cell of a Markdown table, adding a row after the last
+    // one. Returns `false` if the cursor isn't in a table
+    pub fn next_cell(&mut self, forward: bool) -> bool {
+        if self.filetype() != Some("markdown") {
+            return false;
+        }
+
+        self.edit_table(|table, (row, cell)| {
+            let columns = table.columns();
+            if forward {
+                if *cell + 1 < columns {
+                    *cell += 1;
+                } else {
+                    if *row + 1 == table.rows.len() {
+                        table.insert_row(*row + 1);
+                    }
+                    *row += 1;
+                    *cell = 0;
+                }
+            } else if *cell > 0 {
+                *cell = (*cell).min(columns) - 1;
+            } else if *row > 0 {
+                *row -= 1;
+                *cell = columns - 1;
+            }
+        })
+    }
+
+    // Add an empty row below the cursor's row of a Markdown table
+    pub fn insert_table_row(&mut self) -> bool {
+        self.edit_table(|table, (row, _)| {
+            *row += 1;
+            table.insert_row(*row);
+        })
+    }
+
+    // Add an empty column after the cursor's column of a Markdown table
+    pub fn insert_table_column(&mut self) -> bool {
+        self.edit_table(|table, (_, cell)| {
+            *cell = (*cell + 1).min(table.columns());
+            table.insert_column(*cell);
+        })
+    }
+
+    pub fn remove_table_column(&mut self) -> bool {
+        self.edit_table(|table, (_, cell)| table.remove_column(*cell))
+    }
+
+    // Move to the start of the next (or previous) changed region, returns `false` if there's none
+    pub fn goto_hunk(&mut self, forward: bool) -> bool {
+        let row = self.cursor.row;
+        let target = match self.gutter.as_ref() {
+            Some(g) if forward => g.hunk_starts().find(|&r| r > row),
+            Some(g) => g.hunk_starts().filter(|&r| r < row).last(),
+            None => None
+        };
+
+        match target {
+            Some(r) => {
+                self.goto(r, 0);
+                true
+            },
+            None => false
+        }
+    }
+
+    // Scroll a screenful down (or up), taking the cursor along to the same desired column
+    pub fn page(&mut self, forward: bool) {
+        let (_, height) = self.get_viewport_size();
+        let count = self.rows().end;
+        let (top, direction) = if forward {
+            (min(self.origin.y + height, count.saturating_sub(height)), Direction::Down)
+        } else {
+            (self.origin.y.saturating_sub(height), Direction::Up)
+        };
+        self.origin.y = top;
+        self.cursor.move_cursor(&self.buffer, direction, height);
+        self.clamp_cursor();
+        if self.line_anchor.is_some() {
+            self.select_lines();
+        } else {
+            self.deselect();
+        }
+    }
+
+    // Scroll `lines` down (or up), only moving the cursor if it would go out of view
+    pub fn scroll(&mut self, forward: bool, lines: usize) {
+        let (_, height) = self.get_viewport_size();
+        let rows = self.rows();
+        self.origin.y = if forward {
+            min(self.origin.y + lines, rows.end.saturating_sub(height)).max(self.origin.y)
+        } else {
+            max(self.origin.y.saturating_sub(lines), rows.start)
+        };
+
+        // Keep it away from the edges like `update_viewport` does, except at the top
+        let margin = min(self.scroll_margin, height.saturating_sub(1) / 2);
+        let first = if self.origin.y == 0 { 0 } else { self.origin.y + margin };
+        let last = (self.origin.y + height).saturating_sub(margin + 1);
+        let (direction, steps) = if self.cursor.row < first {
+            (Direction::Down, first - self.cursor.row)
+        } else if self.cursor.row > last {
+            (Direction::Up, self.cursor.row - last)
+        } else {
+            return;
+        };
+        self.cursor.move_cursor(&self.buffer, direction, steps);
+        self.clamp_cursor();
+        if self.line_anchor.is_some() {
+            self.select_lines();
+        } else {
+            self.deselect();
+        }
+    }
+
+    pub fn is_viewing(&self) -> bool {
+        self.view
+    }
+
+    // Switch between view mode and editing, returns whether the screen is now in view mode
+    pub fn toggle_view(&mut self) -> bool {
+        self.view = !self.view;
+        self.view
+    }
+
+    // Select the next (or previous) match of `pattern`, wrapping around the buffer. Case is
+    // ignored unless `pattern` has capitals. Returns `false` if there's no match
+    pub fn search(&mut self, pattern: &str, forward: bool) -> bool {
+        self.search = Some(String::from(pattern)).filter(|p| !p.is_empty());
+        let pattern = match &self.search {
+            Some(p) => p,
+            None => return false
+        };
+
+        // Look through every line once, starting and ending on the cursor's
+        let rows = self.rows();
+        let count = rows.len();
+        let (row, byte) = (self.cursor.row - rows.start, self.cursor.byte);
+        for step in 0..=count {
+            let y = rows.start + if forward { (row + step) % count } else { (row + count - step % count) % count };
+            let text = &self.buffer.line(y).unwrap().text;
+            let mut starts = text
+                .char_indices()
+                .map(|(i, _)| i)
+                .filter(|&i| match step {
+                    0 if forward => i > byte,
+                    0 => i < byte,
+                    s if s == count && forward => i <= byte,
+                    s if s == count => i >= byte,
+                    _ => true
+                })
+                .filter(|&i| match_at(text, i, pattern).is_some());
+            let found = if forward { starts.next() } else { starts.next_back() };
+
+            if let Some(i) = found {
+                let end = match_at(text, i, pattern).unwrap_or(i);
+                let start = Cursor::from_byte(&self.buffer, i, y);
+                let end = Cursor::from_byte(&self.buffer, end, y);
+                self.cursor = start.clone();
+                self.selection = Selection::new(start.point(), end.point());
+                return true;
+            }
+        }
+        false
+    }
+
+    // Repeat the last search, returns `None` if there hasn't been one
+    pub fn search_again(&mut self, forward: bool) -> Option<bool> {
+        let pattern = self.search.clone()?;
+        Some(self.search(&pattern, forward))
+    }
+
+    // Start an incremental search from the cursor
+    pub fn begin_incremental(&mut self) {
+        self.incremental = Some(Incremental {
+            pattern: String::new(),
+            found: true,
+            cursor: self.cursor.clone(),
+            selection: self.selection,
+            origin: self.origin,
+            search: self.search.clone()
+        });
+    }
+
+    pub fn is_incremental(&self) -> bool {
+        self.incremental.is_some()
+    }
+
+    // Search for `pattern` again from where the incremental search started
+    pub fn update_incremental(&mut self, pattern: &str) {
+        let start = match self.incremental.as_mut() {
+            Some(i) => i,
+            None => return
+        };
+        start.pattern = String::from(pattern);
+        self.cursor = start.cursor.clone();
+        self.selection = start.selection;
+        self.origin = start.origin;
+
+        let found = pattern.is_empty() || self.search(pattern, true);
+        if let Some(i) = self.incremental.as_mut() {
+            i.found = found;
+        }
+    }
+
+    // Move to the next (or previous) match of the incremental search
+    pub fn step_incremental(&mut self, forward: bool) {
+        let pattern = match &self.incremental {
+            Some(i) if !i.pattern.is_empty() => i.pattern.clone(),
+            _ => return
+        };
+        let found = self.search(&pattern, forward);
+        if let Some(i) = self.incremental.as_mut() {
+            i.found = found;
+        }
+    }
+
+    // Finish the incremental search, going back to where it started unless `accept`ed.
+    // Returns the pattern if it was accepted without any matches
+    pub fn end_incremental(&mut self, accept: bool) -> Option<String> {
+        let start = self.incremental.take()?;
+        if !accept {
+            self.cursor = start.cursor;
+            self.selection = start.selection;
+            self.origin = start.origin;
+            self.search = start.search;
+            None
+        } else if start.pattern.is_empty() {
+            self.search = start.search;
+            None
+        } else {
+            Some(start.pattern).filter(|_| !start.found)
+        }
+    }
+
+    // Byte ranges of line `row` matching the incremental search, to be highlighted
+    fn match_ranges(&self, row: usize) -> Vec<Range<usize>> {
+        let pattern = match &self.incremental {
+            Some(i) if !i.pattern.is_empty() => &i.pattern,
+            _ => return Vec::new()
+        };
+        let text = &self.buffer.line(row).expect("row out-of-bounds").text;
+
+        let mut ranges = Vec::new();
+        let mut next = 0;
+        for (i, _) in text.char_indices() {
+            if i < next {
+                continue;
+            }
+            if let Some(end) = match_at(text, i, pattern) {
+                ranges.push(i..end);
+                next = end;
+            }
+        }
+        ranges
+    }
+
+    // Move the cursor to `row` and `column` (zero-based), clamped to the buffer
+    pub fn goto(&mut self, row: usize, column: usize) {
+        let row = min(row, self.buffer.line_count() - 1);
+        self.cursor = Cursor::from(&self.buffer, column, row);
+        self.deselect();
+    }
+
+    // Move the cursor to byte `byte` of `row`, clamped to the buffer
+    pub fn goto_byte(&mut self, row: usize, byte: usize) {
+        let row = min(row, self.buffer.line_count() - 1);
+        self.cursor = Cursor::from_byte(&self.buffer, byte, row);
+        self.deselect();
+    }
+
+    // Describe the grapheme under the cursor and its position in the file
+    pub fn describe_cursor(&self) -> String {
+        let line = self.buffer.line(self.cursor.row).unwrap();
+        let ending = self.buffer.line_ending().value().len();
+        let offset = self.cursor.offset + self.cursor.row * ending;
+
+        let grapheme = line.column_indices(self.buffer.settings.tab_width)
+            .find(|c| c.byte == self.cursor.byte)
+            .map(|c| c.grapheme);
+
+        match grapheme {
+            None => format!("End of line, offset {}", offset),
+            Some(g) => {
+                let codepoints = g.chars()
+                    .map(|c| format!("U+{:04X}", c as u32))
+                    .collect::<Vec<String>>()
+                    .join(" ");
+                let bytes = g.bytes()
+                    .map(|b| format!("{:02X}", b))
+                    .collect::<Vec<String>>()
+                    .join(" ");
+                format!("'{}' {} (bytes {}) offset {} width {}",
+                    g, codepoints, bytes, offset, g.display_width())
+            }
+        }
+    }
+
+    pub fn selected_text(&self) -> Option<String> {
+        let selection = self.selection.as_ref()?;
+        Some(self.buffer.slice(&selection.start, &selection.end))
+    }
+
+    // Remove the selected text, returns it unless nothing was removed
+    pub fn cut(&mut self) -> Option<String> {
+        let text = self.selected_text()?;
+        let revision = self.buffer.revision();
+        self.backspace();
+        if self.buffer.revision() == revision {
+            return None;
+        }
+        self.kill(&text);
+        Some(text)
+    }
+
+    // Cut from the cursor to the end of the line, or the line ending when it's already
+    // there, into the kill ring
+    pub fn kill_line(&mut self) {
+        let y = self.cursor.row;
+        let len = self.buffer.line(y).expect("row out-of-bounds").text.len();
+        let end = if self.cursor.byte < len {
+            Point { x: len, y }
+        } else if y + 1 < self.rows().end {
+            Point { x: 0, y: y + 1 }
+        } else {
+            return;
+        };
+        self.selection = Selection::new(self.cursor.point(), end);
+        self.cut();
+    }
+
+    // Cut the cursor's whole line, with its ending, into the kill ring
+    pub fn kill_whole_line(&mut self) {
+        self.select_line();
+        self.cut();
+    }
+
+    // Keep `text` in the kill ring, dropping the oldest entry once it's full
+    pub fn kill(&mut self, text: &str) {
+        self.kill_ring.push(String::from(text));
+        if self.kill_ring.len() > KILL_RING_SIZE {
+            self.kill_ring.remove(0);
+        }
+    }
+
+    // Insert the most recently killed text at the cursor
+    pub fn yank(&mut self) {
+        if self.kill_ring.is_empty() {
+            self.set_message(Message::Warning(String::from("Kill ring is empty")));
+            return;
+        }
+        self.yank_entry(0);
+    }
+
+    // Replace the text just yanked with the entry before it in the kill ring, going round
+    // to the most recent after the oldest
+    pub fn yank_pop(&mut self) {
+        let index = match &self.yank {
+            Some(y) if y.revision == self.buffer.revision() => y.index + 1,
+            _ => {
+                self.set_message(Message::Warning(String::from("Nothing was just yanked")));
+                return;
+            }
+        };
+        if let Some(yank) = self.yank.take() {
+            self.selection = Selection::new(yank.start, yank.end);
+        }
+        self.yank_entry(index % self.kill_ring.len());
+    }
+
+    fn yank_entry(&mut self, index: usize) {
+        let text = self.kill_ring[self.kill_ring.len() - 1 - index].clone();
+        let start = match &self.selection {
+            Some(s) => s.start,
+            None => Point { x: self.cursor.byte, y: self.cursor.row }
+        };
+        let revision = self.buffer.revision();
+        self.insert_str(&text);
+
+        self.yank = if self.buffer.revision() != revision {
+            let end = Point { x: self.cursor.byte, y: self.cursor.row };
+            Some(Yank { index, start, end, revision: self.buffer.revision() })
+        } else {
+            None
+        };
+    }
+
+    // Count lines, words, graphemes and bytes in the selection or whole buffer
+    pub fn count(&self) -> String {
+        let (what, lines, text) = match &self.selection {
+            Some(Selection { start, end }) => {
+                ("Selection", end.y - start.y + 1, self.buffer.slice(start, end))
+            },
+            None => ("Buffer", self.buffer.line_count(), self.buffer.to_string())
+        };
+
+        format!("{}: {} lines, {} words, {} characters, {} bytes",
+            what,
+            lines,
+            text.unicode_words().count(),
+            text.graphemes(true).count(),
+            text.len()
+        )
+    }
+
+    pub fn cycle_line_ending(&mut self) {
+        if self.is_readonly() {
+            self.set_message(Message::Warning(String::from(READONLY)));
+            return;
+        }
+        self.buffer.cycle_line_ending();
+    }
+
+    // Apply `edit` to the buffer unless it's locked, returns the edit that undoes it.
+    // The selection is moved along with the text
+    fn execute(&mut self, edit: &Edit) -> Option<Edit> {
+        let reason = if self.follow.is_some() {
+            "Buffer is being followed (C-x F to stop)"
+        } else if self.view {
+            "Buffer is in view mode (C-x V to leave)"
+        } else if self.is_readonly() {
+            READONLY
+        } else if !self.is_editable(edit) {
+            "Edit is outside the narrowed lines (C-x W to widen)"
+        } else if self.touches_protected(edit) {
+            "Text is protected (C-x L to unprotect)"
+        } else {
+            let (undo, change) = self.buffer.execute(edit)?;
+            self.highlights.edited(change.start.y, self.buffer.revision());
+            if let Some(gutter) = self.gutter.as_mut() {
+                gutter.edited(&change, self.buffer.revision());
+            }
+            self.conflicts_edited(&change);
+            if let Some(selection) = self.selection.as_mut() {
+                if !selection.adjust(&change) {
+                    self.selection = None;
+                }
+            }
+            self.protected.retain_mut(|p| p.adjust(&change));
+            for at in self.extra_cursors.iter_mut() {
+                *at = change.map(*at);
+            }
+            if let Some(narrow) = self.narrow.as_mut() {
+                narrow.end = change.map(Point { x: 0, y: narrow.end }).y;
+            }
+            return Some(undo);
+        };
+        self.set_message(Message::Warning(String::from(reason)));
+        None
+    }
+
+    // Whether `edit` stays within the narrowed rows
+    fn is_editable(&self, edit: &Edit) -> bool {
+        let (start, end) = self.buffer.span(edit);
+        let rows = self.rows();
+        rows.contains(&start.y) && rows.contains(&end.y)
+    }
+
+    // Whether `edit` would change protected text, inserting right before or after it is fine
+    fn touches_protected(&self, edit: &Edit) -> bool {
+        let (start, end) = self.buffer.span(edit);
+        self.protected.iter().any(|p| {
+            if start == end { p.start < start && start < p.end } else { start < p.end && p.start < end }
+        })
+    }
+
+    // Stop the selected text from being edited, returns `false` if nothing is selected
+    pub fn protect(&mut self) -> bool {
+        match self.selection.take() {
+            Some(selection) => {
+                self.protected.retain(|p| !selection.contains(p));
+                self.protected.push(selection);
+                self.deselect();
+                true
+            },
+            None => false
+        }
+    }
+
+    // Let the protected text under the cursor be edited again, returns `false` if there's none
+    pub fn unprotect(&mut self) -> bool {
+        let at = self.cursor.point();
+        let count = self.protected.len();
+        self.protected.retain(|p| !(p.start <= at && at < p.end));
+        self.protected.len() < count
+    }
+
+    // Keep an edit to undo later, in the same group as the ones before if it carries on
+    // typing (or deleting) from where they left off, without a pause or starting a new word
+    fn push_undo(&mut self, item: (Cursor, Edit)) {
+        self.redo_stack.clear();
+        let (before, undo) = item;
+        let ch = match &undo {
+            Edit::Insert(ch, _) => Some(*ch),
+            Edit::Delete(pt) | Edit::Overwrite(_, pt) => {
+                self.buffer.line(pt.y).and_then(|l| l.text.get(pt.x..)?.chars().next())
+            },
+            _ => None
+        };
+
+        let now = Instant::now();
+        let joined = match (self.typing, ch, self.undo_stack.last()) {
+            (Some((previous, at, time)), Some(ch), Some((_, last, _))) => {
+                let new_word = previous.is_whitespace() && !ch.is_whitespace();
+                at == before.point()
+                    && now - time < self.undo_idle
+                    && mem::discriminant(last) == mem::discriminant(&undo)
+                    && !new_word
+            },
+            _ => false
+        };
+        if !joined {
+            self.undo_group += 1;
+        }
+        self.typing = ch.map(|ch| (ch, self.cursor.point(), now));
+        self.undo_stack.push((before, undo, self.undo_group));
+    }
+
+    // Put the undo steps from `first` on in the group of the first one, which later typing
+    // can join, so they're undone together
+    fn group_undo_since(&mut self, first: usize) {
+        if let Some(&(_, _, group)) = self.undo_stack.get(first) {
+            for (_, _, g) in &mut self.undo_stack[first..] {
+                *g = group;
+            }
+            self.undo_group = group;
+        }
+    }
+
+    pub fn insert(&mut self, ch: char) {
+        let edit = if let Some(selection) = &self.selection {
+            self.cursor = Cursor::from_byte(&self.buffer, selection.start.x, selection.start.y);
+            Edit::Replace(selection.start, selection.end, ch.to_string())
+        } else {
+            let pt = Point { x: self.cursor.byte, y: self.cursor.row };
+            Edit::Insert(ch, pt)
+        };
+
+        if let Some(undo) = self.execute(&edit) {
+            let before = self.cursor.clone();
+            match &undo {
+                // A new line, which may have started indented
+                Edit::Cut(_, end) => self.cursor = Cursor::from_byte(&self.buffer, end.x, end.y),
+                _ => self.cursor.step_cursor(&self.buffer, Direction::Right)
+            }
+            self.push_undo((before, undo));
+        }
+        self.deselect();
+    }
+
+    // Insert `s` at the cursor (replacing the selection, if any) as a single edit
+    pub fn insert_str(&mut self, s: &str) {
+        let before = self.cursor.clone();
+        let edit = if let Some(selection) = &self.selection {
+            Edit::Replace(selection.start, selection.end, s.to_string())
+        } else {
+            let pt = Point { x: self.cursor.byte, y: self.cursor.row };
+            Edit::Paste(pt, s.to_string())
+        };
+
+        if let Some(undo) = self.execute(&edit) {
+            if let Edit::Cut(_, end) | Edit::Replace(_, end, _) = &undo {
+                self.cursor = Cursor::from_byte(&self.buffer, end.x, end.y);
+            }
+            self.push_undo((before, undo));
+        }
+        self.deselect();
+    }
+
+    // Indent to the next tab stop, with spaces if the buffer expands tabs,
+    // or move to the next cell in a Markdown table
+    pub fn tab(&mut self) {
+        if self.next_cell(true) {
+            return;
+        }
+
+        let settings = &self.buffer.settings;
+        if settings.expand_tabs {
+            let width = settings.tab_width.max(1);
+            let count = width - self.cursor.column % width;
+            self.insert_str(&" ".repeat(count));
+        } else {
+            self.insert('\t');
+        }
     }
 
     pub fn overwrite(&mut self, ch: char) {
@@ -435,7 +2305,7 @@ impl Screen {
         let pt = Point { x: self.cursor.byte, y: self.cursor.row };
         let edit = Edit::Overwrite(ch, pt);
 
-        if let Some(undo) = self.buffer.execute(&edit) {
+        if let Some(undo) = self.execute(&edit) {
             let before = self.cursor.clone();
             self.cursor.step_cursor(&self.buffer, Direction::Right);
             
@@ -445,33 +2315,27 @@ impl Screen {
 
     pub fn backspace(&mut self) {
         let at_zero = self.cursor.row == 0 && self.cursor.column == 0;
-        let has_select = self.selection.is_some();
-        let selection = self.selection.as_ref();
+        let selection = self.selection;
 
-        if !has_select && at_zero { return; }
+        if selection.is_none() && at_zero { return; }
 
         let before = self.cursor.clone();
-        if has_select {
+        if let Some(selection) = &selection {
             // To delete a selection, set cursor to left edge
-            self.cursor = selection.map(|(l, _)| l.clone()).unwrap();
+            self.cursor = Cursor::from_byte(&self.buffer, selection.start.x, selection.start.y);
         } else {
             // For a regular backspace, step once to the left
             self.cursor.step_cursor(&self.buffer, Direction::Left);
         }
 
-        let edit = if has_select { 
-            let (start, end) = selection
-                .map(|(l, r)| (
-                    Point { x: l.byte, y: l.row },
-                    Point { x: r.byte, y: r.row }
-                )).unwrap();
-            Edit::Cut(start, end) 
+        let edit = if let Some(selection) = selection { 
+            Edit::Cut(selection.start, selection.end) 
         } else {
             let pt = Point { x: self.cursor.byte, y: self.cursor.row };
             Edit::Delete(pt)
         };
 
-        if let Some(undo) = self.buffer.execute(&edit) {
+        if let Some(undo) = self.execute(&edit) {
             self.push_undo((before, undo));
         }
         self.deselect();
@@ -485,7 +2349,7 @@ impl Screen {
         let pt = Point { x: self.cursor.byte, y: self.cursor.row };
         let edit = Edit::Delete(pt);
 
-        if let Some(undo) = self.buffer.execute(&edit) {
+        if let Some(undo) = self.execute(&edit) {
             let before = self.cursor.clone();
             self.push_undo((before, undo));
         }
@@ -493,11 +2357,13 @@ impl Screen {
 
     pub fn home(&mut self) {
         self.cursor.home(&self.buffer);
+        self.move_extra_cursors(Cursor::home);
         self.deselect();
     }
 
     pub fn end(&mut self) {
         self.cursor.end(&self.buffer);
+        self.move_extra_cursors(Cursor::end);
         self.deselect();
     }
 
@@ -511,47 +2377,140 @@ impl Screen {
         self.deselect();
     }
 
-    pub fn undo(&mut self) {
-        if let Some((_, last)) = self.undo_stack.last() {
-            let kind = std::mem::discriminant(last);
+    // Add a cursor at the next occurrence of the selected text, or of the text cursors were
+    // last added for, otherwise on the line below the lowest cursor. Returns `false` if
+    // there's nowhere left to add one
+    pub fn add_cursor(&mut self) -> bool {
+        if let Some(selection) = self.selection {
+            self.cursor_pattern = self.selected_text().filter(|t| !t.is_empty() && !t.contains('\n'));
+            self.cursor = Cursor::from_byte(&self.buffer, selection.end.x, selection.end.y);
+            self.deselect();
+        } else if self.extra_cursors.is_empty() {
+            self.cursor_pattern = None;
+        }
+
+        let last = self.extra_cursors.last().copied().unwrap_or(self.cursor.point());
+        let at = match self.cursor_pattern.clone() {
+            Some(pattern) => {
+                // Search from the last one added, putting everything back after
+                let (cursor, search) = (self.cursor.clone(), self.search.clone());
+                self.cursor = Cursor::from_byte(&self.buffer, last.x, last.y);
+                let found = if self.search(&pattern, true) { self.selection.map(|s| s.end) } else { None };
+                self.cursor = cursor;
+                self.search = search;
+                self.selection = None;
+                found
+            },
+            None => {
+                let lowest = self.extra_cursors.iter().copied().fold(self.cursor.point(), max);
+                let column = Cursor::from_byte(&self.buffer, lowest.x, lowest.y).column;
+                Some(lowest.y + 1)
+                    .filter(|&y| y < self.rows().end)
+                    .map(|y| Cursor::from(&self.buffer, column, y).point())
+            }
+        };
+
+        match at {
+            Some(at) if at != self.cursor.point() && !self.extra_cursors.contains(&at) => {
+                self.extra_cursors.push(at);
+                true
+            },
+            _ => false
+        }
+    }
 
-            while !self.undo_stack.is_empty() {
-                let (_, u) = self.undo_stack.last().unwrap();
-                if std::mem::discriminant(u) != kind { break; }
+    // Drop the extra cursors, returns `false` if there were none
+    pub fn single_cursor(&mut self) -> bool {
+        self.cursor_pattern = None;
+        !mem::take(&mut self.extra_cursors).is_empty()
+    }
+
+    pub fn cursor_count(&self) -> usize {
+        self.extra_cursors.len() + 1
+    }
+
+    // Move each extra cursor with `step`, like the cursor itself
+    fn move_extra_cursors<F>(&mut self, step: F) where F : Fn(&mut Cursor, &Buffer) {
+        for at in self.extra_cursors.iter_mut() {
+            let mut cursor = Cursor::from_byte(&self.buffer, at.x, at.y);
+            step(&mut cursor, &self.buffer);
+            *at = cursor.point();
+        }
+        self.merge_cursors();
+    }
+
+    // Cursors that ran into each other become one
+    fn merge_cursors(&mut self) {
+        let main = self.cursor.point();
+        let mut seen = vec![main];
+        self.extra_cursors.retain(|&at| {
+            let new = !seen.contains(&at);
+            seen.push(at);
+            new
+        });
+    }
+
+    // Make an edit at the cursor, then at each extra cursor, all undone in one step
+    pub fn at_each_cursor<F>(&mut self, mut edit: F) where F : FnMut(&mut Screen) {
+        if self.extra_cursors.is_empty() {
+            return edit(self);
+        }
+
+        // The cursor goes first, so typing carries on from it afterwards
+        let first = self.undo_stack.len();
+        self.extra_cursors.insert(0, self.cursor.point());
+        for i in 0..self.extra_cursors.len() {
+            let at = self.extra_cursors[i];
+            self.cursor = Cursor::from_byte(&self.buffer, at.x, at.y);
+            edit(self);
+            self.extra_cursors[i] = self.cursor.point();
+        }
+        let main = self.extra_cursors.remove(0);
+        self.cursor = Cursor::from_byte(&self.buffer, main.x, main.y);
+
+        self.group_undo_since(first);
+        if let Some(typing) = self.typing.as_mut() {
+            typing.1 = main;
+        }
+        self.merge_cursors();
+    }
 
-                let (cursor, undo) = self.undo_stack.pop().unwrap();
-                if let Some(redo) = self.buffer.execute(&undo) {
-                    self.redo_stack.push((self.cursor.clone(), redo));
+    pub fn undo(&mut self) {
+        self.typing = None;
+        if let Some(&(_, _, group)) = self.undo_stack.last() {
+            while self.undo_stack.last().is_some_and(|&(_, _, g)| g == group) {
+                let (cursor, undo, _) = self.undo_stack.pop().unwrap();
+                if let Some(redo) = self.execute(&undo) {
+                    self.redo_stack.push((self.cursor.clone(), redo, group));
                     self.cursor = cursor;
                 } else {
-                    break; // Failed to execute undo
+                    // Failed to execute undo, keep it for when the buffer is unlocked
+                    self.undo_stack.push((cursor, undo, group));
+                    break;
                 }
             }
         }
-        self.deselect();
     }
 
     pub fn redo(&mut self) {
-        if let Some((_, last)) = self.redo_stack.last() {
-            let kind = std::mem::discriminant(last);
-
-            while !self.redo_stack.is_empty() {
-                let (_, r) = self.redo_stack.last().unwrap();
-                if std::mem::discriminant(r) != kind { break; }
-
-                let (cursor, redo) = self.redo_stack.pop().unwrap();
-                if let Some(undo) = self.buffer.execute(&redo) {
-                    self.undo_stack.push((self.cursor.clone(), undo));
+        self.typing = None;
+        if let Some(&(_, _, group)) = self.redo_stack.last() {
+            while self.redo_stack.last().is_some_and(|&(_, _, g)| g == group) {
+                let (cursor, redo, _) = self.redo_stack.pop().unwrap();
+                if let Some(undo) = self.execute(&redo) {
+                    self.undo_stack.push((self.cursor.clone(), undo, group));
                     self.cursor = cursor;
                 } else {
-                    break; // Failed to execute redo
+                    // Failed to execute redo, keep it for when the buffer is unlocked
+                    self.redo_stack.push((cursor, redo, group));
+                    break;
                 }
             }
         }
-        self.deselect();
     }
 
     pub fn set_message(&mut self, m: Message) {
+        self.expires = Instant::now() + m.timeout();
         self.message = Some(m)
     }
 
@@ -559,62 +2518,486 @@ impl Screen {
         self.message = None
     }
 
+    // Whether some lines end differently from the rest, and what saving does to them
+    pub fn endings_warning(&self) -> Option<Message> {
+        let count = self.buffer.mixed_endings();
+        if count == 0 {
+            return None;
+        }
+        let saving = if self.buffer.settings.preserve_endings { "keeps" } else { "converts" };
+        Some(Message::Warning(format!(
+            "Mixed line endings ({} not {}), saving {} them", count, self.buffer.line_ending(), saving
+        )))
+    }
+
+    // Clear the current message once it's been shown long enough, returns whether it was
+    pub fn tick(&mut self) -> bool {
+        if self.message.is_none() || Instant::now() < self.expires {
+            return false;
+        }
+        self.clear_message();
+        true
+    }
+
     pub fn is_dirty(&self) -> bool {
         self.buffer.is_dirty()
     }
 
+    pub fn colors(&self) -> &Colors {
+        &self.colors
+    }
+
+    // When another program changed the file, unless it's being followed already
+    pub fn changed_on_disk(&self) -> Option<SystemTime> {
+        if self.follow.is_some() {
+            return None;
+        }
+        self.buffer.changed_on_disk()
+    }
+
+    pub fn keep_changes(&mut self, modified: SystemTime) {
+        self.buffer.keep_changes(modified);
+    }
+
+    // Read the file again, the edits so far can't be undone across it
+    pub fn reload(&mut self) -> io::Result<()> {
+        self.buffer.reload()?;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.typing = None;
+        self.highlights.edited(0, self.buffer.revision());
+        self.protected.clear();
+        self.narrow = None;
+        self.single_cursor();
+        self.goto(self.cursor.row, self.cursor.column);
+        Ok(())
+    }
+
+    pub fn is_readonly(&self) -> bool {
+        self.buffer.is_readonly()
+    }
+
+    // Allow or reject edits, returns whether the buffer is now read-only
+    pub fn toggle_readonly(&mut self) -> Result<bool, String> {
+        if self.follow.is_some() {
+            return Err(String::from("Buffer is being followed (C-x F to stop)"));
+        }
+        let readonly = !self.is_readonly();
+        self.buffer.set_readonly(readonly);
+        Ok(readonly)
+    }
+
+    pub fn byte_count(&self) -> usize {
+        self.buffer.byte_count()
+    }
+
+    // Remote files are saved locally then uploaded
     pub fn save(&mut self, overwrite: bool) -> io::Result<usize> {
-        self.buffer.save(overwrite)
+        let wrote = self.buffer.save(overwrite)?;
+        if let Some(remote) = self.remote.as_mut() {
+            if let Err(e) = remote.store() {
+                self.buffer.set_dirty();
+                return Err(e);
+            }
+        }
+        Ok(wrote)
     }
 
+    // The buffer becomes a local file, even if it was remote
     pub fn save_as(&mut self, path: &Path, overwrite: bool) -> io::Result<usize> {
-        self.buffer.save_as(&path, overwrite)
+        let wrote = self.buffer.save_as(path, overwrite)?;
+        self.remote = None;
+        Ok(wrote)
+    }
+
+    pub fn save_elevated(&mut self, path: &Path, program: &str, password: Option<&str>) -> io::Result<usize> {
+        self.buffer.save_elevated(path, program, password)
+    }
+
+    pub fn lines(&self) -> Vec<&str> {
+        self.buffer.lines().iter().map(|l| l.text.as_str()).collect()
+    }
+
+    pub fn filetype(&self) -> Option<&str> {
+        self.buffer.settings.filetype.as_deref()
+    }
+
+    // A unified diff from the file as saved on disk to the buffer's contents
+    pub fn disk_diff(&self) -> io::Result<Vec<String>> {
+        let saved = fs::read_to_string(self.path())?;
+        let old: Vec<&str> = saved.lines().collect();
+        let new = self.lines();
+
+        let name = self.path().display();
+        Ok(diff::unified(&old, &new, &format!("{} (saved)", name), &format!("{} (buffer)", name), 3))
     }
 
     pub fn path(&self) -> &Path {
         self.buffer.path()
     }
 
-    pub fn deselect(&mut self) {
-        self.selection = None;
+    // Where the buffer was opened from, which for remote files isn't `path`
+    pub fn is_remote(&self) -> bool {
+        self.remote.is_some()
     }
 
-    pub fn select(&mut self, direction: Direction) {
+    pub fn location(&self) -> PathBuf {
+        match &self.remote {
+            Some(remote) => PathBuf::from(remote.spec()),
+            None => self.buffer.path().to_path_buf()
+        }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    // Resolve `path` against the screen's working directory
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        self.directory.join(path)
+    }
+
+    pub fn set_directory(&mut self, path: &str) -> io::Result<()> {
+        let directory = self.resolve(path).canonicalize()?;
+        if !directory.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::NotADirectory, "Not a directory"));
+        }
+        self.directory = directory;
+        Ok(())
+    }
+
+    // The rows that are shown and can be edited
+    fn rows(&self) -> Range<usize> {
+        let count = self.buffer.line_count();
+        match &self.narrow {
+            Some(rows) => min(rows.start, count - 1)..min(rows.end, count),
+            None => 0..count
+        }
+    }
+
+    // Keep the cursor within the narrowed rows
+    fn clamp_cursor(&mut self) {
+        let rows = self.rows();
+        if self.cursor.row < rows.start {
+            self.cursor = Cursor::from_byte(&self.buffer, 0, rows.start);
+        } else if self.cursor.row >= rows.end {
+            let last = rows.end - 1;
+            let len = self.buffer.line(last).map_or(0, |l| l.text.len());
+            self.cursor = Cursor::from_byte(&self.buffer, len, last);
+        }
+    }
+
+    // Show and edit only the selected lines, returns `false` if nothing is selected.
+    // A selection ending at the start of a line doesn't take it in
+    pub fn narrow(&mut self) -> bool {
+        let rows = match self.selected_rows() {
+            Some(rows) => rows,
+            None => return false
+        };
+        self.narrow = Some(rows);
+        self.deselect();
+        true
+    }
+
+    // The rows the selection covers, leaving out the last if it ends at its start
+    fn selected_rows(&self) -> Option<Range<usize>> {
+        let selection = self.selection?;
+        let end = if selection.end.x == 0 { selection.end.y } else { selection.end.y + 1 };
+        Some(selection.start.y..max(end, selection.start.y + 1))
+    }
+
+    // Replace the selected lines, or the cursor's, with what `edit` makes of them as a
+    // single edit, keeping them selected
+    pub fn edit_lines<F>(&mut self, edit: F) where F : FnOnce(Vec<&str>) -> Vec<String> {
+        let selected = self.selection.is_some();
+        let rows = self.selected_rows().unwrap_or(self.cursor.row..self.cursor.row + 1);
+        let lines = rows.clone().map(|y| self.buffer.line(y).expect("row out-of-bounds").text.as_str()).collect();
+        let text = edit(lines).join("\n");
+
+        let bottom = rows.end - 1;
+        let start = Point { x: 0, y: rows.start };
+        let end = Point { x: self.buffer.line(bottom).map_or(0, |l| l.text.len()), y: bottom };
+        if self.buffer.slice(&start, &end) == text {
+            return;
+        }
+
         let before = self.cursor.clone();
-        self.cursor.step_cursor(&self.buffer, direction);
-        let after = self.cursor.clone();
-
-        let a = after.offset;
-        let b = before.offset;
-
-        if let Some((left, right)) = &self.selection {
-            let l = left.offset;
-            let r = right.offset;
-            if a < b { // Moved left or up
-                if b == l {
-                    self.selection = Some((after, right.clone()));
-                } else if a < l {
-                    self.selection = Some((after, left.clone()));
-                } else {
-                    self.selection = Some((left.clone(), after));
-                }
-            } else if a >= b { // Moved right or down
-                if b == r {
-                    self.selection = Some((left.clone(), after));
-                } else if a > r {
-                    self.selection = Some((right.clone(), after));
-                } else {
-                    self.selection = Some((after, right.clone()));
+        if let Some(undo) = self.execute(&Edit::Replace(start, end, text)) {
+            let end = match &undo {
+                Edit::Replace(_, end, _) => *end,
+                _ => start
+            };
+            self.push_undo((before.clone(), undo));
+            self.goto(before.row, before.column);
+            if selected {
+                self.selection = Selection::new(start, end);
+            }
+        }
+    }
+
+    // Comment out the selected lines, or the cursor's, or uncomment them if they all are.
+    // Returns `false` if the filetype has no line comments
+    pub fn toggle_comment(&mut self) -> bool {
+        match self.buffer.settings.comment.clone() {
+            Some(prefix) => {
+                self.edit_lines(|lines| transform::toggle_comment(lines, &prefix));
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn strips_trailing(&self) -> bool {
+        self.buffer.settings.strip_trailing
+    }
+
+    // Remove spaces and tabs from the ends of lines, undone in one step. Returns how many
+    // lines changed
+    pub fn strip_trailing_whitespace(&mut self) -> usize {
+        let (row, byte) = (self.cursor.row, self.cursor.byte);
+        let first = self.undo_stack.len();
+        let mut count = 0;
+
+        for y in self.rows() {
+            let text = &self.buffer.line(y).expect("row out-of-bounds").text;
+            let end = text.trim_end_matches([' ', '\t']).len();
+            if end == text.len() {
+                continue;
+            }
+            let cut = Edit::Cut(Point { x: end, y }, Point { x: text.len(), y });
+            match self.execute(&cut) {
+                Some(undo) => self.push_undo((self.cursor.clone(), undo)),
+                None => break
+            }
+            count += 1;
+        }
+
+        self.group_undo_since(first);
+        self.typing = None;
+        self.goto_byte(row, byte);
+        count
+    }
+
+    // Strip trailing whitespace before saving, in a way `unstrip` can take back
+    pub fn strip_for_save(&mut self) -> Strip {
+        let first = self.undo_stack.len();
+        let (cursor, dirty) = (self.cursor.clone(), self.is_dirty());
+        let redo = mem::take(&mut self.redo_stack);
+        let lines = self.strip_trailing_whitespace();
+        if lines == 0 {
+            self.redo_stack = redo;
+            return Strip { lines, first, redo: Vec::new(), cursor, dirty };
+        }
+        Strip { lines, first, redo, cursor, dirty }
+    }
+
+    // Put back what `strip` took off when the save didn't happen, leaving the text and
+    // its history as they were
+    pub fn unstrip(&mut self, strip: Strip) {
+        if strip.lines == 0 {
+            return;
+        }
+        while self.undo_stack.len() > strip.first {
+            if let Some((_, undo, _)) = self.undo_stack.pop() {
+                self.execute(&undo);
+            }
+        }
+        self.redo_stack = strip.redo;
+        self.cursor = strip.cursor;
+        if !strip.dirty {
+            self.buffer.set_clean();
+        }
+    }
+
+    // Join the cursor's line with the next, or all the selected lines together. With
+    // `collapse`, the space around each join becomes a single one. Returns `false` if
+    // there's no line to join
+    pub fn join_lines(&mut self, collapse: bool) -> bool {
+        let rows = self.selected_rows().unwrap_or(self.cursor.row..self.cursor.row + 1);
+        let top = rows.start;
+        let bottom = min(max(rows.end - 1, top + 1), self.rows().end - 1);
+        if bottom <= top {
+            return false;
+        }
+
+        let mut text = self.buffer.line(top).expect("row out-of-bounds").text.clone();
+        let mut join = text.len();
+        for y in top + 1..=bottom {
+            let next = &self.buffer.line(y).expect("row out-of-bounds").text;
+            if collapse {
+                text.truncate(text.trim_end().len());
+                join = text.len();
+                let next = next.trim_start();
+                if !text.is_empty() && !next.is_empty() {
+                    text.push(' ');
                 }
+                text.push_str(next);
+            } else {
+                join = text.len();
+                text.push_str(next);
+            }
+        }
+
+        let before = self.cursor.clone();
+        let end = Point { x: self.buffer.line(bottom).map_or(0, |l| l.text.len()), y: bottom };
+        if let Some(undo) = self.execute(&Edit::Replace(Point { x: 0, y: top }, end, text)) {
+            self.push_undo((before, undo));
+            self.deselect();
+            self.goto_byte(top, join);
+        }
+        true
+    }
+
+    // Show the whole buffer again, returns `false` if it wasn't narrowed
+    pub fn widen(&mut self) -> bool {
+        self.narrow.take().is_some()
+    }
+
+    pub fn deselect(&mut self) {
+        self.selection = None;
+        self.line_anchor = None;
+    }
+
+    // Select the word, run of spaces or punctuation the cursor is on, as a double click does
+    pub fn select_word(&mut self) {
+        let at = self.cursor.byte;
+        let text = &self.buffer.line(self.cursor.row).expect("row out-of-bounds").text;
+        let word = text
+            .split_word_bound_indices()
+            .map(|(i, w)| i..i + w.len())
+            .find(|r| r.contains(&at) || r.end == text.len());
+        self.deselect();
+        if let Some(range) = word {
+            let y = self.cursor.row;
+            self.set_selection(Selection::new(Point { x: range.start, y }, Point { x: range.end, y }));
+        }
+    }
+
+    // Select the cursor's line with its ending, as a triple click does
+    pub fn select_line(&mut self) {
+        self.deselect();
+        self.select_lines();
+    }
+
+    // Start or stop selecting whole lines from the cursor's, returns whether it started
+    pub fn toggle_line_select(&mut self) -> bool {
+        if self.line_anchor.is_some() {
+            self.deselect();
+            return false;
+        }
+        self.line_anchor = Some(self.cursor.row);
+        self.select_lines();
+        true
+    }
+
+    // Larger and larger regions around the selection or cursor: the word, the lines,
+    // the paragraph, then the whole buffer
+    fn expansion_candidates(&self) -> Vec<Selection> {
+        let at = self.cursor.point();
+        let (start, end) = self.selection.map_or((at, at), |s| (s.start, s.end));
+        let rows = self.rows();
+        let mut candidates = Vec::new();
+
+        if start.y == end.y {
+            let text = &self.buffer.line(start.y).expect("row out-of-bounds").text;
+            let word = text
+                .split_word_bound_indices()
+                .map(|(i, w)| (i..i + w.len(), w))
+                .find(|(r, w)| {
+                    r.start <= start.x && end.x <= r.end && w.chars().any(char::is_alphanumeric)
+                });
+            if let Some((range, _)) = word {
+                candidates.extend(Selection::new(
+                    Point { x: range.start, y: start.y },
+                    Point { x: range.end, y: end.y }
+                ));
             }
+        }
+
+        let line_end = |y: usize| Point { x: self.buffer.line(y).map_or(0, |l| l.text.len()), y };
+        candidates.extend(Selection::new(Point { x: 0, y: start.y }, line_end(end.y)));
+
+        let blank = |y: usize| self.buffer.line(y).is_none_or(|l| l.text.trim().is_empty());
+        if !blank(start.y) {
+            let top = (rows.start..start.y).rev().take_while(|&y| !blank(y)).last().unwrap_or(start.y);
+            let bottom = (end.y + 1..rows.end)
+                .take_while(|&y| !blank(y))
+                .last()
+                .unwrap_or(end.y);
+            candidates.extend(Selection::new(Point { x: 0, y: top }, line_end(bottom)));
+        }
+
+        candidates.extend(Selection::new(Point { x: 0, y: rows.start }, line_end(rows.end - 1)));
+        candidates
+    }
+
+    // Grow the selection to the next enclosing region, returns `false` if it can't grow
+    pub fn expand_selection(&mut self) -> bool {
+        if self.expansions.last() != Some(&self.selection) {
+            self.expansions = vec![self.selection];
+        }
+
+        let next = self.expansion_candidates()
+            .into_iter()
+            .find(|c| self.selection.is_none_or(|s| c.contains(&s) && *c != s));
+        match next {
+            Some(selection) => {
+                self.set_selection(Some(selection));
+                self.expansions.push(self.selection);
+                true
+            },
+            None => false
+        }
+    }
+
+    // Go back to the selection before the last expansion, returns `false` if there was none
+    pub fn shrink_selection(&mut self) -> bool {
+        if self.expansions.len() < 2 || self.expansions.last() != Some(&self.selection) {
+            self.expansions.clear();
+            return false;
+        }
+        self.expansions.pop();
+        let previous = self.expansions.last().copied().flatten();
+        self.set_selection(previous);
+        true
+    }
+
+    // Select `selection`, with the cursor at its end
+    fn set_selection(&mut self, selection: Option<Selection>) {
+        if let Some(selection) = &selection {
+            self.cursor = Cursor::from_byte(&self.buffer, selection.end.x, selection.end.y);
+        }
+        self.selection = selection;
+    }
+
+    // Select every line from the anchor to the cursor, with the ending of the last one
+    fn select_lines(&mut self) {
+        let anchor = self.line_anchor.unwrap_or(self.cursor.row);
+        let top = min(anchor, self.cursor.row);
+        let bottom = max(anchor, self.cursor.row);
+
+        let end = if bottom + 1 < self.buffer.line_count() {
+            Point { x: 0, y: bottom + 1 }
         } else {
-            self.selection = if b <= a { 
-                Some((before, after))
-            } else {
-                Some((after, before))
-            };
+            Point { x: self.buffer.line(bottom).map_or(0, |l| l.text.len()), y: bottom }
+        };
+        self.selection = Selection::new(Point { x: 0, y: top }, end);
+    }
+
+    pub fn select(&mut self, direction: Direction) {
+        let before = self.cursor.point();
+        self.cursor.step_cursor(&self.buffer, direction);
+        self.clamp_cursor();
+        if self.line_anchor.is_some() {
+            return self.select_lines();
         }
 
-        assert!(self.selection.as_ref().map_or(true, |(l, r)| l.offset <= r.offset), "Invalid selection");
+        // The end the cursor isn't at stays put
+        let anchor = match &self.selection {
+            Some(selection) if selection.start == before => selection.end,
+            Some(selection) => selection.start,
+            None => before
+        };
+        self.selection = Selection::new(anchor, self.cursor.point());
     }
 }
\ No newline at end of file
@@ -0,0 +1,45 @@
+use crate::buffer::{Change, Point};
+use std::ops::Range;
+
+// Selected text between two points in the buffer, kept in place as it's edited
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Selection {
+    pub start: Point,
+    pub end: Point
+}
+
+impl Selection {
+    // A selection between `a` and `b` in either order, `None` if it would be empty
+    pub fn new(a: Point, b: Point) -> Option<Self> {
+        let (start, end) = if a <= b { (a, b) } else { (b, a) };
+        if start == end { None } else { Some(Selection { start, end }) }
+    }
+
+    // Follow an edit, text inserted right at the end isn't selected.
+    // Returns `false` if the selected text was removed entirely
+    pub fn adjust(&mut self, change: &Change) -> bool {
+        self.start = change.map(self.start);
+        if self.end != change.start {
+            self.end = change.map(self.end);
+        }
+        self.start < self.end
+    }
+
+    pub fn contains(&self, other: &Selection) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    pub fn contains_row(&self, row: usize) -> bool {
+        (self.start.y..=self.end.y).contains(&row)
+    }
+
+    // The selected bytes of line `row`, which is `len` bytes long
+    pub fn columns(&self, row: usize, len: usize) -> Option<Range<usize>> {
+        if !self.contains_row(row) {
+            return None;
+        }
+        let start = if row == self.start.y { self.start.x } else { 0 };
+        let end = if row == self.end.y { self.end.x } else { len };
+        Some(start.min(len)..end.min(len))
+    }
+}
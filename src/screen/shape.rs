@@ -0,0 +1,81 @@
+use crate::config::ConfigFile;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Block,
+    Underline,
+    Bar
+}
+
+impl Shape {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "block" => Some(Shape::Block),
+            "underline" => Some(Shape::Underline),
+            "bar" => Some(Shape::Bar),
+            _ => None
+        }
+    }
+}
+
+// The escape sequence setting the cursor's style (DECSCUSR), 0 being the terminal's default
+pub struct Style(u8);
+
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\x1b[{} q", self.0)
+    }
+}
+
+// Goes back to the style the user set up their terminal with
+pub const RESTORE: Style = Style(0);
+
+// The cursor's shape while inserting, overwriting and typing in prompts, set from `[cursor]`
+#[derive(Debug, Clone, Copy)]
+pub struct CursorShapes {
+    pub insert: Shape,
+    pub overwrite: Shape,
+    pub prompt: Shape,
+    pub blink: bool
+}
+
+impl Default for CursorShapes {
+    fn default() -> Self {
+        CursorShapes {
+            insert: Shape::Bar,
+            overwrite: Shape::Block,
+            prompt: Shape::Underline,
+            blink: true
+        }
+    }
+}
+
+impl CursorShapes {
+    pub fn build(file: &ConfigFile) -> Result<Self, String> {
+        let mut shapes = CursorShapes::default();
+        for (key, shape) in [
+            ("insert", &mut shapes.insert),
+            ("overwrite", &mut shapes.overwrite),
+            ("prompt", &mut shapes.prompt)
+        ] {
+            if let Some(s) = file.string("cursor", key) {
+                *shape = Shape::parse(s).ok_or_else(|| {
+                    format!("Invalid cursor shape '{}' for {}, expected block, underline or bar", s, key)
+                })?;
+            }
+        }
+        shapes.blink = file.boolean("cursor", "blink").unwrap_or(true);
+        Ok(shapes)
+    }
+
+    pub fn style(&self, shape: Shape) -> Style {
+        let code = match shape {
+            Shape::Block => 1,
+            Shape::Underline => 3,
+            Shape::Bar => 5
+        };
+        // Each steady style comes right after its blinking one
+        Style(if self.blink { code } else { code + 1 })
+    }
+}
@@ -0,0 +1,141 @@
+use crate::buffer::Buffer;
+use unicode_segmentation::UnicodeSegmentation;
+use crate::width::DisplayWidth;
+use std::ops::Range;
+
+// Columns are separated by the delimiter and this much space
+const GAP: usize = 1;
+
+// Pick the delimiter for a filetype, or guess it from the first line
+pub fn delimiter(filetype: Option<&str>, first_line: &str) -> char {
+    match filetype {
+        Some("tsv") => '\t',
+        Some("csv") => ',',
+        _ if first_line.contains('\t') => '\t',
+        _ if first_line.contains(';') && !first_line.contains(',') => ';',
+        _ => ','
+    }
+}
+
+// Byte ranges of the fields in `text`, not including delimiters inside quotes
+pub fn fields(text: &str, delimiter: char) -> Vec<Range<usize>> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+
+    for (i, c) in text.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == delimiter && !quoted {
+            fields.push(start..i);
+            start = i + c.len_utf8();
+        }
+    }
+    fields.push(start..text.len());
+    fields
+}
+
+// Display-only alignment of delimiter-separated columns
+pub struct Table {
+    pub delimiter: char,
+    pub header: bool, // Keep the first line at the top when scrolled
+    widths: Vec<usize>, // Widest field in each column
+    revision: Option<usize> // Buffer revision `widths` were measured in
+}
+
+impl Table {
+    pub fn new(delimiter: char) -> Self {
+        Table {
+            delimiter,
+            header: false,
+            widths: Vec::new(),
+            revision: None
+        }
+    }
+
+    // Measure the columns again if the buffer has changed since last time
+    pub fn update(&mut self, buffer: &Buffer) {
+        if self.revision == Some(buffer.revision()) {
+            return;
+        }
+        self.revision = Some(buffer.revision());
+
+        self.widths.clear();
+        for line in buffer.lines() {
+            for (i, field) in fields(&line.text, self.delimiter).into_iter().enumerate() {
+                let width = line.text[field].display_width();
+                match self.widths.get_mut(i) {
+                    Some(w) => *w = (*w).max(width),
+                    None => self.widths.push(width)
+                }
+            }
+        }
+    }
+
+    fn start(&self, column: usize) -> usize {
+        self.widths.iter().take(column).map(|w| w + 1 + GAP).sum()
+    }
+
+    fn width(&self, column: usize) -> usize {
+        self.widths.get(column).copied().unwrap_or_default()
+    }
+
+    // How the delimiter is shown, tabs have no width of their own
+    fn separator(&self) -> char {
+        if self.delimiter == '\t' { '│' } else { self.delimiter }
+    }
+
+    // `text` with each field padded to its column's width
+    pub fn render(&self, text: &str) -> String {
+        let fields = fields(text, self.delimiter);
+        let last = fields.len() - 1;
+        let mut out = String::new();
+
+        for (i, field) in fields.into_iter().enumerate() {
+            let field = &text[field];
+            out.push_str(field);
+            if i < last {
+                let pad = self.width(i).saturating_sub(field.display_width());
+                out.extend(std::iter::repeat_n(' ', pad));
+                out.push(self.separator());
+                out.extend(std::iter::repeat_n(' ', GAP));
+            }
+        }
+        out
+    }
+
+    // Where byte `byte` of `text` is shown
+    pub fn column_at(&self, text: &str, byte: usize) -> usize {
+        for (i, field) in fields(text, self.delimiter).into_iter().enumerate() {
+            if byte <= field.end {
+                let start = self.start(i);
+                return if byte == field.end && byte < text.len() {
+                    // On the delimiter after the field
+                    start + self.width(i)
+                } else {
+                    start + text[field.start..byte].display_width()
+                };
+            }
+        }
+        self.start(0)
+    }
+
+    // The byte of `text` shown at `column`, or the nearest one before it
+    pub fn byte_at(&self, text: &str, column: usize) -> usize {
+        let fields = fields(text, self.delimiter);
+        let i = (0..fields.len())
+            .rev()
+            .find(|&i| self.start(i) <= column)
+            .unwrap_or_default();
+
+        let field = &fields[i];
+        let mut x = self.start(i);
+        for (byte, grapheme) in text[field.clone()].grapheme_indices(true) {
+            x += grapheme.display_width();
+            if x > column {
+                return field.start + byte;
+            }
+        }
+        field.end
+    }
+}
@@ -0,0 +1,67 @@
+use crate::screen::colors::Colors;
+use crate::screen::Screen;
+use crate::width::DisplayWidth;
+use termion as t;
+use std::io::{self, Write};
+use std::ops::Range;
+
+fn label(screen: &Screen) -> String {
+    let dirty = if screen.is_dirty() { "*" } else { "" };
+    format!(" {}{} ", screen.display_name(), dirty)
+}
+
+// Column ranges of the visible tabs, scrolled so that the current tab is visible
+fn layout(screens: &[Screen], index: usize, width: usize) -> Vec<(usize, Range<usize>)> {
+    let widths: Vec<usize> = screens.iter()
+        .map(|s| label(s).display_width())
+        .collect();
+
+    // Skip tabs on the left until the current one fits
+    let mut first = 0;
+    while first < index && widths[first..=index].iter().sum::<usize>() > width {
+        first += 1;
+    }
+
+    let mut start = 0;
+    let mut tabs = Vec::new();
+    for (i, w) in widths.iter().enumerate().skip(first) {
+        if start + w > width { break; }
+        tabs.push((i, start..start + w));
+        start += w;
+    }
+    tabs
+}
+
+pub fn draw<W>(out: &mut W, screens: &[Screen], index: usize, width: u16, colors: &Colors) -> io::Result<()>
+    where W : Write
+{
+    let width = width as usize;
+    write!(out, "{}", t::cursor::Goto(1, 1))?;
+
+    let mut printed = 0;
+    for (i, range) in layout(screens, index, width) {
+        if i == index {
+            write!(out, "{}{}", t::color::Bg(colors.status_bg), t::color::Fg(colors.status_fg))?;
+        } else {
+            write!(out, "{}{}", t::color::Bg(colors.tab_bg), t::color::Fg(colors.tab_fg))?;
+        }
+        write!(out, "{}", label(&screens[i]))?;
+        printed = range.end;
+    }
+
+    let remaining = width - printed;
+    write!(out, "{}{:remaining$}{}{}",
+        t::color::Bg(colors.tab_bg),
+        "",
+        t::color::Bg(t::color::Reset),
+        t::color::Fg(t::color::Reset)
+    )
+}
+
+// Find the index of the tab under the zero-based column `x`
+pub fn tab_at(screens: &[Screen], index: usize, width: u16, x: u16) -> Option<usize> {
+    layout(screens, index, width as usize)
+        .into_iter()
+        .find(|(_, r)| r.contains(&(x as usize)))
+        .map(|(i, _)| i)
+}
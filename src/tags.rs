@@ -0,0 +1,118 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+// Where a tag's definition is in its file
+#[derive(Debug, Clone)]
+pub enum Address {
+    Line(usize), // Zero-based
+    Pattern(String) // Text of the line, from a `/^...$/` search
+}
+
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub name: String,
+    pub path: PathBuf,
+    pub address: Address,
+    pub kind: Option<String>
+}
+
+impl Address {
+    // Find the row this address refers to in `lines`
+    pub fn locate(&self, lines: &[&str]) -> Option<usize> {
+        match self {
+            Address::Line(n) => Some(*n).filter(|&n| n < lines.len()),
+            Address::Pattern(p) => {
+                let (anchored, p) = match p.strip_suffix('$') {
+                    Some(p) => (true, p),
+                    None => (false, p.as_str())
+                };
+                lines.iter().position(|l| if anchored { *l == p } else { l.starts_with(p) })
+            }
+        }
+    }
+}
+
+impl Tag {
+    // e.g. `src/main.rs:12 (f)`
+    pub fn describe(&self) -> String {
+        let address = match &self.address {
+            Address::Line(n) => format!("{}", n + 1),
+            Address::Pattern(p) => String::from(p.trim_end_matches('$').trim())
+        };
+        match &self.kind {
+            Some(k) => format!("{}: {} ({})", self.path.display(), address, k),
+            None => format!("{}: {}", self.path.display(), address)
+        }
+    }
+}
+
+// Look for a `tags` file in `directory` or the nearest parent that has one
+pub fn find_file(directory: &Path) -> Option<PathBuf> {
+    directory
+        .ancestors()
+        .map(|d| d.join("tags"))
+        .find(|p| p.is_file())
+}
+
+fn parse_address(s: &str) -> Option<Address> {
+    if let Ok(n) = s.parse::<usize>() {
+        return Some(Address::Line(n.saturating_sub(1)));
+    }
+
+    // Patterns are `/^text$/` or `?^text$?`, with the delimiter and backslash escaped
+    let delimiter = s.chars().next().filter(|&c| c == '/' || c == '?')?;
+    let inner = s[1..].strip_suffix(delimiter)?;
+    let inner = inner.strip_prefix('^').unwrap_or(inner);
+
+    let mut pattern = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            pattern.extend(chars.next());
+        } else {
+            pattern.push(c);
+        }
+    }
+    Some(Address::Pattern(pattern))
+}
+
+// Find all tags called `name` in the tags file at `path`
+pub fn lookup(path: &Path, name: &str) -> io::Result<Vec<Tag>> {
+    let root = path.parent().unwrap_or(Path::new(""));
+    let reader = BufReader::new(File::open(path)?);
+    let mut tags = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        let mut fields = line.splitn(3, '\t');
+        if fields.next() != Some(name) {
+            continue;
+        }
+        let file = match fields.next() {
+            Some(f) => f,
+            None => continue
+        };
+        let rest = fields.next().unwrap_or_default();
+
+        // Extended tags end the address with `;"` followed by tab separated fields
+        let (address, extra) = rest.split_once(";\"").unwrap_or((rest, ""));
+        let address = match parse_address(address.trim_end()) {
+            Some(a) => a,
+            None => continue
+        };
+        let kind = extra
+            .split('\t')
+            .map(|f| f.strip_prefix("kind:").unwrap_or(f))
+            .find(|f| !f.is_empty() && !f.contains(':'))
+            .map(String::from);
+
+        tags.push(Tag {
+            name: String::from(name),
+            path: root.join(file),
+            address,
+            kind
+        });
+    }
+    Ok(tags)
+}
@@ -0,0 +1,21 @@
+use crate::config::ConfigFile;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Where skeletons for new files are kept, next to the config file
+pub fn directory() -> Option<PathBuf> {
+    Some(ConfigFile::path()?.parent()?.join("templates"))
+}
+
+// The skeleton for a new file at `path`, returned with the template's name. It's
+// `templates/<file name>` for names like `Makefile`, else `templates/<extension>` like `rs`
+pub fn find(path: &Path) -> Option<(String, String)> {
+    let directory = directory()?;
+    [path.file_name(), path.extension()]
+        .into_iter()
+        .flatten()
+        .find_map(|name| {
+            let text = fs::read_to_string(directory.join(name)).ok()?;
+            Some((name.to_string_lossy().into_owned(), text))
+        })
+}
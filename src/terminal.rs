@@ -0,0 +1,203 @@
+use crate::depth::{self, Depth};
+use crate::grid::{Cell, Grid};
+use crate::input::{BRACKETED_PASTE_OFF, BRACKETED_PASTE_ON};
+use crate::screen::shape;
+use termion::{clear, cursor, style};
+use std::cell;
+use std::io::{self, Write};
+use std::mem;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// What `MouseTerminal` sends to turn mouse reporting on and off
+const MOUSE_ON: &str = "\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h";
+const MOUSE_OFF: &str = "\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l";
+
+// The terminal's settings before raw mode, to go back to while suspended
+static COOKED: OnceLock<libc::termios> = OnceLock::new();
+// Set when something else asks ted to stop, like `kill -TSTP`
+static STOP: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    // Set when drawing without a terminal, see `harness`
+    static FAKE_SIZE: cell::Cell<Option<(u16, u16)>> = const { cell::Cell::new(None) };
+}
+
+// Columns and rows of the terminal, or of the pretend one while a harness runs
+pub fn size() -> io::Result<(u16, u16)> {
+    match FAKE_SIZE.with(cell::Cell::get) {
+        Some(size) => Ok(size),
+        None => termion::terminal_size()
+    }
+}
+
+// Pretend the terminal is `size` on this thread, `None` goes back to asking the real one
+pub fn fake_size(size: Option<(u16, u16)>) {
+    FAKE_SIZE.with(|s| s.set(size));
+}
+
+fn attributes() -> io::Result<libc::termios> {
+    // SAFETY: `termios` is plain data, filled in by `tcgetattr`
+    let mut termios = unsafe { mem::zeroed() };
+    if unsafe { libc::tcgetattr(libc::STDOUT_FILENO, &mut termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(termios)
+}
+
+fn set_attributes(termios: &libc::termios) -> io::Result<()> {
+    if unsafe { libc::tcsetattr(libc::STDOUT_FILENO, libc::TCSANOW, termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+extern "C" fn on_stop(_: libc::c_int) {
+    STOP.store(true, Ordering::Relaxed);
+}
+
+// Remember the terminal's settings, before it's put in raw mode, and catch the signal
+// to stop so that goes through `suspend` as well
+pub fn prepare_suspend() -> io::Result<()> {
+    let _ = COOKED.set(attributes()?);
+    unsafe { libc::signal(libc::SIGTSTP, on_stop as *const () as libc::sighandler_t) };
+    Ok(())
+}
+
+// Whether a signal asked to stop since this was last called
+pub fn stop_requested() -> bool {
+    STOP.swap(false, Ordering::Relaxed)
+}
+
+// Give the terminal back to the shell and stop until resumed with `fg`, after which the
+// next frame is drawn in full. Does nothing without `prepare_suspend`, as in tests
+pub fn suspend<T>(out: &mut Output<T>) -> io::Result<()> where T : Write {
+    let cooked = match COOKED.get() {
+        Some(cooked) => cooked,
+        None => return Ok(())
+    };
+    let raw = attributes()?;
+
+    write!(out, "{}{}{}{}{}{}", MOUSE_OFF, BRACKETED_PASTE_OFF, clear::All, cursor::Goto(1, 1), shape::RESTORE, cursor::Show)?;
+    out.flush()?;
+    set_attributes(cooked)?;
+
+    unsafe {
+        libc::signal(libc::SIGTSTP, libc::SIG_DFL);
+        libc::raise(libc::SIGTSTP);
+        // Carries on from here once resumed
+        libc::signal(libc::SIGTSTP, on_stop as *const () as libc::sighandler_t);
+    }
+
+    set_attributes(&raw)?;
+    write!(out, "{}{}", MOUSE_ON, BRACKETED_PASTE_ON)?;
+    out.flush()
+}
+
+// Output that remembers what the terminal shows, so each frame only sends the rows that
+// changed. Anything else written through it, like a prompt or a picker, means the terminal
+// no longer matches and the next frame is drawn in full.
+pub struct Output<T> where T : Write {
+    out: T,
+    shown: Option<Grid>,
+    stale: bool
+}
+
+impl<T> Output<T> where T : Write {
+    pub fn new(out: T) -> Self {
+        Output { out, shown: None, stale: true }
+    }
+
+    // Show `grid`, redrawing everything if `full` or the size changed
+    pub fn frame(&mut self, grid: Grid, full: bool) -> io::Result<()> {
+        let shown = match self.shown.take() {
+            Some(shown) if !full && !self.stale && shown.size() == grid.size() => Some(shown),
+            _ => None
+        };
+        let (_, height) = grid.size();
+
+        if shown.is_none() {
+            write!(self.out, "{}", clear::All)?;
+        }
+        for y in 0..height {
+            let cells = grid.cells(y);
+            if shown.as_ref().is_some_and(|s| s.cells(y) == cells) {
+                continue;
+            }
+            write!(self.out, "{}{}", cursor::Goto(1, y + 1), style::Reset)?;
+            self.draw_row(cells)?;
+            write!(self.out, "{}", style::Reset)?;
+        }
+
+        if let Some(n) = grid.cursor_style.filter(|&n| shown.as_ref().is_none_or(|s| s.cursor_style != Some(n))) {
+            write!(self.out, "\x1b[{} q", n)?;
+        }
+        let (x, y) = grid.cursor();
+        write!(self.out, "{}", cursor::Goto(x + 1, y + 1))?;
+        if shown.as_ref().is_none_or(|s| s.cursor_visible != grid.cursor_visible) {
+            if grid.cursor_visible {
+                write!(self.out, "{}", cursor::Show)?;
+            } else {
+                write!(self.out, "{}", cursor::Hide)?;
+            }
+        }
+
+        self.shown = Some(grid);
+        self.stale = false;
+        self.out.flush()
+    }
+
+    // Write `sequence` straight to the terminal, for ones that don't change what's shown
+    pub fn send(&mut self, sequence: &str) -> io::Result<()> {
+        write!(self.out, "{}", sequence)?;
+        self.out.flush()
+    }
+
+    // Print a row of cells, only changing colors and styles where they differ
+    fn draw_row(&mut self, cells: &[Cell]) -> io::Result<()> {
+        let depth = depth::current();
+        let mut pen = Cell::default();
+        for cell in cells {
+            let cell = depth::reduce_cell(cell, depth);
+            if (pen.bold && !cell.bold) || (pen.underline && !cell.underline) || (pen.reverse && !cell.reverse) {
+                write!(self.out, "{}", style::Reset)?;
+                pen = Cell::default();
+            }
+            if cell.bold && !pen.bold {
+                write!(self.out, "{}", style::Bold)?;
+            }
+            if cell.underline && !pen.underline {
+                write!(self.out, "{}", style::Underline)?;
+            }
+            if cell.reverse && !pen.reverse {
+                write!(self.out, "{}", style::Invert)?;
+            }
+            if cell.fg != pen.fg {
+                write!(self.out, "\x1b[{}m", depth::sgr(cell.fg, false))?;
+            }
+            if cell.bg != pen.bg {
+                write!(self.out, "\x1b[{}m", depth::sgr(cell.bg, true))?;
+            }
+            write!(self.out, "{}", cell.symbol)?;
+            pen = Cell { symbol: String::new(), ..cell };
+        }
+        Ok(())
+    }
+}
+
+impl<T> Write for Output<T> where T : Write {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stale = true;
+        match depth::current() {
+            Depth::True => self.out.write(buf),
+            depth => {
+                self.out.write_all(&depth::rewrite(buf, depth))?;
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
@@ -0,0 +1,260 @@
+// Encodings and other text transforms applied to the selection
+
+use unicode_segmentation::UnicodeSegmentation;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const HEX_PER_LINE: usize = 16;
+
+fn utf8(bytes: Vec<u8>) -> Result<String, String> {
+    String::from_utf8(bytes).map_err(|_| String::from("Result isn't valid UTF-8"))
+}
+
+pub fn base64_encode(text: &str) -> Result<String, String> {
+    let mut out = String::new();
+    for chunk in text.as_bytes().chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    Ok(out)
+}
+
+// Accepts the URL-safe alphabet too, and ignores whitespace and missing padding
+pub fn base64_decode(text: &str) -> Result<String, String> {
+    let mut bytes = Vec::new();
+    let mut n = 0u32;
+    let mut bits = 0;
+
+    for c in text.chars().filter(|c| !c.is_whitespace()).take_while(|&c| c != '=') {
+        let value = match c {
+            '-' => 62,
+            '_' => 63,
+            c => BASE64
+                .iter()
+                .position(|&b| b as char == c)
+                .ok_or(format!("Invalid Base64 character '{}'", c))? as u32
+        };
+        n = n << 6 | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((n >> bits) as u8);
+        }
+    }
+    utf8(bytes)
+}
+
+// Percent-encode everything but unreserved characters
+pub fn url_encode(text: &str) -> Result<String, String> {
+    let mut out = String::new();
+    for &b in text.as_bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    Ok(out)
+}
+
+pub fn url_decode(text: &str) -> Result<String, String> {
+    let mut bytes = Vec::new();
+    let mut rest = text.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = tail
+                .get(..2)
+                .filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+                .and_then(|h| std::str::from_utf8(h).ok());
+            let byte = hex
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+                .ok_or("Invalid percent escape")?;
+            bytes.push(byte);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    utf8(bytes)
+}
+
+// Space separated hex pairs, a line per 16 bytes
+pub fn hex_dump(text: &str) -> Result<String, String> {
+    Ok(text
+        .as_bytes()
+        .chunks(HEX_PER_LINE)
+        .map(|chunk| chunk.iter().map(|b| format!("{:02x}", b)).collect::<Vec<String>>().join(" "))
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+// Offset, hex and printable ASCII columns, like `hexdump -C`, for viewing binary files
+pub fn hex_view(bytes: &[u8]) -> String {
+    bytes
+        .chunks(HEX_PER_LINE)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut line = format!("{:08x} ", i * HEX_PER_LINE);
+            for j in 0..HEX_PER_LINE {
+                // An extra space halfway, to make columns easier to count
+                if j % 8 == 0 {
+                    line.push(' ');
+                }
+                match chunk.get(j) {
+                    Some(b) => line.push_str(&format!("{:02x} ", b)),
+                    None => line.push_str("   ")
+                }
+            }
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                .collect();
+            format!("{} |{}|", line, ascii)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// Read hex pairs back, ignoring whitespace
+pub fn hex_undump(text: &str) -> Result<String, String> {
+    let digits: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err(String::from("Odd number of hex digits"));
+    }
+
+    let bytes = digits
+        .chunks(2)
+        .map(|pair| {
+            let pair: String = pair.iter().collect();
+            Some(&pair)
+                .filter(|p| p.chars().all(|c| c.is_ascii_hexdigit()))
+                .and_then(|p| u8::from_str_radix(p, 16).ok())
+                .ok_or(format!("Invalid hex '{}'", pair))
+        })
+        .collect::<Result<Vec<u8>, String>>()?;
+    utf8(bytes)
+}
+
+
+// Sort lines, numerically by the number each starts with (lines without one count as
+// zero, like `sort -n`) or else by their text, dropping repeated lines when `unique`
+pub fn sort_lines(mut lines: Vec<&str>, reverse: bool, numeric: bool, unique: bool) -> Vec<String> {
+    if numeric {
+        lines.sort_by(|a, b| leading_number(a).total_cmp(&leading_number(b)).then(a.cmp(b)));
+    } else {
+        lines.sort();
+    }
+    if unique {
+        lines.dedup();
+    }
+    if reverse {
+        lines.reverse();
+    }
+    lines.into_iter().map(String::from).collect()
+}
+
+fn leading_number(line: &str) -> f64 {
+    let line = line.trim_start();
+    let end = line
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || c == '.' || (i == 0 && (c == '-' || c == '+'))))
+        .map_or(line.len(), |(i, _)| i);
+    // The longest part that's a number, "1.2.3" is 1.2
+    (1..=end).rev().find_map(|n| line[..n].parse().ok()).unwrap_or(0.0)
+}
+
+// Capitalize the first letter of each word and lower-case the rest
+pub fn title_case(text: &str) -> String {
+    text.split_word_bounds()
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars.as_str().to_lowercase().chars()).collect(),
+                None => String::new()
+            }
+        })
+        .collect()
+}
+
+// Comment out lines with `prefix` at the least indentation among them, or uncomment them
+// when they all already are. Blank lines are left alone
+pub fn toggle_comment(lines: Vec<&str>, prefix: &str) -> Vec<String> {
+    let filled = || lines.iter().filter(|l| !l.trim().is_empty());
+    let commented = filled().all(|l| l.trim_start().starts_with(prefix));
+    let indent = filled().map(|l| l.len() - l.trim_start().len()).min().unwrap_or(0);
+
+    lines.iter().map(|&line| {
+        if line.trim().is_empty() {
+            String::from(line)
+        } else if commented {
+            let start = line.len() - line.trim_start().len();
+            let rest = &line[start + prefix.len()..];
+            format!("{}{}", &line[..start], rest.strip_prefix(' ').unwrap_or(rest))
+        } else {
+            format!("{}{} {}", &line[..indent], prefix, &line[indent..])
+        }
+    }).collect()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_utf8() -> Result<String, String> {
+        Err(String::from("Result isn't valid UTF-8"))
+    }
+
+    #[test]
+    fn base64() {
+        // Every length of the last group, so none, one and two padding characters
+        for (text, encoded) in [
+            ("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy"), ("é ✓", "w6kg4pyT")
+        ] {
+            assert_eq!(base64_encode(text).as_deref(), Ok(encoded));
+            assert_eq!(base64_decode(encoded).as_deref(), Ok(text));
+        }
+
+        assert_eq!(base64_decode("Pz8_Pz8-").as_deref(), Ok("?????>"));
+        assert_eq!(base64_decode("Pz8/Pz8+").as_deref(), Ok("?????>"));
+        assert_eq!(base64_decode("Zm8").as_deref(), Ok("fo"));
+        assert_eq!(base64_decode(" Zm9v\nYmFy ").as_deref(), Ok("foobar"));
+        assert_eq!(base64_decode("Zm9v!"), Err(String::from("Invalid Base64 character '!'")));
+        assert_eq!(base64_decode("/w=="), invalid_utf8());
+    }
+
+    #[test]
+    fn url() {
+        for (text, encoded) in [
+            ("", ""), ("a-z_0.9~", "a-z_0.9~"), ("a b&c=d/e?", "a%20b%26c%3Dd%2Fe%3F"), ("é", "%C3%A9")
+        ] {
+            assert_eq!(url_encode(text).as_deref(), Ok(encoded));
+            assert_eq!(url_decode(encoded).as_deref(), Ok(text));
+        }
+
+        assert_eq!(url_decode("%c3%a9+").as_deref(), Ok("é+"));
+        assert_eq!(url_decode("%zz"), Err(String::from("Invalid percent escape")));
+        assert_eq!(url_decode("100%"), Err(String::from("Invalid percent escape")));
+        assert_eq!(url_decode("%4"), Err(String::from("Invalid percent escape")));
+        assert_eq!(url_decode("%ff"), invalid_utf8());
+    }
+
+    #[test]
+    fn hex() {
+        for text in ["", "hi", "é ✓", "0123456789abcdefg"] {
+            assert_eq!(hex_undump(&hex_dump(text).unwrap()).as_deref(), Ok(text));
+        }
+
+        assert_eq!(hex_dump("hi").as_deref(), Ok("68 69"));
+        assert_eq!(hex_dump(&"a".repeat(17)).unwrap().lines().count(), 2);
+        assert_eq!(hex_undump("6869\n 4A").as_deref(), Ok("hiJ"));
+        assert_eq!(hex_undump("686"), Err(String::from("Odd number of hex digits")));
+        assert_eq!(hex_undump("zz"), Err(String::from("Invalid hex 'zz'")));
+        assert_eq!(hex_undump("ff"), invalid_utf8());
+    }
+}
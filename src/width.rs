@@ -0,0 +1,96 @@
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+use std::borrow::Cow;
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Whether characters of ambiguous width (like `±` or `→`) take two cells, as they do on
+// terminals in East Asian locales. It's global since every width has to agree with it
+static AMBIGUOUS_WIDE: AtomicBool = AtomicBool::new(false);
+
+// Set from `[display] ambiguous_width`, which is `narrow`, `wide` or `auto` (the default)
+pub fn configure(setting: Option<&str>) -> Result<(), String> {
+    let wide = match setting.unwrap_or("auto") {
+        "narrow" => false,
+        "wide" => true,
+        "auto" => detect(),
+        other => return Err(format!("Invalid ambiguous_width '{}', expected narrow, wide or auto", other))
+    };
+    AMBIGUOUS_WIDE.store(wide, Ordering::Relaxed);
+    Ok(())
+}
+
+// Guess from the locale, terminals in Chinese, Japanese and Korean ones usually go wide
+fn detect() -> bool {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|v| env::var(v).ok())
+        .find(|l| !l.is_empty())
+        .unwrap_or_default();
+    ["zh", "ja", "ko"].iter().any(|l| locale.starts_with(l))
+}
+
+pub fn is_ambiguous_wide() -> bool {
+    AMBIGUOUS_WIDE.load(Ordering::Relaxed)
+}
+
+pub fn char_width(c: char) -> Option<usize> {
+    if let Some(width) = control_width(c) {
+        return Some(width);
+    }
+    if is_ambiguous_wide() { c.width_cjk() } else { c.width() }
+}
+
+// Control characters in text are spelled out, since the terminal would act on them:
+// `^G` for the C0 ones and delete, `<0085>` for the C1 ones. Tabs go up to the next tab
+// stop instead, and line breaks never make it into a line
+fn control_width(c: char) -> Option<usize> {
+    match c {
+        '\t' | '\n' | '\r' => None,
+        '\0'..='\x1f' | '\x7f' => Some(2),
+        '\u{80}'..='\u{9f}' => Some(6),
+        _ => None
+    }
+}
+
+pub fn contains_control(text: &str) -> bool {
+    text.chars().any(|c| control_width(c).is_some())
+}
+
+// `text` with its control characters spelled out
+pub fn reveal_controls(text: &str) -> Cow<'_, str> {
+    if !contains_control(text) {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(text
+        .chars()
+        .map(|c| match c {
+            _ if control_width(c).is_none() => c.to_string(),
+            '\x7f' => String::from("^?"),
+            '\0'..='\x1f' => format!("^{}", (c as u8 + 0x40) as char),
+            _ => format!("<{:04x}>", c as u32)
+        })
+        .collect())
+}
+
+// How many terminal cells text takes up
+pub trait DisplayWidth {
+    fn display_width(&self) -> usize;
+}
+
+impl DisplayWidth for str {
+    fn display_width(&self) -> usize {
+        let width = if is_ambiguous_wide() { self.width_cjk() } else { self.width() };
+        if !contains_control(self) {
+            return width;
+        }
+        // Which counts each control character as one column, not as it's spelled out
+        let extra: usize = self.chars().filter_map(control_width).map(|w| w - 1).sum();
+        width + extra
+    }
+}
+
+impl DisplayWidth for String {
+    fn display_width(&self) -> usize {
+        self.as_str().display_width()
+    }
+}
@@ -0,0 +1,204 @@
+use ted::grid::Color;
+use ted::harness::{simulate, typed};
+use ted::Config;
+use termion::event::{Event, Key, MouseButton, MouseEvent};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// A scratch directory, with config and state kept next to it so tests don't touch the user's
+fn scratch(name: &str) -> PathBuf {
+    let base = env::temp_dir().join(format!("ted-harness-{}", std::process::id()));
+    env::set_var("XDG_CONFIG_HOME", base.join("config"));
+    env::set_var("XDG_STATE_HOME", base.join("state"));
+
+    let directory = base.join(name);
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+fn config(path: &Path) -> Config {
+    let args = vec![String::from("ted"), path.display().to_string()];
+    Config::build(&args).unwrap()
+}
+
+#[test]
+fn typing_shows_up_on_screen() {
+    let path = scratch("typing").join("new.txt");
+    let snapshot = simulate(config(&path), 40, 10, typed("hello")).unwrap();
+
+    let screen = snapshot.editor.screens[snapshot.editor.index].lines().join("\n");
+    assert_eq!(screen, "hello");
+    assert!(snapshot.grid.row(0).ends_with("hello"));
+    assert!(snapshot.grid.rows().iter().any(|r| r.contains("new.txt")));
+}
+
+#[test]
+fn chords_undo_and_save() {
+    let directory = scratch("chords");
+    let path = directory.join("saved.txt");
+    // A new word starts a new undo group, so only "two" is undone
+    let mut events = typed("one two");
+    events.extend([Key::Ctrl('x'), Key::Char('z')].map(|k| Ok(Event::Key(k))));
+    events.extend([Key::Ctrl('x'), Key::Char('s')].map(|k| Ok(Event::Key(k))));
+
+    let snapshot = simulate(config(&path), 40, 10, events).unwrap();
+
+    let text = snapshot.editor.screens[0].lines().join("\n");
+    assert_eq!(text, "one ");
+    assert!(path.exists());
+    assert_eq!(fs::read_to_string(&path).unwrap(), text);
+}
+
+#[test]
+fn incremental_search_accepts_and_cancels() {
+    let directory = scratch("search");
+    let path = directory.join("words.txt");
+    fs::write(&path, "one\nfoo two\nbar foo\n").unwrap();
+
+    let search = |last: Key| {
+        let mut events = vec![Ok(Event::Key(Key::Ctrl('x'))), Ok(Event::Key(Key::Char('f')))];
+        events.extend(typed("fo"));
+        events.push(Ok(Event::Key(Key::Down)));
+        events.push(Ok(Event::Key(last)));
+        events
+    };
+
+    // Enter stays on the second match
+    let snapshot = simulate(config(&path), 40, 10, search(Key::Char('\n'))).unwrap();
+    assert_eq!(snapshot.editor.screens[0].cursor_position(), (2, 4));
+    assert!(!snapshot.grid.row(9).contains("I-search"));
+
+    // Esc goes back to the start
+    let snapshot = simulate(config(&path), 40, 10, search(Key::Esc)).unwrap();
+    assert_eq!(snapshot.editor.screens[0].cursor_position(), (0, 0));
+}
+
+#[test]
+fn syntax_colors_follow_edits() {
+    let directory = scratch("syntax");
+    let path = directory.join("main.rs");
+    fs::write(&path, "let x;\nlet y;\n").unwrap();
+
+    // Columns are offset by the line number and gutter
+    let keyword = simulate(config(&path), 40, 10, Vec::new()).unwrap().grid.cell(2, 1).fg;
+    assert_ne!(keyword, Color::Default);
+
+    // Opening a block comment on the first line colors the second one as a comment
+    let snapshot = simulate(config(&path), 40, 10, typed("/*")).unwrap();
+    assert_ne!(snapshot.grid.cell(2, 1).fg, keyword);
+    assert_eq!(snapshot.grid.cell(2, 1).fg, snapshot.grid.cell(2, 0).fg);
+}
+
+#[test]
+fn split_panes_keep_their_own_view() {
+    let directory = scratch("panes");
+    let path = directory.join("numbers.txt");
+    let numbers: Vec<String> = (1..=40).map(|n| n.to_string()).collect();
+    fs::write(&path, numbers.join("\n")).unwrap();
+
+    let chord = |c| [Key::Ctrl('x'), Key::Char(c)].map(|k| Ok(Event::Key(k)));
+    let mut events: Vec<_> = chord('3').into();
+    events.extend((0..30).map(|_| Ok(Event::Key(Key::Down))));
+    events.extend(chord('a'));
+    events.extend(typed("X"));
+
+    let snapshot = simulate(config(&path), 60, 16, events).unwrap();
+    let top = snapshot.grid.row(0);
+    assert!(top.starts_with("17 17"), "{}", top);
+    assert!(top.ends_with("\u{2502} 1 X1"), "{}", top);
+    assert_eq!(snapshot.editor.screens[0].cursor_position(), (0, 1));
+}
+
+#[test]
+fn tabs_reach_the_next_tab_stop() {
+    let directory = scratch("tabs");
+    let path = directory.join("tabbed.txt");
+    fs::write(&path, "\tx\nab\tc\n").unwrap();
+
+    // Moving up from after the second tab lands after the first, at the same column
+    let mut events: Vec<_> = [Key::Down, Key::Right, Key::Right, Key::Right, Key::Up]
+        .map(|k| Ok(Event::Key(k)))
+        .into();
+    events.extend(typed("Y"));
+
+    let snapshot = simulate(config(&path), 40, 10, events).unwrap();
+    assert_eq!(snapshot.grid.row(0).trim_end(), "1     Yx");
+    assert_eq!(snapshot.grid.row(1).trim_end(), "2 ab  c");
+    assert_eq!(snapshot.editor.screens[0].lines()[0], "\tYx");
+}
+
+#[test]
+fn new_lines_keep_the_indentation() {
+    let directory = scratch("indent");
+    let path = directory.join("indented.txt");
+    fs::write(&path, "    one\n").unwrap();
+
+    let events = |undo: bool| {
+        let mut events = vec![Ok(Event::Key(Key::End)), Ok(Event::Key(Key::Char('\n')))];
+        events.extend(typed("two"));
+        if undo {
+            events.extend([Key::Ctrl('x'), Key::Char('z'), Key::Ctrl('x'), Key::Char('z')].map(|k| Ok(Event::Key(k))));
+        }
+        events
+    };
+    let snapshot = simulate(config(&path), 40, 10, events(false)).unwrap();
+    assert_eq!(snapshot.editor.screens[0].lines(), ["    one", "    two"]);
+
+    // Undoing the new line takes its indentation too
+    let snapshot = simulate(config(&path), 40, 10, events(true)).unwrap();
+    assert_eq!(snapshot.editor.screens[0].lines(), ["    one"]);
+}
+
+#[test]
+fn undo_takes_back_a_word_at_a_time() {
+    let path = scratch("undo").join("words.txt");
+    let undo = || [Key::Ctrl('x'), Key::Char('z')].map(|k| Ok(Event::Key(k)));
+
+    let mut events = typed("hello world");
+    events.extend(undo());
+    let snapshot = simulate(config(&path), 40, 10, events).unwrap();
+    assert_eq!(snapshot.editor.screens[0].lines(), ["hello "]);
+
+    let mut events = typed("hello world");
+    events.extend(undo());
+    events.extend(undo());
+    let snapshot = simulate(config(&path), 40, 10, events).unwrap();
+    assert_eq!(snapshot.editor.screens[0].lines(), [""]);
+}
+
+#[test]
+fn double_and_triple_clicks_select_a_word_and_a_line() {
+    let path = scratch("clicks").join("words.txt");
+    fs::write(&path, "foo bar baz\nsecond\n").unwrap();
+    // Column 8 is on "bar", past the line numbers
+    let clicks = |n| (0..n).map(|_| Ok(Event::Mouse(MouseEvent::Press(MouseButton::Left, 8, 1)))).collect::<Vec<_>>();
+
+    let snapshot = simulate(config(&path), 40, 10, clicks(2)).unwrap();
+    assert_eq!(snapshot.editor.screens[0].selected_text().as_deref(), Some("bar"));
+
+    let snapshot = simulate(config(&path), 40, 10, clicks(3)).unwrap();
+    assert_eq!(snapshot.editor.screens[0].selected_text().as_deref(), Some("foo bar baz\n"));
+}
+
+#[test]
+fn extra_cursors_edit_together_and_undo_together() {
+    let path = scratch("cursors").join("lines.txt");
+    fs::write(&path, "one\ntwo\nthree\n").unwrap();
+    let keys = |keys: &[Key]| keys.iter().map(|&k| Ok(Event::Key(k))).collect::<Vec<_>>();
+
+    let events = |undo: bool| {
+        let mut events = keys(&[Key::Ctrl('x'), Key::Char('M'), Key::Ctrl('x'), Key::Char('M')]);
+        events.extend(typed("- "));
+        if undo {
+            events.extend(keys(&[Key::Ctrl('x'), Key::Char('z')]));
+        }
+        events
+    };
+    let snapshot = simulate(config(&path), 40, 10, events(false)).unwrap();
+    assert_eq!(snapshot.editor.screens[0].lines(), ["- one", "- two", "- three"]);
+
+    let snapshot = simulate(config(&path), 40, 10, events(true)).unwrap();
+    assert_eq!(snapshot.editor.screens[0].lines(), ["one", "two", "three"]);
+}